@@ -0,0 +1,206 @@
+use crate::{
+    error::{Error, Result},
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
+    operator_creation,
+    primitives::Raster,
+    source::{CreateUnaryOperator, OperatorParams, Query, RasterSource, Source},
+    ArithmeticPixel, BoxedRasterType, CreateBoxedUnaryOperator, MetaOperator, OverflowPolicy,
+};
+use num_traits::{NumCast, One};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign};
+
+/// The parameters of the `AddConstantOperator`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AddConstantParams {
+    pub constant: f64,
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+}
+
+impl AddConstantParams {
+    pub fn new(constant: f64) -> Self {
+        AddConstantParams {
+            constant,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+
+    /// the constant as a pixel of type `T`. Integer types only accept whole numbers in their range.
+    pub fn typed_constant<T: ArithmeticPixel>(&self) -> Result<T> {
+        let converted = if self.constant.fract() != 0. && !T::TYPE.is_float() {
+            None
+        } else {
+            <T as NumCast>::from(self.constant)
+        };
+        converted.ok_or_else(|| Error::InvalidParameter {
+            name: "constant".to_string(),
+            reason: format!("{} is not a valid {:?} value", self.constant, T::TYPE),
+        })
+    }
+}
+
+impl OperatorParams for AddConstantParams {
+    fn validate(&self) -> Result<()> {
+        if self.constant.is_finite() {
+            Ok(())
+        } else {
+            Err(Error::InvalidParameter {
+                name: "constant".to_string(),
+                reason: "the constant must be finite".to_string(),
+            })
+        }
+    }
+}
+
+/// The AddConstantOperator adds a constant to every valid pixel.
+#[derive(Debug, Clone)]
+pub struct AddConstantOperator<S> {
+    pub source: S,
+    pub params: AddConstantParams,
+}
+
+impl<T, S> Source for AddConstantOperator<S>
+where
+    S: Source<Output = Raster<T>>,
+    T: ArithmeticPixel,
+{
+    type Output = Raster<T>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        let constant = self.params.typed_constant::<T>()?;
+        let r = self.source.query(query)?;
+        let results = r
+            .data
+            .iter()
+            .map(|&p| {
+                if r.is_no_data(p) {
+                    None
+                } else {
+                    self.params.overflow.add(p, constant)
+                }
+            })
+            .collect();
+        let no_data_value = r.no_data_value.unwrap_or(T::NO_DATA);
+        Raster::from_results(
+            r.shape,
+            r.bands,
+            r.geo_transform,
+            results,
+            Some(no_data_value),
+        )
+    }
+}
+
+impl<S> CreateUnaryOperator<S, AddConstantParams> for AddConstantOperator<S> {
+    fn create<T1>(source: S, params: AddConstantParams) -> Self {
+        AddConstantOperator { source, params }
+    }
+}
+
+/// Adds a constant to every valid pixel.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MetaAddConstantOperator {
+    #[serde(flatten)]
+    pub params: AddConstantParams,
+    #[schemars(skip)]
+    pub sources: Vec<Box<dyn MetaRasterOperator>>,
+}
+
+impl MetaAddConstantOperator {
+    pub const REQUIRES_TYPES: [RasterWants; 1] = [RasterWants::Any];
+
+    fn create_typed_raster_op<T>(&self) -> Result<Box<dyn RasterSource<RasterType = T>>>
+    where
+        T: BoxedRasterType + Add + AddAssign + One + ArithmeticPixel,
+    {
+        self.params.validate()?;
+        self.params.typed_constant::<T>()?;
+        operator_creation::create_operator_unary_raster::<Self, T, _>(
+            self.create_raster_source(0)?,
+            self.params,
+        )
+    }
+}
+
+impl CreateBoxedUnaryOperator<AddConstantParams> for MetaAddConstantOperator {
+    fn create_unary_boxed<T1>(
+        source: Box<dyn RasterSource<RasterType = T1>>,
+        params: AddConstantParams,
+    ) -> Box<dyn RasterSource<RasterType = T1>>
+    where
+        T1: Add + AddAssign + One + ArithmeticPixel,
+    {
+        Box::new(AddConstantOperator::create::<T1>(source, params))
+    }
+}
+
+impl MetaOperator for MetaAddConstantOperator {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        self.sources.as_slice()
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::REQUIRES_TYPES
+    }
+}
+
+#[typetag::serde]
+impl MetaRasterOperator for MetaAddConstantOperator {
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        match self.params.overflow {
+            OverflowPolicy::Promote => Ok(RasterCreates::UpgradesInput(0)),
+            _ => Ok(RasterCreates::SameAsInput(0)),
+        }
+    }
+
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaAddConstantOperator: Raster
+        .with_raster_sources(&MetaAddConstantOperator::REQUIRES_TYPES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{gdal_source, query},
+        RasterType, StaticRasterType,
+    };
+
+    #[test]
+    fn adds_constants() {
+        let operator = MetaAddConstantOperator {
+            params: AddConstantParams::new(3.),
+            sources: vec![gdal_source("add_constant_u8.tif", vec![1u8, 2, 3, 254])],
+        };
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![4, 5, 6, u8::NO_DATA]);
+        assert_eq!(raster.no_data_value, Some(u8::NO_DATA));
+    }
+
+    #[test]
+    fn rejects_fractional_constants_for_integers() {
+        let operator = MetaAddConstantOperator {
+            params: AddConstantParams::new(0.5),
+            sources: vec![gdal_source(
+                "add_constant_fraction_u8.tif",
+                vec![1u8, 2, 3, 254],
+            )],
+        };
+        assert_eq!(operator.creates_type().unwrap(), RasterType::U8);
+        let error = operator.create_raster_op().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "MetaAddConstantOperator: the parameter constant is invalid: 0.5 is not a valid U8 value"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// An enum for the types of attribute columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AttributeType {
+    Int,
+    Float,
+    Text,
+    Bool,
+}
+
+/// A single attribute value. Every column is nullable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Null,
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeValue::Null => Ok(()),
+            AttributeValue::Int(value) => write!(f, "{}", value),
+            AttributeValue::Float(value) => write!(f, "{}", value),
+            AttributeValue::Text(value) => write!(f, "{}", value),
+            AttributeValue::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The values of one attribute for all features of a collection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeColumn {
+    Int(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    Text(Vec<Option<String>>),
+    Bool(Vec<Option<bool>>),
+}
+
+impl AttributeColumn {
+    /// an empty column of the given type
+    pub fn empty(attribute_type: AttributeType) -> Self {
+        match attribute_type {
+            AttributeType::Int => AttributeColumn::Int(Vec::new()),
+            AttributeType::Float => AttributeColumn::Float(Vec::new()),
+            AttributeType::Text => AttributeColumn::Text(Vec::new()),
+            AttributeType::Bool => AttributeColumn::Bool(Vec::new()),
+        }
+    }
+
+    pub fn attribute_type(&self) -> AttributeType {
+        match self {
+            AttributeColumn::Int(_) => AttributeType::Int,
+            AttributeColumn::Float(_) => AttributeType::Float,
+            AttributeColumn::Text(_) => AttributeType::Text,
+            AttributeColumn::Bool(_) => AttributeType::Bool,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            AttributeColumn::Int(values) => values.len(),
+            AttributeColumn::Float(values) => values.len(),
+            AttributeColumn::Text(values) => values.len(),
+            AttributeColumn::Bool(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the value of a feature or `None` if the index is out of bounds
+    pub fn get(&self, feature: usize) -> Option<AttributeValue> {
+        let value = match self {
+            AttributeColumn::Int(values) => values.get(feature)?.map(AttributeValue::Int),
+            AttributeColumn::Float(values) => values.get(feature)?.map(AttributeValue::Float),
+            AttributeColumn::Text(values) => values.get(feature)?.clone().map(AttributeValue::Text),
+            AttributeColumn::Bool(values) => values.get(feature)?.map(AttributeValue::Bool),
+        };
+        Some(value.unwrap_or(AttributeValue::Null))
+    }
+
+    /// appends a value. `Null` can be appended to any column.
+    pub fn push(&mut self, value: AttributeValue) -> Result<()> {
+        match (self, value) {
+            (AttributeColumn::Int(values), AttributeValue::Int(value)) => values.push(Some(value)),
+            (AttributeColumn::Float(values), AttributeValue::Float(value)) => {
+                values.push(Some(value))
+            }
+            (AttributeColumn::Text(values), AttributeValue::Text(value)) => {
+                values.push(Some(value))
+            }
+            (AttributeColumn::Bool(values), AttributeValue::Bool(value)) => {
+                values.push(Some(value))
+            }
+            (AttributeColumn::Int(values), AttributeValue::Null) => values.push(None),
+            (AttributeColumn::Float(values), AttributeValue::Null) => values.push(None),
+            (AttributeColumn::Text(values), AttributeValue::Null) => values.push(None),
+            (AttributeColumn::Bool(values), AttributeValue::Null) => values.push(None),
+            (column, value) => {
+                return Err(Error::AttributeTypeMismatch {
+                    expected: column.attribute_type(),
+                    value: format!("{:?}", value),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// a new column with the values of the given features
+    pub fn select(&self, features: &[usize]) -> Self {
+        fn select<T: Clone>(values: &[T], features: &[usize]) -> Vec<T> {
+            features.iter().map(|&i| values[i].clone()).collect()
+        }
+
+        match self {
+            AttributeColumn::Int(values) => AttributeColumn::Int(select(values, features)),
+            AttributeColumn::Float(values) => AttributeColumn::Float(select(values, features)),
+            AttributeColumn::Text(values) => AttributeColumn::Text(select(values, features)),
+            AttributeColumn::Bool(values) => AttributeColumn::Bool(select(values, features)),
+        }
+    }
+}
+
+/// The name and type of an attribute column.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub attribute_type: AttributeType,
+}
+
+/// The ordered attribute columns of a feature collection. It is known before a workflow runs.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct AttributeSchema {
+    columns: Vec<AttributeDefinition>,
+}
+
+impl AttributeSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a column. Column names must be unique.
+    pub fn with_column(mut self, name: &str, attribute_type: AttributeType) -> Result<Self> {
+        if self.contains(name) {
+            return Err(Error::DuplicateAttribute(name.to_string()));
+        }
+        self.columns.push(AttributeDefinition {
+            name: name.to_string(),
+            attribute_type,
+        });
+        Ok(self)
+    }
+
+    pub fn columns(&self) -> &[AttributeDefinition] {
+        &self.columns
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.attribute_type(name).is_some()
+    }
+
+    /// the type of a column or `None` if there is no column with this name
+    pub fn attribute_type(&self, name: &str) -> Option<AttributeType> {
+        self.columns
+            .iter()
+            .find(|column| column.name == name)
+            .map(|column| column.attribute_type)
+    }
+
+    /// checks that a column exists and has the expected type
+    pub fn require(&self, name: &str, attribute_type: AttributeType) -> Result<()> {
+        match self.attribute_type(name) {
+            Some(found) if found == attribute_type => Ok(()),
+            Some(found) => Err(Error::AttributeTypeMismatch {
+                expected: attribute_type,
+                value: format!("the column {} of type {:?}", name, found),
+            }),
+            None => Err(Error::UnknownAttribute(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_are_nullable() {
+        let mut column = AttributeColumn::empty(AttributeType::Text);
+        column.push(AttributeValue::Text("a".to_string())).unwrap();
+        column.push(AttributeValue::Null).unwrap();
+
+        assert_eq!(column.get(0), Some(AttributeValue::Text("a".to_string())));
+        assert_eq!(column.get(1), Some(AttributeValue::Null));
+        assert_eq!(column.get(2), None);
+        assert!(column.push(AttributeValue::Int(1)).is_err());
+        assert_eq!(
+            column.select(&[1, 0]),
+            AttributeColumn::Text(vec![None, Some("a".to_string())])
+        );
+    }
+
+    #[test]
+    fn schema() {
+        let schema = AttributeSchema::new()
+            .with_column("id", AttributeType::Int)
+            .unwrap()
+            .with_column("temperature", AttributeType::Float)
+            .unwrap();
+
+        assert!(schema
+            .clone()
+            .with_column("id", AttributeType::Text)
+            .is_err());
+        assert!(schema.require("temperature", AttributeType::Float).is_ok());
+        assert!(matches!(
+            schema.require("temperature", AttributeType::Int),
+            Err(Error::AttributeTypeMismatch { .. })
+        ));
+        assert!(matches!(
+            schema.require("humidity", AttributeType::Float),
+            Err(Error::UnknownAttribute(_))
+        ));
+    }
+}
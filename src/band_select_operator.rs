@@ -0,0 +1,179 @@
+use crate::{
+    error::{Error, Result},
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
+    primitives::Raster,
+    result_descriptor::RasterResultDescriptor,
+    source::{CreateUnaryOperator, OperatorParams, Query, RasterSource, Source},
+    BoxedRasterType, MetaOperator,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The parameters of the `BandSelectOperator`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BandSelectParams {
+    /// the zero based bands in the order of the output, e.g. `[3, 2, 1]` for a false color image
+    pub bands: Vec<usize>,
+}
+
+impl OperatorParams for BandSelectParams {
+    fn validate(&self) -> Result<()> {
+        if self.bands.is_empty() {
+            return Err(Error::InvalidParameter {
+                name: "bands".to_string(),
+                reason: "at least one band must be selected".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The BandSelectOperator keeps the given bands of its source in the given order. Bands may be repeated.
+#[derive(Debug, Clone)]
+pub struct BandSelectOperator<S> {
+    pub source: S,
+    pub params: BandSelectParams,
+}
+
+impl<T, S> Source for BandSelectOperator<S>
+where
+    S: RasterSource<RasterType = T>,
+    T: Copy,
+{
+    type Output = Raster<T>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        self.source
+            .raster_query(query)?
+            .select_bands(&self.params.bands)
+    }
+}
+
+impl<S> CreateUnaryOperator<S, BandSelectParams> for BandSelectOperator<S> {
+    fn create<T1>(source: S, params: BandSelectParams) -> Self {
+        BandSelectOperator { source, params }
+    }
+}
+
+/// Selects and reorders the bands of a raster.
+///
+/// The MetaBandSelectOperator selects bands from its source.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MetaBandSelectOperator {
+    #[serde(flatten)]
+    pub params: BandSelectParams,
+    #[schemars(skip)]
+    pub sources: Vec<Box<dyn MetaRasterOperator>>,
+}
+
+impl MetaBandSelectOperator {
+    pub const REQUIRES_TYPES: [RasterWants; 1] = [RasterWants::Any];
+
+    fn create_typed_raster_op<T: BoxedRasterType>(
+        &self,
+    ) -> Result<Box<dyn RasterSource<RasterType = T>>> {
+        self.params.validate()?;
+        let source = self.create_raster_source(0)?.get::<T>()?;
+        Ok(Box::new(BandSelectOperator::create::<T>(
+            source,
+            self.params.clone(),
+        )))
+    }
+}
+
+impl MetaOperator for MetaBandSelectOperator {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        self.sources.as_slice()
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::REQUIRES_TYPES
+    }
+}
+
+#[typetag::serde]
+impl MetaRasterOperator for MetaBandSelectOperator {
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        Ok(RasterCreates::SameAsInput(0))
+    }
+
+    /// checks that the source has all selected bands
+    fn band_count(&self) -> Result<usize> {
+        self.params.validate()?;
+        let source_bands = self.raster_source_bands(0)?;
+        match self.params.bands.iter().find(|&&band| band >= source_bands) {
+            Some(&band) => Err(Error::BandOutOfRange {
+                band,
+                bands: source_bands,
+            }),
+            None => Ok(self.params.bands.len()),
+        }
+    }
+
+    /// the selected bands keep the grid and no-data value of the source
+    fn result_descriptor(&self) -> Result<RasterResultDescriptor> {
+        Ok(RasterResultDescriptor {
+            bands: self.band_count()?,
+            ..self.raster_source_descriptor(0)?
+        })
+    }
+
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaBandSelectOperator: Raster
+        .with_raster_sources(&MetaBandSelectOperator::REQUIRES_TYPES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{query_area, write_test_geotiff_bands},
+        validate_raster_graph, GridShape, MetaGdalSource, RasterType,
+    };
+
+    fn rgb_source(name: &str) -> Box<dyn MetaRasterOperator> {
+        Box::new(MetaGdalSource::new(write_test_geotiff_bands(
+            name,
+            GridShape::new(2, 1),
+            3,
+            vec![1u8, 2, 10, 20, 100, 200],
+        )))
+    }
+
+    #[test]
+    fn selects_bands() {
+        let operator = MetaBandSelectOperator {
+            params: BandSelectParams {
+                bands: vec![2, 0, 0],
+            },
+            sources: vec![rgb_source("band_select_rgb.tif")],
+        };
+        assert_eq!(validate_raster_graph(&operator).unwrap(), RasterType::U8);
+        assert_eq!(operator.band_count().unwrap(), 3);
+
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query_area(2., 1.))
+            .unwrap();
+        assert_eq!(raster.bands, 3);
+        assert_eq!(raster.data, vec![100, 200, 1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn rejects_missing_bands() {
+        let operator = MetaBandSelectOperator {
+            params: BandSelectParams { bands: vec![1, 3] },
+            sources: vec![rgb_source("band_select_missing.tif")],
+        };
+        assert_eq!(
+            validate_raster_graph(&operator).unwrap_err().to_string(),
+            "the operator graph is invalid: MetaBandSelectOperator: \
+             there is no band 3 in a raster with 3 bands"
+        );
+    }
+}
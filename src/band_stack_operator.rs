@@ -0,0 +1,280 @@
+use crate::{
+    error::{Error, Result},
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
+    primitives::Raster,
+    raster_type::StaticRasterType,
+    result_descriptor::RasterResultDescriptor,
+    source::{Query, RasterSource, Source},
+    BoxedRasterType, MetaOperator,
+};
+use num_traits::NumCast;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The BandStackOperator combines the bands of all its sources into one raster, e.g. single band files of a satellite scene.
+/// The sources must cover the same grid.
+pub struct BandStackOperator<T> {
+    pub sources: Vec<Box<dyn RasterSource<RasterType = T>>>,
+}
+
+impl<T> Source for BandStackOperator<T>
+where
+    T: StaticRasterType + PartialOrd + NumCast,
+{
+    type Output = Raster<T>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        let mut sources = self.sources.iter();
+        let first = sources
+            .next()
+            .ok_or(Error::MissingRasterSource { index: 0 })?;
+        sources.try_fold(first.raster_query(query)?, |stack, source| {
+            stack.stack(source.raster_query(query)?)
+        })
+    }
+}
+
+/// Combines the bands of all sources into one raster.
+///
+/// The MetaBandStackOperator stacks the bands of its sources in order. They are promoted to a common type.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MetaBandStackOperator {
+    #[schemars(skip)]
+    pub sources: Vec<Box<dyn MetaRasterOperator>>,
+}
+
+impl MetaBandStackOperator {
+    /// at least one source is required, any number of further sources are stacked on it
+    pub const REQUIRES_TYPES: [RasterWants; 1] = [RasterWants::Any];
+
+    fn create_typed_raster_op<T>(&self) -> Result<Box<dyn RasterSource<RasterType = T>>>
+    where
+        T: BoxedRasterType + PartialOrd + NumCast,
+    {
+        let sources = (0..self.sources.len())
+            .map(|index| Ok(self.create_raster_source(index)?.convert::<T>()))
+            .collect::<Result<_>>()?;
+        Ok(Box::new(BandStackOperator { sources }))
+    }
+}
+
+impl MetaOperator for MetaBandStackOperator {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        self.sources.as_slice()
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::REQUIRES_TYPES
+    }
+
+    fn variadic_raster_wants(&self) -> Option<RasterWants> {
+        Some(RasterWants::Any)
+    }
+}
+
+#[typetag::serde]
+impl MetaRasterOperator for MetaBandStackOperator {
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        let mut output_type = self.raster_source_type(0)?;
+        for index in 1..self.sources.len() {
+            output_type = output_type.promoted(self.raster_source_type(index)?);
+        }
+        Ok(RasterCreates::SecificType(output_type))
+    }
+
+    fn band_count(&self) -> Result<usize> {
+        (0..self.sources.len())
+            .map(|index| self.raster_source_bands(index))
+            .sum()
+    }
+
+    /// The stack keeps the first no-data value of its sources that the created type can represent.
+    /// If there is none, missing pixels are marked with the no-data value of the created type.
+    fn result_descriptor(&self) -> Result<RasterResultDescriptor> {
+        let first = self.common_raster_source_descriptor()?;
+        let data_type = self.creates_type()?;
+        let mut no_data_values = vec![first.no_data_value];
+        for index in 1..self.sources.len() {
+            no_data_values.push(self.raster_source_descriptor(index)?.no_data_value);
+        }
+        let no_data_value = if no_data_values.iter().all(Option::is_none) {
+            None
+        } else {
+            no_data_values
+                .into_iter()
+                .flatten()
+                .find(|&no_data_value| data_type.can_represent(no_data_value))
+                .or(Some(data_type.no_data_value()))
+        };
+        Ok(RasterResultDescriptor {
+            data_type,
+            bands: self.band_count()?,
+            no_data_value,
+            ..first
+        })
+    }
+
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaBandStackOperator: Raster
+        .with_variadic_raster_sources(&MetaBandStackOperator::REQUIRES_TYPES, RasterWants::Any)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{
+            query_area, write_test_geotiff, write_test_geotiff_bands,
+            write_test_geotiff_with_no_data,
+        },
+        validate_raster_graph, BandSelectParams, GdalSource, GridShape, MetaBandSelectOperator,
+        MetaGdalSource, RasterOperatorExt, RasterType,
+    };
+    use std::marker::PhantomData;
+
+    #[test]
+    fn stacks_and_promotes_bands() {
+        let operator: Box<dyn MetaRasterOperator> = serde_json::from_value(serde_json::json!({
+            "type": "MetaBandStackOperator",
+            "sources": [
+                {
+                    "type": "MetaGdalSource",
+                    "dataset": write_test_geotiff_bands(
+                        "band_stack_rgb.tif",
+                        GridShape::new(2, 1),
+                        3,
+                        vec![1u8, 2, 3, 4, 5, 6],
+                    ),
+                    "bands": [2, 1],
+                },
+                {
+                    "type": "MetaGdalSource",
+                    "dataset": write_test_geotiff(
+                        "band_stack_nir.tif",
+                        GridShape::new(2, 1),
+                        vec![-7i16, 8],
+                    ),
+                },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(
+            validate_raster_graph(operator.as_ref()).unwrap(),
+            RasterType::I16
+        );
+        assert_eq!(operator.band_count().unwrap(), 3);
+
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<i16>()
+            .unwrap()
+            .raster_query(query_area(2., 1.))
+            .unwrap();
+        assert_eq!(raster.bands, 3);
+        assert_eq!(raster.data, vec![5, 6, 3, 4, -7, 8]);
+    }
+
+    #[test]
+    fn stacks_any_number_of_sources() {
+        let dataset = write_test_geotiff("band_stack_many.tif", GridShape::new(2, 1), vec![1u8, 2]);
+        let stack = |sources: usize| MetaBandStackOperator {
+            sources: (0..sources)
+                .map(|_| {
+                    Box::new(MetaGdalSource::new(dataset.clone())) as Box<dyn MetaRasterOperator>
+                })
+                .collect(),
+        };
+
+        let operator = stack(100);
+        assert_eq!(validate_raster_graph(&operator).unwrap(), RasterType::U8);
+        assert_eq!(operator.band_count().unwrap(), 100);
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query_area(2., 1.))
+            .unwrap();
+        assert_eq!(raster.data, [1, 2].repeat(100));
+
+        assert_eq!(
+            validate_raster_graph(&stack(0)).unwrap_err().to_string(),
+            "the operator graph is invalid: MetaBandStackOperator: the raster source 0 is missing"
+        );
+    }
+
+    #[test]
+    fn marks_missing_pixels_with_a_value_no_valid_pixel_takes() {
+        let first = GdalSource::<u8> {
+            dataset: write_test_geotiff(
+                "band_stack_no_data_first.tif",
+                GridShape::new(2, 1),
+                vec![0u8, 255],
+            ),
+            bands: None,
+            time: None,
+            data: PhantomData,
+        };
+        let second = GdalSource::<u8> {
+            dataset: write_test_geotiff_with_no_data(
+                "band_stack_no_data_second.tif",
+                GridShape::new(2, 1),
+                vec![0u8, 7],
+                0,
+            ),
+            bands: None,
+            time: None,
+            data: PhantomData,
+        };
+
+        // 0 and 255 are valid pixels of the first source
+        let raster = first.stack_bands(second).query(query_area(2., 1.)).unwrap();
+        assert_eq!(raster.data, vec![0, 255, 254, 7]);
+        assert_eq!(raster.no_data_value, Some(254));
+        assert_eq!(raster.validity_mask(), vec![true, true, false, true]);
+    }
+
+    #[test]
+    fn chains_band_operators() {
+        let source = |name: &str, data: Vec<u16>| GdalSource::<u16> {
+            dataset: write_test_geotiff(name, GridShape::new(2, 1), data),
+            bands: None,
+            time: None,
+            data: PhantomData,
+        };
+        let stack = source("band_stack_chain_a.tif", vec![1, 2])
+            .stack_bands(source("band_stack_chain_b.tif", vec![3, 4]))
+            .select_bands(vec![1]);
+        assert_eq!(stack.query(query_area(2., 1.)).unwrap().data, vec![3, 4]);
+    }
+
+    #[test]
+    fn rejects_band_mismatches_in_pixel_wise_operators() {
+        let rgb = || -> Box<dyn MetaRasterOperator> {
+            Box::new(MetaGdalSource::new(write_test_geotiff_bands(
+                "band_stack_mismatch.tif",
+                GridShape::new(2, 1),
+                3,
+                vec![0u8; 6],
+            )))
+        };
+        let operator = crate::MetaAddRasterOperator {
+            params: Default::default(),
+            sources: vec![
+                rgb(),
+                Box::new(MetaBandSelectOperator {
+                    params: BandSelectParams { bands: vec![0] },
+                    sources: vec![rgb()],
+                }),
+            ],
+        };
+        assert_eq!(
+            validate_raster_graph(&operator).unwrap_err().to_string(),
+            "the operator graph is invalid: MetaAddRasterOperator: \
+             cannot combine a raster with 3 bands and a raster with 1 bands"
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use crate::{
+    error::Result,
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
+    operator_creation,
+    primitives::Raster,
+    raster_type::{RasterType, StaticRasterType},
+    source::{CreateBinaryOperator, OperatorParams, Query, RasterSource, Source},
+    ArithmeticPixel, BoxedRasterOperatorInstance, BoxedRasterType,
+    CreateBoxedBinaryOperatorInplace, MetaOperator, OverflowPolicy,
+};
+use num_traits::{NumCast, One};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign};
+
+/// The pixel-wise operations of the `BinaryRasterOperator`.
+/// Comparisons yield 1 if they hold and 0 otherwise. The `MetaBinaryRasterOperator` creates u8 rasters for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum BinaryOperation {
+    Add,
+    Subtract,
+    Multiply,
+    /// integer divisions truncate towards zero. Divisions by zero yield no-data.
+    Divide,
+    Min,
+    Max,
+    /// integers raised to negative powers yield no-data
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl BinaryOperation {
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOperation::Equal
+                | BinaryOperation::NotEqual
+                | BinaryOperation::Less
+                | BinaryOperation::LessOrEqual
+                | BinaryOperation::Greater
+                | BinaryOperation::GreaterOrEqual
+        )
+    }
+
+    /// the result for two valid pixels or `None` if it cannot be represented.
+    /// `overflow` decides about additions, subtractions, multiplications and powers that overflow.
+    pub fn apply<T: ArithmeticPixel>(self, a: T, b: T, overflow: OverflowPolicy) -> Option<T> {
+        match self {
+            BinaryOperation::Add => overflow.add(a, b),
+            BinaryOperation::Subtract => overflow.sub(a, b),
+            BinaryOperation::Multiply => overflow.mul(a, b),
+            BinaryOperation::Divide => a.checked_div(b),
+            BinaryOperation::Min => Some(if b < a { b } else { a }),
+            BinaryOperation::Max => Some(if b > a { b } else { a }),
+            BinaryOperation::Power => overflow.pow(a, b),
+            BinaryOperation::Equal
+            | BinaryOperation::NotEqual
+            | BinaryOperation::Less
+            | BinaryOperation::LessOrEqual
+            | BinaryOperation::Greater
+            | BinaryOperation::GreaterOrEqual => Some(if self.compare(a, b) {
+                T::one()
+            } else {
+                T::zero()
+            }),
+        }
+    }
+
+    /// true if the comparison holds. Arithmetic operations never hold.
+    pub fn compare<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            BinaryOperation::Equal => a == b,
+            BinaryOperation::NotEqual => a != b,
+            BinaryOperation::Less => a < b,
+            BinaryOperation::LessOrEqual => a <= b,
+            BinaryOperation::Greater => a > b,
+            BinaryOperation::GreaterOrEqual => a >= b,
+            _ => false,
+        }
+    }
+}
+
+/// The parameters of the `BinaryRasterOperator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BinaryRasterParams {
+    pub operation: BinaryOperation,
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+}
+
+impl BinaryRasterParams {
+    pub fn new(operation: BinaryOperation) -> Self {
+        BinaryRasterParams {
+            operation,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+impl OperatorParams for BinaryRasterParams {}
+
+/// The BinaryRasterOperator combines two rasters pixel by pixel.
+/// A no-data pixel in any input or a result that cannot be represented yields a no-data pixel.
+#[derive(Debug, Clone)]
+pub struct BinaryRasterOperator<S1, S2> {
+    pub source: (S1, S2),
+    pub params: BinaryRasterParams,
+}
+
+impl<T1, T2, S1, S2> Source for BinaryRasterOperator<S1, S2>
+where
+    S1: RasterSource<RasterType = T1>,
+    S2: RasterSource<RasterType = T2>,
+    T1: ArithmeticPixel,
+    T2: ArithmeticPixel + Into<T1>,
+{
+    type Output = Raster<T1>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        let r1 = self.source.0.raster_query(query)?;
+        let r2 = self.source.1.raster_query(query)?;
+        r1.check_compatible(&r2)?;
+
+        let results = r1
+            .data
+            .iter()
+            .zip(r2.data.iter())
+            .map(|(&p1, &p2)| {
+                if r1.is_no_data(p1) || r2.is_no_data(p2) {
+                    None
+                } else {
+                    self.params
+                        .operation
+                        .apply(p1, p2.into(), self.params.overflow)
+                }
+            })
+            .collect();
+        Raster::from_results(
+            r1.shape,
+            r1.bands,
+            r1.geo_transform,
+            results,
+            Some(r1.no_data_value.unwrap_or(T1::NO_DATA)),
+        )
+    }
+}
+
+/// The ComparisonRasterOperator compares two rasters of the same type pixel by pixel.
+/// It yields 1 if the comparison holds, 0 otherwise and no-data if any input is missing.
+#[derive(Debug, Clone)]
+pub struct ComparisonRasterOperator<S1, S2> {
+    pub source: (S1, S2),
+    pub operation: BinaryOperation,
+}
+
+impl<T, S1, S2> Source for ComparisonRasterOperator<S1, S2>
+where
+    S1: RasterSource<RasterType = T>,
+    S2: RasterSource<RasterType = T>,
+    T: ArithmeticPixel,
+{
+    type Output = Raster<u8>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        let r1 = self.source.0.raster_query(query)?;
+        let r2 = self.source.1.raster_query(query)?;
+        r1.check_compatible(&r2)?;
+
+        let results = r1
+            .data
+            .iter()
+            .zip(r2.data.iter())
+            .map(|(&p1, &p2)| {
+                if r1.is_no_data(p1) || r2.is_no_data(p2) {
+                    None
+                } else {
+                    Some(self.operation.compare(p1, p2) as u8)
+                }
+            })
+            .collect();
+        // like the descriptor, keep the no-data value of the first source if a u8 can represent it
+        let no_data_value = r1
+            .no_data_value
+            .and_then(|value| value.to_f64())
+            .filter(|&value| RasterType::U8.can_represent(value))
+            .and_then(<u8 as NumCast>::from)
+            .unwrap_or(u8::NO_DATA);
+        Raster::from_results(
+            r1.shape,
+            r1.bands,
+            r1.geo_transform,
+            results,
+            Some(no_data_value),
+        )
+    }
+}
+
+impl<X1, X2> CreateBinaryOperator<X1, X2, BinaryRasterParams> for BinaryRasterOperator<X1, X2>
+where
+    X1: RasterSource,
+    X2: RasterSource,
+    X2::RasterType: Into<X1::RasterType>,
+{
+    fn create<T1, T2>(source_a: X1, source_b: X2, params: BinaryRasterParams) -> Self {
+        BinaryRasterOperator {
+            source: (source_a, source_b),
+            params,
+        }
+    }
+}
+
+impl CreateBoxedBinaryOperatorInplace<BinaryRasterParams> for MetaBinaryRasterOperator {
+    fn create_binary_boxed<T1, T2>(
+        source_a: Box<dyn RasterSource<RasterType = T1>>,
+        source_b: Box<dyn RasterSource<RasterType = T2>>,
+        params: BinaryRasterParams,
+    ) -> Box<dyn RasterSource<RasterType = T1>>
+    where
+        T1: Add + AddAssign + One + ArithmeticPixel,
+        T2: Add + AddAssign + One + Into<T1> + ArithmeticPixel,
+    {
+        Box::new(BinaryRasterOperator {
+            source: (source_a, source_b),
+            params,
+        })
+    }
+}
+
+/// Combines two rasters pixel by pixel with an arithmetic operation or a comparison.
+///
+/// The sources are promoted to a common type, which is also the output type
+/// unless the overflow policy promotes it further. Comparisons create u8 rasters.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MetaBinaryRasterOperator {
+    #[serde(flatten)]
+    pub params: BinaryRasterParams,
+    #[schemars(skip)]
+    pub sources: Vec<Box<dyn MetaRasterOperator>>,
+}
+
+impl MetaBinaryRasterOperator {
+    pub const REQUIRES_TYPES: [RasterWants; 2] = [RasterWants::Any, RasterWants::Any];
+
+    fn create_typed_raster_op<T>(&self) -> Result<Box<dyn RasterSource<RasterType = T>>>
+    where
+        T: BoxedRasterType + Add + AddAssign + One + ArithmeticPixel,
+    {
+        if self.params.operation.is_comparison() {
+            return T::from_boxed_instance(self.create_comparison()?);
+        }
+        operator_creation::create_operator_binary_raster::<Self, T, _>(
+            self.create_raster_source(0)?,
+            self.create_raster_source(1)?,
+            self.params,
+        )
+    }
+
+    /// compares the sources in their promoted type
+    fn create_comparison(&self) -> Result<BoxedRasterOperatorInstance> {
+        self.params.validate()?;
+        let source_a = self.create_raster_source(0)?;
+        let source_b = self.create_raster_source(1)?;
+        let input_type = source_a.raster_type().promoted(source_b.raster_type());
+        let operation = self.params.operation;
+        let comparison: Box<dyn RasterSource<RasterType = u8>> = crate::dispatch_raster_types!(match_type: input_type, T => {
+            Box::new(ComparisonRasterOperator {
+                source: (source_a.convert::<T>(), source_b.convert::<T>()),
+                operation,
+            })
+        });
+        Ok(u8::into_boxed_instance(comparison))
+    }
+}
+
+impl MetaOperator for MetaBinaryRasterOperator {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        self.sources.as_slice()
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::REQUIRES_TYPES
+    }
+}
+
+#[typetag::serde]
+impl MetaRasterOperator for MetaBinaryRasterOperator {
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        if self.params.operation.is_comparison() {
+            return Ok(RasterCreates::SecificType(RasterType::U8));
+        }
+        let overflow = self.params.overflow;
+        if overflow != OverflowPolicy::Promote {
+            return Ok(RasterCreates::PromotedInputs(0, 1));
+        }
+        let input_type = self
+            .raster_source_type(0)?
+            .promoted(self.raster_source_type(1)?);
+        Ok(RasterCreates::SecificType(
+            overflow.output_type(input_type)?,
+        ))
+    }
+
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaBinaryRasterOperator: Raster
+        .with_raster_sources(&MetaBinaryRasterOperator::REQUIRES_TYPES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{gdal_source, query, write_test_geotiff},
+        Coordinate2D, GridShape, MetaGdalSource, SpatialResolution, StaticRasterType,
+    };
+
+    const NO_DATA: OverflowPolicy = OverflowPolicy::NoData;
+
+    #[test]
+    fn operations() {
+        assert_eq!(BinaryOperation::Subtract.apply(3u8, 5, NO_DATA), None);
+        assert_eq!(
+            BinaryOperation::Subtract.apply(3u8, 5, OverflowPolicy::Saturate),
+            Some(0)
+        );
+        assert_eq!(
+            BinaryOperation::Subtract.apply(3u8, 5, OverflowPolicy::Wrap),
+            Some(254)
+        );
+        assert_eq!(BinaryOperation::Subtract.apply(3i16, 5, NO_DATA), Some(-2));
+        assert_eq!(BinaryOperation::Multiply.apply(100u8, 3, NO_DATA), None);
+        assert_eq!(BinaryOperation::Divide.apply(7i32, 2, NO_DATA), Some(3));
+        assert_eq!(BinaryOperation::Divide.apply(7i32, 0, NO_DATA), None);
+        assert_eq!(BinaryOperation::Divide.apply(7f32, 0., NO_DATA), None);
+        assert_eq!(BinaryOperation::Divide.apply(7f64, 2., NO_DATA), Some(3.5));
+        assert_eq!(BinaryOperation::Min.apply(7u64, 2, NO_DATA), Some(2));
+        assert_eq!(BinaryOperation::Max.apply(-7i64, 2, NO_DATA), Some(2));
+        assert_eq!(BinaryOperation::Power.apply(2u32, 10, NO_DATA), Some(1024));
+        assert_eq!(BinaryOperation::Power.apply(2i32, -1, NO_DATA), None);
+        assert_eq!(BinaryOperation::Power.apply(4f64, 0.5, NO_DATA), Some(2.));
+        assert_eq!(BinaryOperation::Power.apply(-4f64, 0.5, NO_DATA), None);
+        assert_eq!(BinaryOperation::Less.apply(1u8, 2, NO_DATA), Some(1));
+        assert_eq!(
+            BinaryOperation::GreaterOrEqual.apply(1f32, 2., NO_DATA),
+            Some(0.)
+        );
+        assert_eq!(BinaryOperation::NotEqual.apply(1i16, 2, NO_DATA), Some(1));
+    }
+
+    #[test]
+    fn divides_rasters() {
+        let source = |name: &str, data: Vec<i16>| -> Box<dyn MetaRasterOperator> {
+            Box::new(MetaGdalSource::new(write_test_geotiff(
+                name,
+                GridShape::new(2, 2),
+                data,
+            )))
+        };
+        let operator: Box<dyn MetaRasterOperator> = Box::new(MetaBinaryRasterOperator {
+            params: BinaryRasterParams::new(BinaryOperation::Divide),
+            sources: vec![
+                source("binary_divides_a.tif", vec![10, 9, -8, 7]),
+                source("binary_divides_b.tif", vec![2, 0, 3, -7]),
+            ],
+        });
+
+        // the operation is part of the serialized workflow
+        let json = serde_json::to_string(&operator).unwrap();
+        assert!(json.contains(r#""operation":"Divide""#));
+        let operator: Box<dyn MetaRasterOperator> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(operator.creates_type().unwrap(), RasterType::I16);
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<i16>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+
+        assert_eq!(raster.data, vec![5, i16::NO_DATA, -2, -1]);
+        assert_eq!(raster.no_data_value, Some(i16::NO_DATA));
+    }
+
+    #[test]
+    fn keeps_results_equal_to_the_no_data_value() {
+        let dataset = |name: &str, data: Vec<u8>, no_data_value: Option<u8>| {
+            let path = crate::test_util::temp_path(name);
+            let raster = Raster::new(
+                GridShape::new(2, 2),
+                crate::GeoTransform::new_north_up(
+                    Coordinate2D::new(0., 2.),
+                    SpatialResolution::new_square(1.).unwrap(),
+                ),
+                data,
+                no_data_value,
+            );
+            crate::write_geotiff(&path, &raster, None).unwrap();
+            Box::new(MetaGdalSource::new(path.to_string_lossy().into_owned()))
+                as Box<dyn MetaRasterOperator>
+        };
+        let query = query();
+        let operator = |operation| MetaBinaryRasterOperator {
+            params: BinaryRasterParams::new(operation),
+            sources: vec![
+                dataset("binary_no_data_0_a.tif", vec![0, 1, 2, 3], Some(0)),
+                dataset("binary_no_data_0_b.tif", vec![1, 1, 2, 1], None),
+            ],
+        };
+
+        // zero differences are valid although 0 marks missing pixels in the first source
+        let raster = operator(BinaryOperation::Subtract)
+            .create_raster_op()
+            .unwrap()
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query)
+            .unwrap();
+        assert_eq!(raster.validity_mask(), vec![false, true, true, true]);
+        assert_eq!(raster.data[1..], [0, 0, 2]);
+
+        // comparisons create u8 rasters whose false results stay valid
+        let greater = operator(BinaryOperation::Greater);
+        assert_eq!(greater.creates_type().unwrap(), RasterType::U8);
+        let raster = greater
+            .create_raster_op()
+            .unwrap()
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query)
+            .unwrap();
+        assert_eq!(raster.validity_mask(), vec![false, true, true, true]);
+        assert_eq!(raster.data[1..], [0, 0, 1]);
+    }
+
+    #[test]
+    fn compares_promoted_inputs() {
+        let operator = MetaBinaryRasterOperator {
+            params: BinaryRasterParams::new(BinaryOperation::LessOrEqual),
+            sources: vec![
+                gdal_source("binary_compare_i16.tif", vec![-1i16, 2, 3, 400]),
+                gdal_source("binary_compare_f32.tif", vec![-1.5f32, 2., 3.5, f32::NAN]),
+            ],
+        };
+        assert_eq!(operator.creates_type().unwrap(), RasterType::U8);
+        assert_eq!(
+            operator.result_descriptor().unwrap().no_data_value,
+            Some(u8::NO_DATA.into())
+        );
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![0, 1, 1, 0]);
+        assert_eq!(raster.no_data_value, Some(u8::NO_DATA));
+    }
+}
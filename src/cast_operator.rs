@@ -0,0 +1,341 @@
+use crate::{
+    error::{Error, Result},
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
+    primitives::{is_no_data, Raster},
+    raster_type::{RasterType, StaticRasterType},
+    result_descriptor::RasterResultDescriptor,
+    source::{OperatorParams, Query, RasterSource, Source},
+    BoxedRasterType, MetaOperator,
+};
+use num_traits::{AsPrimitive, Bounded, NumCast};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+/// How fractions are removed when casting to an integer type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Rounding {
+    /// rounds half-way cases away from zero
+    #[default]
+    Nearest,
+    Floor,
+    Ceil,
+    TowardZero,
+}
+
+impl Rounding {
+    pub fn apply(self, value: f64) -> f64 {
+        match self {
+            Rounding::Nearest => value.round(),
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+            Rounding::TowardZero => value.trunc(),
+        }
+    }
+}
+
+/// Maps the value range `from` linearly onto `to`, e.g. reflectances from `[0, 1]` to `[0, 10000]`.
+/// Values outside of `from` are extrapolated.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LinearScale {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+}
+
+impl LinearScale {
+    pub fn apply(&self, value: f64) -> f64 {
+        let (from_min, from_max) = self.from;
+        let (to_min, to_max) = self.to;
+        to_min + (value - from_min) * (to_max - to_min) / (from_max - from_min)
+    }
+}
+
+/// The options of the `CastOperator`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CastParams {
+    /// scales the values before they are rounded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<LinearScale>,
+    #[serde(default)]
+    pub rounding: Rounding,
+    /// clamps values outside of the output type to its valid range instead of producing no-data pixels
+    #[serde(default = "CastParams::default_clamp")]
+    pub clamp: bool,
+}
+
+impl Default for CastParams {
+    fn default() -> Self {
+        CastParams {
+            scale: None,
+            rounding: Rounding::default(),
+            clamp: Self::default_clamp(),
+        }
+    }
+}
+
+impl CastParams {
+    fn default_clamp() -> bool {
+        true
+    }
+
+    /// the valid range of `T` as `f64`. It excludes the no-data value if it is one of the bounds.
+    fn valid_range<T>() -> (f64, f64)
+    where
+        T: StaticRasterType + Bounded + NumCast + AsPrimitive<f64>,
+    {
+        // 64 bit bounds are rounded as `f64` and may not fit into `T`
+        let inward = |mut bound: f64| {
+            while T::from(bound).is_none_or(|value| is_no_data(value, T::NO_DATA)) {
+                bound = f64::from_bits(bound.to_bits() - 1);
+            }
+            bound
+        };
+        (inward(T::min_value().as_()), inward(T::max_value().as_()))
+    }
+
+    /// casts a valid pixel or returns `None` if it has no representation in `T`
+    pub fn cast<T>(&self, value: f64) -> Option<T>
+    where
+        T: StaticRasterType + Bounded + NumCast + AsPrimitive<f64>,
+    {
+        let mut value = match self.scale {
+            Some(scale) => scale.apply(value),
+            None => value,
+        };
+        if value.is_nan() {
+            return None;
+        }
+        if !T::TYPE.is_float() {
+            value = self.rounding.apply(value);
+        }
+
+        let (min, max) = Self::valid_range::<T>();
+        if value < min || value > max {
+            if !self.clamp {
+                return None;
+            }
+            value = value.clamp(min, max);
+        }
+        T::from(value)
+    }
+}
+
+impl OperatorParams for CastParams {
+    /// fails if the scale cannot map any value
+    fn validate(&self) -> Result<()> {
+        if let Some(scale) = self.scale {
+            let values = [scale.from.0, scale.from.1, scale.to.0, scale.to.1];
+            if values.iter().any(|value| !value.is_finite()) || scale.from.0 == scale.from.1 {
+                return Err(Error::InvalidParameter {
+                    name: "scale".to_string(),
+                    reason: format!(
+                        "cannot map [{}, {}] onto [{}, {}]",
+                        scale.from.0, scale.from.1, scale.to.0, scale.to.1
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The CastOperator converts the pixels of its source to `T`.
+/// No-data pixels stay no-data pixels and use the no-data value of `T`.
+#[derive(Debug, Clone)]
+pub struct CastOperator<S, T> {
+    pub source: S,
+    pub params: CastParams,
+    pub data: PhantomData<T>,
+}
+
+impl<S, T> CastOperator<S, T> {
+    pub fn new(source: S, params: CastParams) -> Self {
+        CastOperator {
+            source,
+            params,
+            data: PhantomData,
+        }
+    }
+}
+
+impl<S, F, T> Source for CastOperator<S, T>
+where
+    S: RasterSource<RasterType = F>,
+    F: StaticRasterType + AsPrimitive<f64>,
+    T: StaticRasterType + PartialOrd + Bounded + NumCast + AsPrimitive<f64>,
+{
+    type Output = Raster<T>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        let r = self.source.raster_query(query)?;
+
+        let results = r
+            .data
+            .iter()
+            .map(|&value| {
+                if r.is_no_data(value) {
+                    None
+                } else {
+                    self.params.cast::<T>(value.as_())
+                }
+            })
+            .collect();
+        // the valid range excludes the no-data value of `T`, so no valid result takes it
+        Raster::from_results(r.shape, r.bands, r.geo_transform, results, Some(T::NO_DATA))
+    }
+}
+
+/// Converts a raster to another type with optional scaling, rounding and clamping.
+///
+/// The MetaCastOperator converts any raster type to `output_type`.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MetaCastOperator {
+    pub output_type: RasterType,
+    #[serde(flatten)]
+    pub params: CastParams,
+    #[schemars(skip)]
+    pub sources: Vec<Box<dyn MetaRasterOperator>>,
+}
+
+impl MetaCastOperator {
+    pub const REQUIRES_TYPES: [RasterWants; 1] = [RasterWants::Any];
+
+    fn create_typed_raster_op<T>(&self) -> Result<Box<dyn RasterSource<RasterType = T>>>
+    where
+        T: BoxedRasterType + PartialOrd + Bounded + NumCast + AsPrimitive<f64>,
+    {
+        self.params.validate()?;
+        let source = self.create_raster_source(0)?;
+        let operator: Box<dyn RasterSource<RasterType = T>> = crate::dispatch_raster_types!(match_instance: source, source => {
+            Box::new(CastOperator::<_, T>::new(source, self.params))
+        });
+        Ok(operator)
+    }
+}
+
+impl MetaOperator for MetaCastOperator {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        self.sources.as_slice()
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::REQUIRES_TYPES
+    }
+}
+
+#[typetag::serde]
+impl MetaRasterOperator for MetaCastOperator {
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        self.params.validate()?;
+        Ok(RasterCreates::SecificType(self.output_type))
+    }
+
+    /// missing pixels are always marked with the no-data value of the output type
+    fn result_descriptor(&self) -> Result<RasterResultDescriptor> {
+        Ok(RasterResultDescriptor {
+            data_type: self.creates_type()?,
+            no_data_value: Some(self.output_type.no_data_value()),
+            ..self.raster_source_descriptor(0)?
+        })
+    }
+
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaCastOperator: Raster
+        .with_raster_sources(&MetaCastOperator::REQUIRES_TYPES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{gdal_source, query, write_test_geotiff},
+        validate_raster_graph, GridShape,
+    };
+
+    #[test]
+    fn casts_values() {
+        let params = CastParams::default();
+        assert_eq!(params.cast::<u8>(2.5), Some(3));
+        assert_eq!(params.cast::<u8>(-3.), Some(0));
+        // the largest u8 is the no-data value
+        assert_eq!(params.cast::<u8>(1000.), Some(254));
+        assert_eq!(params.cast::<i16>(-40000.), Some(i16::MIN + 1));
+        assert_eq!(params.cast::<i64>(-1e30), Some(-9223372036854774784));
+        assert_eq!(params.cast::<u64>(1e30), Some(18446744073709549568));
+        assert_eq!(params.cast::<f32>(1e40), Some(f32::MAX));
+        assert_eq!(params.cast::<f64>(f64::NAN), None);
+
+        let params = CastParams {
+            rounding: Rounding::Floor,
+            clamp: false,
+            ..CastParams::default()
+        };
+        assert_eq!(params.cast::<i32>(-2.5), Some(-3));
+        assert_eq!(params.cast::<u8>(255.), None);
+        assert_eq!(params.cast::<u8>(-0.5), None);
+
+        let params = CastParams {
+            scale: Some(LinearScale {
+                from: (0., 1.),
+                to: (0., 10000.),
+            }),
+            ..CastParams::default()
+        };
+        assert_eq!(params.cast::<u16>(0.12345), Some(1235));
+        assert_eq!(params.cast::<f32>(0.5), Some(5000.));
+    }
+
+    #[test]
+    fn packs_reflectances() {
+        let operator: Box<dyn MetaRasterOperator> = serde_json::from_value(serde_json::json!({
+            "type": "MetaCastOperator",
+            "output_type": "U8",
+            "scale": {"from": [0., 1.], "to": [0., 250.]},
+            "sources": [{
+                "type": "MetaGdalSource",
+                "dataset": write_test_geotiff(
+                    "cast_reflectance.tif",
+                    GridShape::new(2, 2),
+                    vec![0.5f32, f32::NAN, 1.5, -0.1],
+                ),
+            }],
+        }))
+        .unwrap();
+        assert_eq!(
+            validate_raster_graph(operator.as_ref()).unwrap(),
+            RasterType::U8
+        );
+
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![125, u8::NO_DATA, 254, 0]);
+        assert_eq!(raster.no_data_value, Some(u8::NO_DATA));
+    }
+
+    #[test]
+    fn rejects_empty_scales() {
+        let operator = MetaCastOperator {
+            output_type: RasterType::U16,
+            params: CastParams {
+                scale: Some(LinearScale {
+                    from: (1., 1.),
+                    to: (0., 100.),
+                }),
+                ..CastParams::default()
+            },
+            sources: vec![gdal_source("cast_empty_scale.tif", vec![1u8, 2, 3, 4])],
+        };
+        assert_eq!(
+            validate_raster_graph(&operator).unwrap_err().to_string(),
+            "the operator graph is invalid: MetaCastOperator: \
+             the parameter scale is invalid: cannot map [1, 1] onto [0, 100]"
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{
+    error::Result,
+    primitives::Raster,
+    raster_type::StaticRasterType,
+    source::{Query, RasterSource, Source},
+};
+use num_traits::NumCast;
+use std::marker::PhantomData;
+
+/// The ConvertRasterOperator converts the pixels of its source to `T`. Pixels `T` cannot represent become no-data.
+/// It is used to bring the inputs of binary operators to their promoted type.
+#[derive(Debug, Clone)]
+pub struct ConvertRasterOperator<S, T> {
+    pub source: S,
+    pub data: PhantomData<T>,
+}
+
+impl<S, T> ConvertRasterOperator<S, T> {
+    pub fn new(source: S) -> Self {
+        ConvertRasterOperator {
+            source,
+            data: PhantomData,
+        }
+    }
+}
+
+impl<S, F, T> Source for ConvertRasterOperator<S, T>
+where
+    S: RasterSource<RasterType = F>,
+    F: NumCast + PartialEq + Copy,
+    T: StaticRasterType + PartialOrd + NumCast,
+{
+    type Output = Raster<T>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        self.source.raster_query(query)?.convert()
+    }
+}
@@ -0,0 +1,489 @@
+use crate::{
+    attributes::{AttributeColumn, AttributeSchema, AttributeType, AttributeValue},
+    error::{Error, Result},
+    feature_collection::{
+        FeatureCollection, MultiPointCollection, MultiPoints, VectorCollectionType,
+    },
+    meta_raster_operator::{MetaRasterOperator, MetaVectorOperator, RasterWants},
+    primitives::{BoundingBox2D, Coordinate2D, SpatialReference},
+    result_descriptor::VectorResultDescriptor,
+    source::{CreateSourceOperator, Query, Source, VectorSource},
+    MetaOperator,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The errors that can occur while reading CSV files.
+#[derive(Debug)]
+pub enum CsvError {
+    Csv(csv::Error),
+    /// the delimiter is not a single byte character
+    InvalidDelimiter(char),
+    /// the file has no column with this name
+    MissingColumn(String),
+    /// the header names two columns alike
+    DuplicateColumn(String),
+    /// a coordinate or time cannot be parsed. Rows are counted from 1, not counting the header.
+    InvalidValue {
+        row: usize,
+        column: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::Csv(error) => write!(f, "csv error: {}", error),
+            CsvError::InvalidDelimiter(delimiter) => {
+                write!(f, "the delimiter {:?} is not an ASCII character", delimiter)
+            }
+            CsvError::MissingColumn(column) => write!(f, "there is no column {}", column),
+            CsvError::DuplicateColumn(column) => {
+                write!(f, "the column {} appears more than once", column)
+            }
+            CsvError::InvalidValue { row, column, value } => write!(
+                f,
+                "invalid value {:?} in row {} of column {}",
+                value, row, column
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CsvError::Csv(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for CsvError {
+    fn from(error: csv::Error) -> Self {
+        CsvError::Csv(error)
+    }
+}
+
+/// Describes how points are read from a CSV file.
+/// Without a header the columns are named by their zero based index, i.e. `"0"`, `"1"`, ….
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct CsvSourceParams {
+    pub dataset: String,
+    pub x_column: String,
+    pub y_column: String,
+    /// a column of integer time stamps. Rows outside of the query time interval are skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_column: Option<String>,
+    #[serde(default = "CsvSourceParams::default_delimiter")]
+    pub delimiter: char,
+    #[serde(default = "CsvSourceParams::default_header")]
+    pub header: bool,
+    /// the coordinate system of the coordinates, WGS 84 longitudes and latitudes by default
+    #[serde(default = "SpatialReference::wgs84")]
+    pub spatial_reference: SpatialReference,
+}
+
+/// The column names and rows of a CSV file.
+struct CsvTable {
+    columns: Vec<String>,
+    rows: Vec<csv::StringRecord>,
+}
+
+impl CsvTable {
+    fn column_index(&self, name: &str) -> Result<usize, CsvError> {
+        self.columns
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| CsvError::MissingColumn(name.to_string()))
+    }
+
+    fn parse<T: std::str::FromStr>(&self, row: usize, column: usize) -> Result<T, CsvError> {
+        let value = self.rows[row][column].trim();
+        value.parse().map_err(|_| CsvError::InvalidValue {
+            row: row + 1,
+            column: self.columns[column].clone(),
+            value: value.to_string(),
+        })
+    }
+}
+
+impl CsvSourceParams {
+    fn default_delimiter() -> char {
+        ','
+    }
+
+    fn default_header() -> bool {
+        true
+    }
+
+    fn read_table(&self) -> Result<CsvTable, CsvError> {
+        if !self.delimiter.is_ascii() {
+            return Err(CsvError::InvalidDelimiter(self.delimiter));
+        }
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter as u8)
+            .has_headers(self.header)
+            .from_path(&self.dataset)?;
+
+        let rows = reader
+            .records()
+            .collect::<Result<Vec<csv::StringRecord>, _>>()?;
+        let columns: Vec<String> = if self.header {
+            reader.headers()?.iter().map(str::to_string).collect()
+        } else {
+            let width = rows.first().map_or(0, csv::StringRecord::len);
+            (0..width).map(|index| index.to_string()).collect()
+        };
+        if let Some(column) = columns
+            .iter()
+            .enumerate()
+            .find(|(index, column)| columns[..*index].contains(column))
+            .map(|(_, column)| column)
+        {
+            return Err(CsvError::DuplicateColumn(column.clone()));
+        }
+        Ok(CsvTable { columns, rows })
+    }
+
+    /// the indices of the attribute columns, i.e. all columns except the coordinates and time
+    fn attribute_columns(&self, table: &CsvTable) -> Result<Vec<usize>, CsvError> {
+        let mut excluded = vec![
+            table.column_index(&self.x_column)?,
+            table.column_index(&self.y_column)?,
+        ];
+        if let Some(time_column) = &self.time_column {
+            excluded.push(table.column_index(time_column)?);
+        }
+        Ok((0..table.columns.len())
+            .filter(|column| !excluded.contains(column))
+            .collect())
+    }
+
+    /// the attributes of the points. Their types are inferred from the values.
+    pub fn schema(&self) -> Result<AttributeSchema, CsvError> {
+        let table = self.read_table()?;
+        let schema = self.attribute_columns(&table)?.into_iter().fold(
+            AttributeSchema::new(),
+            |schema, column| {
+                schema
+                    .with_column(&table.columns[column], column_type(&table, column))
+                    .expect("the header has unique column names")
+            },
+        );
+        Ok(schema)
+    }
+
+    /// the area covered by the points or `None` if the file has no rows
+    pub fn bounding_box(&self) -> Result<Option<BoundingBox2D>, CsvError> {
+        let table = self.read_table()?;
+        let x = table.column_index(&self.x_column)?;
+        let y = table.column_index(&self.y_column)?;
+        let points = (0..table.rows.len())
+            .map(|row| {
+                Ok(Coordinate2D::new(
+                    table.parse(row, x)?,
+                    table.parse(row, y)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, CsvError>>()?;
+        Ok(BoundingBox2D::from_coordinates(points))
+    }
+
+    /// reads the rows inside the query rectangle and time interval as single points
+    pub fn read(&self, query: &Query) -> Result<MultiPointCollection, CsvError> {
+        let table = self.read_table()?;
+        let x = table.column_index(&self.x_column)?;
+        let y = table.column_index(&self.y_column)?;
+        let time = self
+            .time_column
+            .as_ref()
+            .map(|time_column| table.column_index(time_column))
+            .transpose()?;
+
+        let mut rows = Vec::new();
+        let mut points = Vec::new();
+        for row in 0..table.rows.len() {
+            let point = Coordinate2D::new(table.parse(row, x)?, table.parse(row, y)?);
+            if let Some(time) = time {
+                if !query
+                    .time_interval
+                    .contains_instant(table.parse(row, time)?)
+                {
+                    continue;
+                }
+            }
+            if query.bbox.contains_coordinate(&point) {
+                rows.push(row);
+                points.push(vec![point]);
+            }
+        }
+
+        let mut collection = FeatureCollection::new(MultiPoints::from_features(points));
+        for column in self.attribute_columns(&table)? {
+            let attribute_type = column_type(&table, column);
+            let mut values = AttributeColumn::empty(attribute_type);
+            for &row in &rows {
+                values
+                    .push(parse_value(&table.rows[row][column], attribute_type))
+                    .expect("the values match the inferred column type");
+            }
+            collection = collection
+                .with_attribute(&table.columns[column], values)
+                .expect("the header has unique column names");
+        }
+        Ok(collection)
+    }
+}
+
+/// the type of a value or `None` if it is empty
+fn value_type(value: &str) -> Option<AttributeType> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else if value.parse::<i64>().is_ok() {
+        Some(AttributeType::Int)
+    } else if value.parse::<f64>().is_ok() {
+        Some(AttributeType::Float)
+    } else if value.parse::<bool>().is_ok() {
+        Some(AttributeType::Bool)
+    } else {
+        Some(AttributeType::Text)
+    }
+}
+
+/// the narrowest type all values of a column can be parsed as. Columns that mix numbers and text are text.
+fn column_type(table: &CsvTable, column: usize) -> AttributeType {
+    table
+        .rows
+        .iter()
+        .filter_map(|row| value_type(&row[column]))
+        .fold(None, |column_type, value_type| {
+            match (column_type, value_type) {
+                (None, value_type) => Some(value_type),
+                (Some(column_type), value_type) if column_type == value_type => Some(column_type),
+                (Some(AttributeType::Int), AttributeType::Float)
+                | (Some(AttributeType::Float), AttributeType::Int) => Some(AttributeType::Float),
+                _ => Some(AttributeType::Text),
+            }
+        })
+        .unwrap_or(AttributeType::Text)
+}
+
+fn parse_value(value: &str, attribute_type: AttributeType) -> AttributeValue {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return AttributeValue::Null;
+    }
+    let value = match attribute_type {
+        AttributeType::Int => trimmed.parse().ok().map(AttributeValue::Int),
+        AttributeType::Float => trimmed.parse().ok().map(AttributeValue::Float),
+        AttributeType::Bool => trimmed.parse().ok().map(AttributeValue::Bool),
+        AttributeType::Text => Some(AttributeValue::Text(value.to_string())),
+    };
+    value.unwrap_or(AttributeValue::Null)
+}
+
+/// A CsvPointSource produces a point for every row of a CSV file.
+#[derive(Debug, Clone)]
+pub struct CsvPointSource {
+    pub params: CsvSourceParams,
+}
+
+// It is a Source producing MultiPointCollections -> its a VectorSource
+impl Source for CsvPointSource {
+    type Output = MultiPointCollection;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        self.params.read(&query).map_err(|source| Error::Dataset {
+            dataset: self.params.dataset.clone(),
+            source: source.into(),
+        })
+    }
+}
+
+impl CreateSourceOperator<CsvSourceParams> for CsvPointSource {
+    fn create(params: CsvSourceParams) -> Self {
+        CsvPointSource { params }
+    }
+}
+
+/// Reads points and their attributes from a CSV file.
+///
+/// The MetaCsvPointSource describes the file and its coordinate columns. The attributes are read from the file.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct MetaCsvPointSource {
+    #[serde(flatten)]
+    pub params: CsvSourceParams,
+}
+
+impl MetaOperator for MetaCsvPointSource {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        &[] // no sources!
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &[]
+    }
+}
+
+#[typetag::serde]
+impl MetaVectorOperator for MetaCsvPointSource {
+    fn creates_collection_type(&self) -> Result<VectorCollectionType> {
+        Ok(VectorCollectionType::MultiPoint)
+    }
+
+    fn attribute_schema(&self) -> Result<AttributeSchema> {
+        self.params.schema().map_err(|source| Error::Dataset {
+            dataset: self.params.dataset.clone(),
+            source: source.into(),
+        })
+    }
+
+    /// the extent is computed from the coordinates of all rows
+    fn result_descriptor(&self) -> Result<VectorResultDescriptor> {
+        let bbox = self
+            .params
+            .bounding_box()
+            .map_err(|source| Error::Dataset {
+                dataset: self.params.dataset.clone(),
+                source: source.into(),
+            })?;
+        Ok(VectorResultDescriptor {
+            bbox,
+            spatial_reference: Some(self.params.spatial_reference),
+            ..VectorResultDescriptor::new(self.creates_collection_type()?, self.attribute_schema()?)
+        })
+    }
+
+    fn create_multi_point_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiPointCollection>>> {
+        Ok(Box::new(CsvPointSource::create(self.params.clone())))
+    }
+}
+
+crate::register_operator! {
+    MetaCsvPointSource: Vector
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        feature_collection::VectorData,
+        test_util::{query_area, temp_path},
+        validate_vector_graph, BoundingBox2D, TimeInterval,
+    };
+
+    fn query(time_interval: TimeInterval) -> Query {
+        Query {
+            time_interval,
+            ..query_area(10., 10.)
+        }
+    }
+
+    #[test]
+    fn reads_points_with_typed_attributes() {
+        let path = temp_path("csv_source_points.csv");
+        std::fs::write(
+            &path,
+            "name;lon;lat;count;value;time\n\
+             a;1;2;3;1.5;10\n\
+             b;50;50;4;2;10\n\
+             ;3;4;;7;20\n\
+             c;5;6;5;8;30\n",
+        )
+        .unwrap();
+
+        let source: Box<dyn MetaVectorOperator> = serde_json::from_value(serde_json::json!({
+            "type": "MetaCsvPointSource",
+            "dataset": path,
+            "x_column": "lon",
+            "y_column": "lat",
+            "time_column": "time",
+            "delimiter": ";",
+        }))
+        .unwrap();
+        validate_vector_graph(source.as_ref()).unwrap();
+        assert_eq!(
+            source.attribute_schema().unwrap(),
+            AttributeSchema::new()
+                .with_column("name", AttributeType::Text)
+                .unwrap()
+                .with_column("count", AttributeType::Int)
+                .unwrap()
+                .with_column("value", AttributeType::Float)
+                .unwrap()
+        );
+        let descriptor = source.result_descriptor().unwrap();
+        assert_eq!(
+            descriptor.bbox,
+            Some(
+                BoundingBox2D::new(Coordinate2D::new(1., 2.), Coordinate2D::new(50., 50.)).unwrap()
+            )
+        );
+        assert_eq!(
+            descriptor.spatial_reference,
+            Some(SpatialReference::wgs84())
+        );
+
+        let collection = source
+            .create_vector_op()
+            .unwrap()
+            .get::<MultiPointCollection>()
+            .unwrap()
+            .vector_query(query(TimeInterval::new(0, 25).unwrap()))
+            .unwrap();
+        assert_eq!(collection.len(), 2);
+        assert_eq!(
+            collection.geometries.feature(1),
+            &[Coordinate2D::new(3., 4.)]
+        );
+        assert_eq!(
+            collection.attribute("name"),
+            Some(&AttributeColumn::Text(vec![Some("a".to_string()), None]))
+        );
+        assert_eq!(
+            collection.attribute("count"),
+            Some(&AttributeColumn::Int(vec![Some(3), None]))
+        );
+        assert_eq!(
+            collection.attribute("value"),
+            Some(&AttributeColumn::Float(vec![Some(1.5), Some(7.)]))
+        );
+    }
+
+    #[test]
+    fn reads_files_without_header() {
+        let path = temp_path("csv_source_without_header.csv");
+        std::fs::write(&path, "1,2,true\n3,x,false\n").unwrap();
+
+        let params = CsvSourceParams {
+            dataset: path.to_string_lossy().into_owned(),
+            x_column: "0".to_string(),
+            y_column: "1".to_string(),
+            time_column: None,
+            delimiter: ',',
+            header: false,
+            spatial_reference: SpatialReference::wgs84(),
+        };
+        assert_eq!(
+            params.schema().unwrap(),
+            AttributeSchema::new()
+                .with_column("2", AttributeType::Bool)
+                .unwrap()
+        );
+        assert_eq!(
+            CsvPointSource::create(params)
+                .query(query(TimeInterval::new(0, 1).unwrap()))
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "cannot read dataset {}: invalid value \"x\" in row 2 of column 1",
+                path.display()
+            )
+        );
+    }
+}
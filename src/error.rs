@@ -0,0 +1,366 @@
+use crate::{
+    attributes::AttributeType,
+    csv_source::CsvError,
+    feature_collection::VectorCollectionType,
+    geojson::GeoJsonError,
+    geotiff::GeoTiffError,
+    primitives::{
+        Coordinate2D, GeoTransform, GridShape, SpatialReference, SpatialResolution, TimeInterval,
+    },
+    raster_type::RasterType,
+    workflow::WorkflowNodeKind,
+};
+use std::fmt;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The errors that can occur while creating or querying operators.
+#[derive(Debug)]
+pub enum Error {
+    /// an operator got a raster of another type than it expected
+    RasterTypeMismatch {
+        expected: RasterType,
+        found: RasterType,
+    },
+    /// an operator has fewer raster sources than it needs
+    MissingRasterSource { index: usize },
+    /// an operator got a feature collection of another type than it expected
+    CollectionTypeMismatch {
+        expected: VectorCollectionType,
+        found: VectorCollectionType,
+    },
+    /// an operator cannot produce collections of this type
+    UnsupportedCollectionType(VectorCollectionType),
+    /// an operator has more raster sources than it accepts
+    UnexpectedRasterSource { index: usize },
+    /// a raster source produces another type than the operator requires
+    InputTypeMismatch {
+        index: usize,
+        expected: RasterType,
+        found: RasterType,
+    },
+    /// there is no larger type of the same kind
+    CannotUpgrade(RasterType),
+    /// there is no smaller type of the same kind
+    CannotDowngrade(RasterType),
+    /// an operator has fewer vector sources than it needs
+    MissingVectorSource { index: usize },
+    /// two rasters that must be combined pixel by pixel cover different grids
+    IncompatibleGrids {
+        first: Box<(GridShape, GeoTransform)>,
+        second: Box<(GridShape, GeoTransform)>,
+    },
+    /// two rasters that must be combined band by band have different numbers of bands
+    BandCountMismatch { first: usize, second: usize },
+    /// two rasters that must be combined pixel by pixel have different pixel sizes
+    ResolutionMismatch {
+        first: SpatialResolution,
+        second: SpatialResolution,
+    },
+    /// two rasters or collections that must be combined use different coordinate systems
+    SpatialReferenceMismatch {
+        first: Option<SpatialReference>,
+        second: Option<SpatialReference>,
+    },
+    /// the rasters that must be combined pixel by pixel do not overlap
+    DisjointExtents,
+    /// the lower left corner of a bounding box is not below and left of its upper right corner
+    InvalidBoundingBox {
+        lower_left: Coordinate2D,
+        upper_right: Coordinate2D,
+    },
+    /// a time interval starts after its end
+    InvalidTimeInterval { start: i64, end: i64 },
+    /// a pixel size is not strictly positive and finite
+    InvalidResolution { x: f64, y: f64 },
+    /// a raster would have more than `MAX_RASTER_PIXELS` pixels
+    RasterTooLarge {
+        width: usize,
+        height: usize,
+        bands: usize,
+    },
+    /// a query asks for a time at which the data is not valid
+    TimeOutOfRange {
+        requested: TimeInterval,
+        valid: TimeInterval,
+    },
+    /// a raster has no band with this index
+    BandOutOfRange { band: usize, bands: usize },
+    /// the valid pixels of a raster take every value of its type, so none is left to mark missing pixels
+    NoFreeNoDataValue(RasterType),
+    /// a dataset cannot be read
+    Dataset {
+        dataset: String,
+        source: DatasetError,
+    },
+    /// a value or column has another type than the attribute column expects
+    AttributeTypeMismatch {
+        expected: AttributeType,
+        value: String,
+    },
+    /// a collection has no attribute column with this name
+    UnknownAttribute(String),
+    /// a collection already has an attribute column with this name
+    DuplicateAttribute(String),
+    /// an attribute column has not one value per feature
+    AttributeLengthMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// a raster expression cannot be parsed or does not fit the sources
+    InvalidExpression { expression: String, reason: String },
+    /// an operator parameter has a value the operator cannot work with
+    InvalidParameter { name: String, reason: String },
+    /// a workflow has no node with this id
+    UnknownNode(String),
+    /// a workflow node is referenced as another kind of node than it is
+    NodeKindMismatch {
+        node: String,
+        expected: WorkflowNodeKind,
+    },
+    /// a workflow node is referenced that failed its validation before
+    InvalidNode(String),
+    /// the nodes of a workflow reference each other in a cycle
+    CyclicWorkflow(Vec<String>),
+    /// all violations found while validating an operator graph
+    InvalidGraph(Vec<Error>),
+    /// an error that occurred inside an operator graph. The path leads from the root to the failing operator.
+    Operator {
+        path: Vec<String>,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Adds an operator in front of the path of the error.
+    pub fn in_operator(self, operator: &str) -> Self {
+        match self {
+            Error::Operator { mut path, source } => {
+                path.insert(0, operator.to_string());
+                Error::Operator { path, source }
+            }
+            error => Error::Operator {
+                path: vec![operator.to_string()],
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RasterTypeMismatch { expected, found } => {
+                write!(f, "expected a {:?} raster but found {:?}", expected, found)
+            }
+            Error::MissingRasterSource { index } => {
+                write!(f, "the raster source {} is missing", index)
+            }
+            Error::CollectionTypeMismatch { expected, found } => write!(
+                f,
+                "expected a {:?} collection but found {:?}",
+                expected, found
+            ),
+            Error::UnsupportedCollectionType(collection_type) => {
+                write!(f, "{:?} collections are not supported", collection_type)
+            }
+            Error::UnexpectedRasterSource { index } => {
+                write!(f, "the raster source {} is not expected", index)
+            }
+            Error::InputTypeMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "the raster source {} must be {:?} but is {:?}",
+                index, expected, found
+            ),
+            Error::CannotUpgrade(raster_type) => {
+                write!(f, "there is no larger type than {:?}", raster_type)
+            }
+            Error::CannotDowngrade(raster_type) => {
+                write!(f, "there is no smaller type than {:?}", raster_type)
+            }
+            Error::MissingVectorSource { index } => {
+                write!(f, "the vector source {} is missing", index)
+            }
+            Error::IncompatibleGrids { first, second } => write!(
+                f,
+                "incompatible grids {:?} {:?} and {:?} {:?}",
+                first.0, first.1, second.0, second.1
+            ),
+            Error::BandCountMismatch { first, second } => write!(
+                f,
+                "cannot combine a raster with {} bands and a raster with {} bands",
+                first, second
+            ),
+            Error::ResolutionMismatch { first, second } => write!(
+                f,
+                "cannot combine a raster with {}x{} pixels and a raster with {}x{} pixels",
+                first.x, first.y, second.x, second.y
+            ),
+            Error::SpatialReferenceMismatch { first, second } => {
+                let name = |reference: &Option<SpatialReference>| {
+                    reference.map_or("no spatial reference".to_string(), |r| r.to_string())
+                };
+                write!(
+                    f,
+                    "cannot combine data in {} and data in {}",
+                    name(first),
+                    name(second)
+                )
+            }
+            Error::DisjointExtents => write!(f, "the rasters do not overlap"),
+            Error::InvalidBoundingBox {
+                lower_left,
+                upper_right,
+            } => write!(
+                f,
+                "the lower left corner ({}, {}) must be below and left of the upper right corner ({}, {})",
+                lower_left.x, lower_left.y, upper_right.x, upper_right.y
+            ),
+            Error::InvalidTimeInterval { start, end } => write!(
+                f,
+                "the time interval start {} must not be after its end {}",
+                start, end
+            ),
+            Error::InvalidResolution { x, y } => write!(
+                f,
+                "the pixel size {}x{} must be positive and finite",
+                x, y
+            ),
+            Error::RasterTooLarge {
+                width,
+                height,
+                bands,
+            } => write!(
+                f,
+                "a raster with {}x{} pixels and {} bands exceeds the limit of {} pixels",
+                width,
+                height,
+                bands,
+                crate::primitives::MAX_RASTER_PIXELS
+            ),
+            Error::TimeOutOfRange { requested, valid } => write!(
+                f,
+                "the query asks for [{}, {}) but the data is valid in [{}, {})",
+                requested.start(),
+                requested.end(),
+                valid.start(),
+                valid.end()
+            ),
+            Error::BandOutOfRange { band, bands } => {
+                write!(
+                    f,
+                    "there is no band {} in a raster with {} bands",
+                    band, bands
+                )
+            }
+            Error::NoFreeNoDataValue(raster_type) => write!(
+                f,
+                "every {:?} value is a valid pixel, so none is left to mark missing pixels",
+                raster_type
+            ),
+            Error::Dataset { dataset, source } => {
+                write!(f, "cannot read dataset {}: {}", dataset, source)
+            }
+            Error::AttributeTypeMismatch { expected, value } => {
+                write!(f, "expected a {:?} attribute but found {}", expected, value)
+            }
+            Error::UnknownAttribute(name) => write!(f, "there is no attribute {}", name),
+            Error::DuplicateAttribute(name) => write!(f, "the attribute {} already exists", name),
+            Error::AttributeLengthMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "the attribute {} has {} values but there are {} features",
+                name, found, expected
+            ),
+            Error::InvalidExpression { expression, reason } => {
+                write!(f, "invalid expression \"{}\": {}", expression, reason)
+            }
+            Error::InvalidParameter { name, reason } => {
+                write!(f, "the parameter {} is invalid: {}", name, reason)
+            }
+            Error::UnknownNode(node) => write!(f, "there is no workflow node {}", node),
+            Error::NodeKindMismatch { node, expected } => {
+                write!(f, "the workflow node {} is not a {:?} node", node, expected)
+            }
+            Error::InvalidNode(node) => write!(f, "the workflow node {} is invalid", node),
+            Error::CyclicWorkflow(nodes) => {
+                write!(f, "the workflow nodes form a cycle: {}", nodes.join(" -> "))
+            }
+            Error::InvalidGraph(errors) => {
+                write!(f, "the operator graph is invalid: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+            Error::Operator { path, source } => write!(f, "{}: {}", path.join(" -> "), source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Dataset { source, .. } => Some(source),
+            Error::Operator { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// The errors of the file formats datasets are read from.
+#[derive(Debug)]
+pub enum DatasetError {
+    GeoTiff(GeoTiffError),
+    GeoJson(GeoJsonError),
+    Csv(CsvError),
+}
+
+impl fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasetError::GeoTiff(error) => write!(f, "{}", error),
+            DatasetError::GeoJson(error) => write!(f, "{}", error),
+            DatasetError::Csv(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for DatasetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DatasetError::GeoTiff(error) => Some(error),
+            DatasetError::GeoJson(error) => Some(error),
+            DatasetError::Csv(error) => Some(error),
+        }
+    }
+}
+
+impl From<GeoTiffError> for DatasetError {
+    fn from(error: GeoTiffError) -> Self {
+        DatasetError::GeoTiff(error)
+    }
+}
+
+impl From<GeoJsonError> for DatasetError {
+    fn from(error: GeoJsonError) -> Self {
+        DatasetError::GeoJson(error)
+    }
+}
+
+impl From<CsvError> for DatasetError {
+    fn from(error: CsvError) -> Self {
+        DatasetError::Csv(error)
+    }
+}
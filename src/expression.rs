@@ -0,0 +1,635 @@
+use crate::{
+    binary_raster_operator::BinaryOperation,
+    error::{Error, Result},
+    raster_type::{OverflowPolicy, RasterType},
+};
+use num_traits::NumCast;
+use std::{convert::TryFrom, fmt, iter::Peekable, str::CharIndices};
+
+/// The functions of one argument that expressions can call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryFunction {
+    Negate,
+    Abs,
+    Sqrt,
+}
+
+/// A parsed raster expression. The rasters are referenced by the letters `A` to `Z` for the sources 0 to 25.
+///
+/// The grammar in order of increasing precedence is
+/// * comparisons `==`, `!=`, `<`, `<=`, `>`, `>=`, which yield 1 or 0
+/// * `+` and `-`
+/// * `*` and `/`
+/// * unary `-`
+/// * `^`, which is right associative
+/// * numbers, rasters, parentheses and the functions `min(a, b)`, `max(a, b)`, `abs(a)` and `sqrt(a)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Constant(f64),
+    Raster(usize),
+    Unary(UnaryFunction, Box<Expression>),
+    Binary(BinaryOperation, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let mut parser = Parser {
+            expression,
+            tokens: Tokenizer::new(expression).peekable(),
+        };
+        let parsed = parser.comparison()?;
+        match parser.next()? {
+            None => Ok(parsed),
+            Some((position, token)) => {
+                Err(parser.error(&format!("unexpected {:?}", token), position))
+            }
+        }
+    }
+
+    /// the largest raster index the expression references
+    pub fn max_raster(&self) -> Option<usize> {
+        match self {
+            Expression::Constant(_) => None,
+            Expression::Raster(index) => Some(*index),
+            Expression::Unary(_, operand) => operand.max_raster(),
+            Expression::Binary(_, left, right) => left.max_raster().max(right.max_raster()),
+        }
+    }
+
+    /// true if the expression yields integers for integer inputs
+    pub fn is_integral(&self) -> bool {
+        match self {
+            Expression::Constant(value) => value.fract() == 0.,
+            Expression::Raster(_) => true,
+            Expression::Unary(UnaryFunction::Sqrt, _) => false,
+            Expression::Unary(_, operand) => operand.is_integral(),
+            Expression::Binary(BinaryOperation::Divide, _, _)
+            | Expression::Binary(BinaryOperation::Power, _, _) => false,
+            Expression::Binary(_, left, right) => left.is_integral() && right.is_integral(),
+        }
+    }
+
+    /// true if the expression can yield negative values. `source_types` are the types of the rasters.
+    pub fn may_be_negative(&self, source_types: &[RasterType]) -> bool {
+        match self {
+            Expression::Constant(value) => *value < 0.,
+            Expression::Raster(index) => source_types[*index].is_signed(),
+            Expression::Unary(UnaryFunction::Negate, _) => true,
+            Expression::Unary(_, _) => false,
+            Expression::Binary(BinaryOperation::Subtract, _, _) => true,
+            Expression::Binary(operation, _, _) if operation.is_comparison() => false,
+            Expression::Binary(BinaryOperation::Max, left, right) => {
+                left.may_be_negative(source_types) && right.may_be_negative(source_types)
+            }
+            Expression::Binary(BinaryOperation::Power, base, _) => {
+                base.may_be_negative(source_types)
+            }
+            Expression::Binary(_, left, right) => {
+                left.may_be_negative(source_types) || right.may_be_negative(source_types)
+            }
+        }
+    }
+
+    /// Replaces the sub-expressions without rasters by their values.
+    /// Sub-expressions with undefined results are kept, so they still yield no-data pixels.
+    pub fn folded(&self) -> Expression {
+        let folded = match self {
+            Expression::Constant(_) | Expression::Raster(_) => return self.clone(),
+            Expression::Unary(function, operand) => {
+                Expression::Unary(*function, Box::new(operand.folded()))
+            }
+            Expression::Binary(operation, left, right) => Expression::Binary(
+                *operation,
+                Box::new(left.folded()),
+                Box::new(right.folded()),
+            ),
+        };
+        let constant_operands = match &folded {
+            Expression::Unary(_, operand) => matches!(**operand, Expression::Constant(_)),
+            Expression::Binary(_, left, right) => {
+                matches!(**left, Expression::Constant(_))
+                    && matches!(**right, Expression::Constant(_))
+            }
+            _ => false,
+        };
+        if constant_operands {
+            if let Some(value) = folded
+                .evaluate::<f64>(&[])
+                .filter(|value| value.is_finite())
+            {
+                return Expression::Constant(value);
+            }
+        }
+        folded
+    }
+
+    /// Evaluates the expression for one pixel. `pixels` holds the value of each source.
+    /// Results that are undefined, e.g. divisions by zero, are `None`.
+    pub fn evaluate<V: ExpressionValue>(&self, pixels: &[V]) -> Option<V> {
+        match self {
+            Expression::Constant(value) => V::from(*value),
+            Expression::Raster(index) => Some(pixels[*index]),
+            Expression::Unary(function, operand) => operand.evaluate(pixels)?.unary(*function),
+            Expression::Binary(operation, left, right) => {
+                let left = left.evaluate(pixels)?;
+                left.binary(*operation, right.evaluate(pixels)?)
+            }
+        }
+    }
+}
+
+/// The numbers expressions compute with. `f64` computes any expression, `i128` computes
+/// integral expressions over integer rasters exactly, even for 64 bit values beyond 2^53.
+pub trait ExpressionValue: Copy + PartialOrd + NumCast {
+    /// the result or `None` if it is undefined or does not fit
+    fn unary(self, function: UnaryFunction) -> Option<Self>;
+    /// the result or `None` if it is undefined or does not fit
+    fn binary(self, operation: BinaryOperation, other: Self) -> Option<Self>;
+    /// the value as a pixel of type `T` or `None` if `T` cannot represent it
+    fn to_pixel<T: NumCast>(self) -> Option<T>;
+}
+
+/// Infinite intermediate results are kept, e.g. for comparisons, only `NaN` is undefined.
+impl ExpressionValue for f64 {
+    fn unary(self, function: UnaryFunction) -> Option<Self> {
+        let result = match function {
+            UnaryFunction::Negate => -self,
+            UnaryFunction::Abs => self.abs(),
+            UnaryFunction::Sqrt => self.sqrt(),
+        };
+        Some(result).filter(|result| !result.is_nan())
+    }
+
+    fn binary(self, operation: BinaryOperation, other: Self) -> Option<Self> {
+        operation.apply(self, other, OverflowPolicy::NoData)
+    }
+
+    fn to_pixel<T: NumCast>(self) -> Option<T> {
+        Some(self)
+            .filter(|value| value.is_finite())
+            .and_then(T::from)
+    }
+}
+
+/// Only exact results are defined, e.g. `7 / 2` and `sqrt(2)` are not.
+impl ExpressionValue for i128 {
+    fn unary(self, function: UnaryFunction) -> Option<Self> {
+        match function {
+            UnaryFunction::Negate => self.checked_neg(),
+            UnaryFunction::Abs => self.checked_abs(),
+            UnaryFunction::Sqrt if self < 0 => None,
+            UnaryFunction::Sqrt => {
+                let root = (self as f64).sqrt().round() as i128;
+                (root - 1..=root + 1).find(|root| root.checked_mul(*root) == Some(self))
+            }
+        }
+    }
+
+    fn binary(self, operation: BinaryOperation, other: Self) -> Option<Self> {
+        match operation {
+            BinaryOperation::Add => self.checked_add(other),
+            BinaryOperation::Subtract => self.checked_sub(other),
+            BinaryOperation::Multiply => self.checked_mul(other),
+            BinaryOperation::Divide => self
+                .checked_rem(other)
+                .filter(|&remainder| remainder == 0)
+                .and_then(|_| self.checked_div(other)),
+            BinaryOperation::Power => u32::try_from(other)
+                .ok()
+                .and_then(|exponent| self.checked_pow(exponent)),
+            BinaryOperation::Min => Some(self.min(other)),
+            BinaryOperation::Max => Some(self.max(other)),
+            comparison => Some(comparison.compare(self, other).into()),
+        }
+    }
+
+    fn to_pixel<T: NumCast>(self) -> Option<T> {
+        T::from(self)
+    }
+}
+
+/// Writes the expression with all operations in parentheses, so it parses to the same expression.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Constant(value) if *value < 0. => write!(f, "(-{})", -value),
+            Expression::Constant(value) => write!(f, "{}", value),
+            Expression::Raster(index) => write!(f, "{}", (b'A' + *index as u8) as char),
+            Expression::Unary(UnaryFunction::Negate, operand) => write!(f, "(-{})", operand),
+            Expression::Unary(UnaryFunction::Abs, operand) => write!(f, "abs({})", operand),
+            Expression::Unary(UnaryFunction::Sqrt, operand) => write!(f, "sqrt({})", operand),
+            Expression::Binary(BinaryOperation::Min, left, right) => {
+                write!(f, "min({}, {})", left, right)
+            }
+            Expression::Binary(BinaryOperation::Max, left, right) => {
+                write!(f, "max({}, {})", left, right)
+            }
+            Expression::Binary(operation, left, right) => {
+                let operator = match operation {
+                    BinaryOperation::Add => "+",
+                    BinaryOperation::Subtract => "-",
+                    BinaryOperation::Multiply => "*",
+                    BinaryOperation::Divide => "/",
+                    BinaryOperation::Power => "^",
+                    BinaryOperation::Equal => "==",
+                    BinaryOperation::NotEqual => "!=",
+                    BinaryOperation::Less => "<",
+                    BinaryOperation::LessOrEqual => "<=",
+                    BinaryOperation::Greater => ">",
+                    BinaryOperation::GreaterOrEqual => ">=",
+                    BinaryOperation::Min | BinaryOperation::Max => unreachable!("written as calls"),
+                };
+                write!(f, "({} {} {})", left, operator, right)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Operator(&'static str),
+    LeftParenthesis,
+    RightParenthesis,
+    Comma,
+}
+
+struct Tokenizer<'e> {
+    expression: &'e str,
+    chars: Peekable<CharIndices<'e>>,
+}
+
+impl<'e> Tokenizer<'e> {
+    fn new(expression: &'e str) -> Self {
+        Self {
+            expression,
+            chars: expression.char_indices().peekable(),
+        }
+    }
+
+    /// consumes characters while they match and returns their end
+    fn take_while(&mut self, start: usize, matches: impl Fn(char) -> bool) -> usize {
+        let mut end = start;
+        while let Some(&(position, c)) = self.chars.peek() {
+            if !matches(c) {
+                break;
+            }
+            end = position + c.len_utf8();
+            self.chars.next();
+        }
+        end
+    }
+}
+
+impl<'e> Iterator for Tokenizer<'e> {
+    /// the position and the token or the position of an invalid character
+    type Item = Result<(usize, Token), usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.take_while(0, char::is_whitespace);
+        let (start, c) = self.chars.next()?;
+        let token = match c {
+            '0'..='9' | '.' => {
+                let end = self.take_while(start + 1, |c| c.is_ascii_digit() || c == '.');
+                match self.expression[start..end].parse() {
+                    Ok(number) => Token::Number(number),
+                    Err(_) => return Some(Err(start)),
+                }
+            }
+            c if c.is_ascii_alphabetic() => {
+                let end = self.take_while(start + 1, |c| c.is_ascii_alphanumeric() || c == '_');
+                Token::Identifier(self.expression[start..end].to_string())
+            }
+            '(' => Token::LeftParenthesis,
+            ')' => Token::RightParenthesis,
+            ',' => Token::Comma,
+            '+' => Token::Operator("+"),
+            '-' => Token::Operator("-"),
+            '*' => Token::Operator("*"),
+            '/' => Token::Operator("/"),
+            '^' => Token::Operator("^"),
+            '<' | '>' | '=' | '!' => {
+                let with_equals = self.chars.peek().map(|&(_, c)| c) == Some('=');
+                if with_equals {
+                    self.chars.next();
+                }
+                match (c, with_equals) {
+                    ('<', false) => Token::Operator("<"),
+                    ('<', true) => Token::Operator("<="),
+                    ('>', false) => Token::Operator(">"),
+                    ('>', true) => Token::Operator(">="),
+                    ('=', true) => Token::Operator("=="),
+                    ('!', true) => Token::Operator("!="),
+                    _ => return Some(Err(start)),
+                }
+            }
+            _ => return Some(Err(start)),
+        };
+        Some(Ok((start, token)))
+    }
+}
+
+struct Parser<'e> {
+    expression: &'e str,
+    tokens: Peekable<Tokenizer<'e>>,
+}
+
+impl<'e> Parser<'e> {
+    fn error(&self, reason: &str, position: usize) -> Error {
+        Error::InvalidExpression {
+            expression: self.expression.to_string(),
+            reason: format!("{} at position {}", reason, position),
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<(usize, Token)>> {
+        match self.tokens.next() {
+            None => Ok(None),
+            Some(Ok(token)) => Ok(Some(token)),
+            Some(Err(position)) => Err(self.error("invalid character", position)),
+        }
+    }
+
+    /// consumes the next token if it is one of the operators
+    fn operator(&mut self, operators: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.peek() {
+            Some(Ok((_, Token::Operator(operator)))) if operators.contains(operator) => {
+                let operator = *operator;
+                self.tokens.next();
+                Some(operator)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next()? {
+            Some((_, token)) if token == expected => Ok(()),
+            Some((position, token)) => Err(self.error(
+                &format!("expected {:?} but found {:?}", expected, token),
+                position,
+            )),
+            None => Err(self.error(&format!("expected {:?}", expected), self.expression.len())),
+        }
+    }
+
+    fn binary(operation: BinaryOperation, left: Expression, right: Expression) -> Expression {
+        Expression::Binary(operation, Box::new(left), Box::new(right))
+    }
+
+    fn comparison(&mut self) -> Result<Expression> {
+        let mut expression = self.sum()?;
+        while let Some(operator) = self.operator(&["==", "!=", "<", "<=", ">", ">="]) {
+            let operation = match operator {
+                "==" => BinaryOperation::Equal,
+                "!=" => BinaryOperation::NotEqual,
+                "<" => BinaryOperation::Less,
+                "<=" => BinaryOperation::LessOrEqual,
+                ">" => BinaryOperation::Greater,
+                _ => BinaryOperation::GreaterOrEqual,
+            };
+            expression = Self::binary(operation, expression, self.sum()?);
+        }
+        Ok(expression)
+    }
+
+    fn sum(&mut self) -> Result<Expression> {
+        let mut expression = self.product()?;
+        while let Some(operator) = self.operator(&["+", "-"]) {
+            let operation = match operator {
+                "+" => BinaryOperation::Add,
+                _ => BinaryOperation::Subtract,
+            };
+            expression = Self::binary(operation, expression, self.product()?);
+        }
+        Ok(expression)
+    }
+
+    fn product(&mut self) -> Result<Expression> {
+        let mut expression = self.negation()?;
+        while let Some(operator) = self.operator(&["*", "/"]) {
+            let operation = match operator {
+                "*" => BinaryOperation::Multiply,
+                _ => BinaryOperation::Divide,
+            };
+            expression = Self::binary(operation, expression, self.negation()?);
+        }
+        Ok(expression)
+    }
+
+    fn negation(&mut self) -> Result<Expression> {
+        if self.operator(&["-"]).is_some() {
+            // negative numbers are constants, so written expressions parse to the same expression
+            return Ok(match self.negation()? {
+                Expression::Constant(value) => Expression::Constant(-value),
+                operand => Expression::Unary(UnaryFunction::Negate, Box::new(operand)),
+            });
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expression> {
+        let base = self.primary()?;
+        if self.operator(&["^"]).is_some() {
+            // the exponent may be negated, e.g. `A ^ -1`
+            let exponent = self.negation()?;
+            return Ok(Self::binary(BinaryOperation::Power, base, exponent));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expression> {
+        match self.next()? {
+            Some((_, Token::Number(number))) => Ok(Expression::Constant(number)),
+            Some((_, Token::LeftParenthesis)) => {
+                let expression = self.comparison()?;
+                self.expect(Token::RightParenthesis)?;
+                Ok(expression)
+            }
+            Some((position, Token::Identifier(name))) => self.identifier(&name, position),
+            Some((position, token)) => {
+                Err(self.error(&format!("unexpected {:?}", token), position))
+            }
+            None => Err(self.error("unexpected end", self.expression.len())),
+        }
+    }
+
+    fn identifier(&mut self, name: &str, position: usize) -> Result<Expression> {
+        let mut chars = name.chars();
+        if let (Some(c @ 'A'..='Z'), None) = (chars.next(), chars.next()) {
+            return Ok(Expression::Raster((c as u8 - b'A') as usize));
+        }
+
+        let arguments = match name {
+            "min" | "max" => 2,
+            "abs" | "sqrt" => 1,
+            _ => return Err(self.error(&format!("unknown name {}", name), position)),
+        };
+        self.expect(Token::LeftParenthesis)?;
+        let first = self.comparison()?;
+        let second = if arguments == 2 {
+            self.expect(Token::Comma)?;
+            Some(self.comparison()?)
+        } else {
+            None
+        };
+        self.expect(Token::RightParenthesis)?;
+
+        Ok(match (name, second) {
+            ("min", Some(second)) => Self::binary(BinaryOperation::Min, first, second),
+            ("max", Some(second)) => Self::binary(BinaryOperation::Max, first, second),
+            ("abs", _) => Expression::Unary(UnaryFunction::Abs, Box::new(first)),
+            _ => Expression::Unary(UnaryFunction::Sqrt, Box::new(first)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary(operation: BinaryOperation, left: Expression, right: Expression) -> Expression {
+        Expression::Binary(operation, Box::new(left), Box::new(right))
+    }
+
+    #[test]
+    fn parses_with_precedence() {
+        use Expression::{Constant, Raster};
+
+        assert_eq!(
+            Expression::parse("(A - B) / (A + B)").unwrap(),
+            binary(
+                BinaryOperation::Divide,
+                binary(BinaryOperation::Subtract, Raster(0), Raster(1)),
+                binary(BinaryOperation::Add, Raster(0), Raster(1)),
+            )
+        );
+        assert_eq!(
+            Expression::parse("-A ^ 2 * 3 < C").unwrap(),
+            binary(
+                BinaryOperation::Less,
+                binary(
+                    BinaryOperation::Multiply,
+                    Expression::Unary(
+                        UnaryFunction::Negate,
+                        Box::new(binary(BinaryOperation::Power, Raster(0), Constant(2.)))
+                    ),
+                    Constant(3.)
+                ),
+                Raster(2)
+            )
+        );
+        assert_eq!(
+            Expression::parse("max(A, 0.5)").unwrap(),
+            binary(BinaryOperation::Max, Raster(0), Constant(0.5))
+        );
+        assert_eq!(Expression::parse("sqrt(Z)").unwrap().max_raster(), Some(25));
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        for (expression, reason) in [
+            ("A +", "unexpected end at position 3"),
+            ("A + (B", "expected RightParenthesis at position 6"),
+            ("A B", "unexpected Identifier(\"B\") at position 2"),
+            ("foo(A)", "unknown name foo at position 0"),
+            ("A $ B", "invalid character at position 2"),
+            (
+                "min(A)",
+                "expected Comma but found RightParenthesis at position 5",
+            ),
+        ] {
+            match Expression::parse(expression) {
+                Err(Error::InvalidExpression { reason: found, .. }) => assert_eq!(found, reason),
+                result => panic!("{}: expected an error, got {:?}", expression, result),
+            }
+        }
+    }
+
+    #[test]
+    fn evaluates_all_pixels() {
+        let expression = Expression::parse("(A - B) / (A + B)").unwrap();
+        assert!(!expression.is_integral());
+        assert!(Expression::parse("abs(A - 2) * 3").unwrap().is_integral());
+
+        assert_eq!(expression.evaluate(&[3., 1.]), Some(0.5));
+        assert_eq!(expression.evaluate(&[0., 0.]), None);
+        assert_eq!(expression.evaluate(&[1., 1.]), Some(0.));
+    }
+
+    #[test]
+    fn evaluates_integers_exactly() {
+        let expression = Expression::parse("A * 2 - B / 2").unwrap();
+        let large = (1i128 << 62) + 1;
+        assert_eq!(expression.evaluate(&[large, 4]), Some(2 * large - 2));
+        assert_eq!(expression.evaluate(&[large, 3]), None);
+        assert_eq!(
+            Expression::parse("sqrt(A) == 3")
+                .unwrap()
+                .evaluate(&[9i128]),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn knows_which_expressions_may_be_negative() {
+        let unsigned = [RasterType::U8, RasterType::U64];
+        for (expression, negative) in [
+            ("A + B * 2", false),
+            ("A - B", true),
+            ("-A", true),
+            ("abs(A - B)", false),
+            ("A < B - 1", false),
+            ("max(A, -1)", false),
+            ("min(A, -1)", true),
+            ("2 ^ -A", false),
+        ] {
+            assert_eq!(
+                Expression::parse(expression)
+                    .unwrap()
+                    .may_be_negative(&unsigned),
+                negative,
+                "{}",
+                expression
+            );
+        }
+        assert!(Expression::parse("A")
+            .unwrap()
+            .may_be_negative(&[RasterType::I16]));
+    }
+
+    #[test]
+    fn folds_constants() {
+        let expression = Expression::parse("A * (2 + 3) - max(1, -4) / 0 + sqrt(-1)").unwrap();
+        let folded = expression.folded();
+        assert_eq!(folded.to_string(), "(((A * 5) - (1 / 0)) + sqrt((-1)))");
+        // negative constants are written in parentheses and parse to constants again
+        assert_eq!(Expression::parse(&folded.to_string()).unwrap(), folded);
+        assert_eq!(
+            Expression::parse("-2 ^ 2 - -B").unwrap(),
+            binary(
+                BinaryOperation::Subtract,
+                Expression::Unary(
+                    UnaryFunction::Negate,
+                    Box::new(binary(
+                        BinaryOperation::Power,
+                        Expression::Constant(2.),
+                        Expression::Constant(2.)
+                    ))
+                ),
+                Expression::Unary(UnaryFunction::Negate, Box::new(Expression::Raster(1)))
+            )
+        );
+
+        let expression = Expression::parse("-A ^ 2 < min(B, 0.5) == 1 - 3").unwrap();
+        assert_eq!(
+            Expression::parse(&expression.to_string()).unwrap(),
+            expression
+        );
+        assert_eq!(
+            expression.folded().to_string(),
+            "(((-(A ^ 2)) < min(B, 0.5)) == (-2))"
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{
+    error::{Error, Result},
+    expression::Expression,
+    expression::ExpressionValue,
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
+    primitives::Raster,
+    raster_type::{RasterType, StaticRasterType},
+    result_descriptor::RasterResultDescriptor,
+    source::{OperatorParams, Query, RasterSource, Source},
+    BoxedRasterOperatorInstance, BoxedRasterType, MetaOperator,
+};
+use num_traits::NumCast;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+/// The ExpressionOperator evaluates an expression over its sources pixel by pixel.
+/// It computes with `V`, see `ExpressionValue`. A no-data pixel in any source, an undefined
+/// result or a result that does not fit into `T` yields a no-data pixel.
+pub struct ExpressionOperator<T, V> {
+    pub sources: Vec<BoxedRasterOperatorInstance>,
+    pub expression: Expression,
+    pub data: PhantomData<(T, V)>,
+}
+
+/// the pixels of a raster as `V`, `None` for no-data pixels and values `V` cannot represent
+fn expression_values<P, V>(raster: Raster<P>) -> Raster<Option<V>>
+where
+    P: NumCast + PartialEq + Copy,
+    V: NumCast,
+{
+    let data = raster
+        .data
+        .iter()
+        .map(|&value| Some(value).filter(|&value| !raster.is_no_data(value)))
+        .map(|value| value.and_then(V::from))
+        .collect();
+    Raster {
+        shape: raster.shape,
+        bands: raster.bands,
+        geo_transform: raster.geo_transform,
+        data,
+        no_data_value: None,
+    }
+}
+
+impl<T, V> Source for ExpressionOperator<T, V>
+where
+    T: StaticRasterType + PartialOrd + NumCast,
+    V: ExpressionValue,
+{
+    type Output = Raster<T>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        let rasters = self
+            .sources
+            .iter()
+            .map(|source| {
+                crate::dispatch_raster_types!(match_instance: source, source => {
+                    Ok(expression_values::<_, V>(source.raster_query(query)?))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let first = rasters
+            .first()
+            .ok_or(Error::MissingRasterSource { index: 0 })?;
+        for other in &rasters[1..] {
+            first.check_compatible(other)?;
+        }
+
+        let mut pixels = Vec::with_capacity(rasters.len());
+        let results = (0..first.data.len())
+            .map(|index| {
+                pixels.clear();
+                for raster in &rasters {
+                    pixels.push(raster.data[index]?);
+                }
+                self.expression.evaluate(&pixels)?.to_pixel()
+            })
+            .collect();
+        Raster::from_results(
+            first.shape,
+            first.bands,
+            first.geo_transform,
+            results,
+            Some(T::NO_DATA),
+        )
+    }
+}
+
+/// The parameters of the `MetaExpressionOperator`.
+/// Without an `output_type` the sources are promoted to a common type. Expressions that
+/// can produce fractions, e.g. divisions, promote it further to a float type and expressions
+/// that can produce negative values, e.g. subtractions, to a signed type.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct ExpressionParams {
+    pub expression: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_type: Option<RasterType>,
+}
+
+impl ExpressionParams {
+    pub fn new(expression: &str) -> Self {
+        ExpressionParams {
+            expression: expression.to_string(),
+            output_type: None,
+        }
+    }
+}
+
+impl OperatorParams for ExpressionParams {
+    /// fails if the expression cannot be parsed
+    fn validate(&self) -> Result<()> {
+        Expression::parse(&self.expression).map(|_| ())
+    }
+}
+
+/// Evaluates an expression over its sources `A` to `Z` pixel by pixel.
+///
+/// The MetaExpressionOperator references its sources in the expression as `A`, `B`, … `Z`.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MetaExpressionOperator {
+    #[serde(flatten)]
+    pub params: ExpressionParams,
+    #[schemars(skip)]
+    pub sources: Vec<Box<dyn MetaRasterOperator>>,
+}
+
+impl MetaExpressionOperator {
+    /// one entry for each letter that can reference a source
+    const ANY_TYPES: [RasterWants; 26] = [RasterWants::Any; 26];
+
+    /// parses the expression and checks that it references existing sources
+    pub fn parsed_expression(&self) -> Result<Expression> {
+        let expression = Expression::parse(&self.params.expression)?;
+        let invalid = |reason: String| Error::InvalidExpression {
+            expression: self.params.expression.clone(),
+            reason,
+        };
+        if self.sources.is_empty() || self.sources.len() > Self::ANY_TYPES.len() {
+            return Err(invalid(format!(
+                "expected 1 to {} sources but found {}",
+                Self::ANY_TYPES.len(),
+                self.sources.len()
+            )));
+        }
+        match expression.max_raster() {
+            Some(index) if index >= self.sources.len() => Err(invalid(format!(
+                "{} references a missing source",
+                (b'A' + index as u8) as char
+            ))),
+            _ => Ok(expression),
+        }
+    }
+
+    fn create_typed_raster_op<T>(&self) -> Result<Box<dyn RasterSource<RasterType = T>>>
+    where
+        T: BoxedRasterType + PartialOrd + NumCast,
+    {
+        let expression = self.parsed_expression()?;
+        let source_types = self.source_types()?;
+        let sources = (0..self.sources.len())
+            .map(|index| self.create_raster_source(index))
+            .collect::<Result<_>>()?;
+        let exact = expression.is_integral() && !source_types.iter().any(|t| t.is_float());
+        Ok(if exact {
+            Box::new(ExpressionOperator::<T, i128> {
+                sources,
+                expression,
+                data: PhantomData,
+            })
+        } else {
+            Box::new(ExpressionOperator::<T, f64> {
+                sources,
+                expression,
+                data: PhantomData,
+            })
+        })
+    }
+
+    fn source_types(&self) -> Result<Vec<RasterType>> {
+        (0..self.sources.len())
+            .map(|index| self.raster_source_type(index))
+            .collect()
+    }
+}
+
+impl MetaOperator for MetaExpressionOperator {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        self.sources.as_slice()
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::ANY_TYPES[..self.sources.len().min(Self::ANY_TYPES.len())]
+    }
+}
+
+#[typetag::serde]
+impl MetaRasterOperator for MetaExpressionOperator {
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        let expression = self.parsed_expression()?;
+        if let Some(output_type) = self.params.output_type {
+            return Ok(RasterCreates::SecificType(output_type));
+        }
+
+        let source_types = self.source_types()?;
+        let mut output_type = source_types[0];
+        for source_type in &source_types[1..] {
+            output_type = output_type.promoted(*source_type);
+        }
+        if !expression.is_integral() {
+            output_type = output_type.promoted(RasterType::F32);
+        }
+        if !output_type.is_signed() && expression.may_be_negative(&source_types) {
+            output_type = output_type.promoted(RasterType::I16);
+        }
+        Ok(RasterCreates::SecificType(output_type))
+    }
+
+    /// missing pixels are marked with the no-data value of the created type unless a valid result takes it
+    fn result_descriptor(&self) -> Result<RasterResultDescriptor> {
+        let data_type = self.creates_type()?;
+        Ok(RasterResultDescriptor {
+            data_type,
+            bands: self.band_count()?,
+            no_data_value: Some(data_type.no_data_value()),
+            ..self.common_raster_source_descriptor()?
+        })
+    }
+
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaExpressionOperator: Raster
+        .with_optional_raster_sources(&MetaExpressionOperator::ANY_TYPES, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{gdal_source, query},
+        validate_raster_graph,
+    };
+
+    #[test]
+    fn computes_normalized_differences() {
+        let operator: Box<dyn MetaRasterOperator> = serde_json::from_value(serde_json::json!({
+            "type": "MetaExpressionOperator",
+            "expression": "(A - B) / (A + B)",
+            "sources": [],
+        }))
+        .unwrap();
+        assert!(matches!(
+            validate_raster_graph(operator.as_ref()),
+            Err(Error::InvalidGraph(_))
+        ));
+
+        let operator = MetaExpressionOperator {
+            params: ExpressionParams::new("(A - B) / (A + B)"),
+            sources: vec![
+                gdal_source("expression_nir.tif", vec![30u8, 0, 10, 5]),
+                gdal_source("expression_red.tif", vec![10u8, 0, 30, 5]),
+            ],
+        };
+        assert_eq!(validate_raster_graph(&operator).unwrap(), RasterType::F32);
+
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<f32>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data[0], 0.5);
+        assert!(raster.is_no_data(raster.data[1]));
+        assert_eq!(raster.data[2], -0.5);
+        assert_eq!(raster.data[3], 0.);
+    }
+
+    #[test]
+    fn integral_expressions_keep_the_promoted_type() {
+        let operator = MetaExpressionOperator {
+            params: ExpressionParams::new("max(A, B) * 2 - C"),
+            sources: vec![
+                gdal_source("expression_a.tif", vec![1u8, 200, 3, 4]),
+                gdal_source("expression_b.tif", vec![5i16, 6, 7, 8]),
+                gdal_source("expression_c.tif", vec![1u8, 2, 3, 4]),
+            ],
+        };
+        assert_eq!(operator.creates_type().unwrap(), RasterType::I16);
+
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<i16>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![9, 398, 11, 12]);
+        assert_eq!(raster.no_data_value, Some(i16::NO_DATA));
+    }
+
+    #[test]
+    fn differences_of_unsigned_sources_are_signed() {
+        let operator = MetaExpressionOperator {
+            params: ExpressionParams::new("A - B"),
+            sources: vec![
+                gdal_source("expression_difference_a.tif", vec![1u8, 200, 0, 4]),
+                gdal_source("expression_difference_b.tif", vec![3u8, 0, 255, 4]),
+            ],
+        };
+        assert_eq!(operator.creates_type().unwrap(), RasterType::I16);
+
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<i16>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![-2, 200, -255, 0]);
+        assert_eq!(raster.no_data_value, Some(i16::NO_DATA));
+    }
+
+    #[test]
+    fn computes_64_bit_integers_exactly() {
+        let large = (1u64 << 60) + 1;
+        let operator = MetaExpressionOperator {
+            params: ExpressionParams::new("A * 2 + B"),
+            sources: vec![
+                gdal_source("expression_exact_a.tif", vec![large, 1, u64::MAX / 2, 0]),
+                gdal_source("expression_exact_b.tif", vec![1u64, 2, 2, 3]),
+            ],
+        };
+        assert_eq!(operator.creates_type().unwrap(), RasterType::U64);
+
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<u64>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        // u64::MAX / 2 * 2 + 2 overflows the output type
+        assert_eq!(raster.data, vec![2 * large + 1, 4, u64::MAX, 3]);
+        assert_eq!(raster.validity_mask(), vec![true, true, false, true]);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        let operator = MetaExpressionOperator {
+            params: ExpressionParams {
+                output_type: Some(RasterType::U8),
+                ..ExpressionParams::new("A + C")
+            },
+            sources: vec![
+                gdal_source("expression_invalid_a.tif", vec![1u8, 2, 3, 4]),
+                gdal_source("expression_invalid_b.tif", vec![1u8, 2, 3, 4]),
+            ],
+        };
+        assert_eq!(
+            validate_raster_graph(&operator).unwrap_err().to_string(),
+            "the operator graph is invalid: MetaExpressionOperator: \
+             invalid expression \"A + C\": C references a missing source"
+        );
+    }
+}
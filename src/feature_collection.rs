@@ -0,0 +1,563 @@
+use crate::{
+    attributes::{AttributeColumn, AttributeSchema, AttributeValue},
+    error::{Error, Result},
+    primitives::{BoundingBox2D, Coordinate2D},
+};
+use serde::{Deserialize, Serialize};
+
+/// An enum for the geometry types of feature collections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VectorCollectionType {
+    MultiPoint,
+    MultiLineString,
+    MultiPolygon,
+}
+
+/// A trait for Vector Data, i.e. collections of features.
+pub trait VectorData {
+    const TYPE: VectorCollectionType;
+
+    /// the number of features
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The geometries of a feature collection, stored column-wise in one coordinate buffer.
+pub trait Geometries {
+    const TYPE: VectorCollectionType;
+
+    /// the number of features
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// all coordinates of all features
+    fn coordinates(&self) -> &[Coordinate2D];
+
+    /// the range of `coordinates()` belonging to a feature
+    fn feature_coordinate_range(&self, feature: usize) -> std::ops::Range<usize>;
+
+    /// new geometries consisting of the given features in the given order
+    fn select(&self, features: &[usize]) -> Self
+    where
+        Self: Sized;
+
+    /// the bounding box of a feature or `None` if it has no coordinates
+    fn feature_bounding_box(&self, feature: usize) -> Option<BoundingBox2D> {
+        let coordinates = &self.coordinates()[self.feature_coordinate_range(feature)];
+        BoundingBox2D::from_coordinates(coordinates.iter().copied())
+    }
+
+    /// the center of the bounding box of a feature, i.e. the point itself for single points
+    fn feature_center(&self, feature: usize) -> Option<Coordinate2D> {
+        let bbox = self.feature_bounding_box(feature)?;
+        let (lower_left, upper_right) = (bbox.lower_left(), bbox.upper_right());
+        Some(Coordinate2D::new(
+            (lower_left.x + upper_right.x) / 2.,
+            (lower_left.y + upper_right.y) / 2.,
+        ))
+    }
+}
+
+/// A collection of features. All features have the same geometry type and the same attribute columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureCollection<G> {
+    pub geometries: G,
+    attributes: Vec<(String, AttributeColumn)>,
+}
+
+impl<G: Geometries> FeatureCollection<G> {
+    pub fn new(geometries: G) -> Self {
+        Self {
+            geometries,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// adds an attribute column with one value per feature
+    pub fn with_attribute(mut self, name: &str, column: AttributeColumn) -> Result<Self> {
+        if self.attribute(name).is_some() {
+            return Err(Error::DuplicateAttribute(name.to_string()));
+        }
+        if column.len() != self.geometries.len() {
+            return Err(Error::AttributeLengthMismatch {
+                name: name.to_string(),
+                expected: self.geometries.len(),
+                found: column.len(),
+            });
+        }
+        self.attributes.push((name.to_string(), column));
+        Ok(self)
+    }
+
+    /// the attribute column with this name
+    pub fn attribute(&self, name: &str) -> Option<&AttributeColumn> {
+        self.attributes
+            .iter()
+            .find(|(column_name, _)| column_name == name)
+            .map(|(_, column)| column)
+    }
+
+    /// the value of an attribute for a feature or `None` if there is no such column
+    pub fn attribute_value(&self, name: &str, feature: usize) -> Option<AttributeValue> {
+        self.attribute(name)?.get(feature)
+    }
+
+    /// all attribute columns in their order
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, &AttributeColumn)> {
+        self.attributes
+            .iter()
+            .map(|(name, column)| (name.as_str(), column))
+    }
+
+    /// the names and types of the attribute columns
+    pub fn schema(&self) -> AttributeSchema {
+        self.attributes
+            .iter()
+            .fold(AttributeSchema::new(), |schema, (name, column)| {
+                schema
+                    .with_column(name, column.attribute_type())
+                    .expect("attribute names are unique")
+            })
+    }
+
+    /// a new collection consisting of the given features and their attributes
+    pub fn select(&self, features: &[usize]) -> Self {
+        Self {
+            geometries: self.geometries.select(features),
+            attributes: self
+                .attributes
+                .iter()
+                .map(|(name, column)| (name.clone(), column.select(features)))
+                .collect(),
+        }
+    }
+
+    /// the indices of all features whose bounding box intersects `bbox`
+    pub fn features_intersecting(&self, bbox: &BoundingBox2D) -> Vec<usize> {
+        (0..self.geometries.len())
+            .filter(|&feature| {
+                self.geometries
+                    .feature_bounding_box(feature)
+                    .is_some_and(|feature_bbox| feature_bbox.intersection(bbox).is_some())
+            })
+            .collect()
+    }
+}
+
+impl<G: Geometries> VectorData for FeatureCollection<G> {
+    const TYPE: VectorCollectionType = G::TYPE;
+
+    fn len(&self) -> usize {
+        self.geometries.len()
+    }
+}
+
+pub type MultiPointCollection = FeatureCollection<MultiPoints>;
+pub type MultiLineStringCollection = FeatureCollection<MultiLineStrings>;
+pub type MultiPolygonCollection = FeatureCollection<MultiPolygons>;
+
+/// checks that offsets start at 0, never decrease and end at `len`
+fn assert_offsets(offsets: &[usize], len: usize, name: &str) {
+    assert!(
+        offsets.first() == Some(&0)
+            && offsets.last() == Some(&len)
+            && offsets.windows(2).all(|window| window[0] <= window[1]),
+        "the {} offsets {:?} do not partition {} elements",
+        name,
+        offsets,
+        len
+    );
+}
+
+/// builds offsets from the lengths of consecutive parts
+fn offsets_from_lengths(lengths: impl Iterator<Item = usize>) -> Vec<usize> {
+    let mut offsets = vec![0];
+    for length in lengths {
+        offsets.push(offsets[offsets.len() - 1] + length);
+    }
+    offsets
+}
+
+/// Each feature consists of one or more points.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MultiPoints {
+    coordinates: Vec<Coordinate2D>,
+    feature_offsets: Vec<usize>,
+}
+
+impl MultiPoints {
+    /// Creates multi points from columns. Feature `i` owns `coordinates[feature_offsets[i]..feature_offsets[i + 1]]`.
+    ///
+    /// # Panics
+    /// If the offsets do not partition the coordinates.
+    pub fn new(coordinates: Vec<Coordinate2D>, feature_offsets: Vec<usize>) -> Self {
+        assert_offsets(&feature_offsets, coordinates.len(), "feature");
+        Self {
+            coordinates,
+            feature_offsets,
+        }
+    }
+
+    pub fn from_features(features: Vec<Vec<Coordinate2D>>) -> Self {
+        let feature_offsets = offsets_from_lengths(features.iter().map(Vec::len));
+        Self::new(features.into_iter().flatten().collect(), feature_offsets)
+    }
+
+    pub fn feature_offsets(&self) -> &[usize] {
+        &self.feature_offsets
+    }
+
+    /// the points of a feature
+    pub fn feature(&self, feature: usize) -> &[Coordinate2D] {
+        &self.coordinates[self.feature_coordinate_range(feature)]
+    }
+}
+
+impl Geometries for MultiPoints {
+    const TYPE: VectorCollectionType = VectorCollectionType::MultiPoint;
+
+    fn len(&self) -> usize {
+        self.feature_offsets.len().saturating_sub(1)
+    }
+
+    fn coordinates(&self) -> &[Coordinate2D] {
+        &self.coordinates
+    }
+
+    fn feature_coordinate_range(&self, feature: usize) -> std::ops::Range<usize> {
+        self.feature_offsets[feature]..self.feature_offsets[feature + 1]
+    }
+
+    fn select(&self, features: &[usize]) -> Self {
+        Self::from_features(
+            features
+                .iter()
+                .map(|&feature| self.feature(feature).to_vec())
+                .collect(),
+        )
+    }
+}
+
+/// Each feature consists of one or more lines.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MultiLineStrings {
+    coordinates: Vec<Coordinate2D>,
+    line_offsets: Vec<usize>,
+    feature_offsets: Vec<usize>,
+}
+
+impl MultiLineStrings {
+    /// Creates multi line strings from columns. Line `j` owns `coordinates[line_offsets[j]..line_offsets[j + 1]]`
+    /// and feature `i` owns the lines `feature_offsets[i]..feature_offsets[i + 1]`.
+    ///
+    /// # Panics
+    /// If the offsets do not partition the coordinates and lines.
+    pub fn new(
+        coordinates: Vec<Coordinate2D>,
+        line_offsets: Vec<usize>,
+        feature_offsets: Vec<usize>,
+    ) -> Self {
+        assert_offsets(&line_offsets, coordinates.len(), "line");
+        assert_offsets(
+            &feature_offsets,
+            line_offsets.len().saturating_sub(1),
+            "feature",
+        );
+        Self {
+            coordinates,
+            line_offsets,
+            feature_offsets,
+        }
+    }
+
+    pub fn from_features(features: Vec<Vec<Vec<Coordinate2D>>>) -> Self {
+        let feature_offsets = offsets_from_lengths(features.iter().map(Vec::len));
+        let lines: Vec<Vec<Coordinate2D>> = features.into_iter().flatten().collect();
+        let line_offsets = offsets_from_lengths(lines.iter().map(Vec::len));
+        Self::new(
+            lines.into_iter().flatten().collect(),
+            line_offsets,
+            feature_offsets,
+        )
+    }
+
+    pub fn line_offsets(&self) -> &[usize] {
+        &self.line_offsets
+    }
+
+    pub fn feature_offsets(&self) -> &[usize] {
+        &self.feature_offsets
+    }
+
+    /// the lines of a feature
+    pub fn feature(&self, feature: usize) -> Vec<&[Coordinate2D]> {
+        (self.feature_offsets[feature]..self.feature_offsets[feature + 1])
+            .map(|line| &self.coordinates[self.line_offsets[line]..self.line_offsets[line + 1]])
+            .collect()
+    }
+}
+
+impl Geometries for MultiLineStrings {
+    const TYPE: VectorCollectionType = VectorCollectionType::MultiLineString;
+
+    fn len(&self) -> usize {
+        self.feature_offsets.len().saturating_sub(1)
+    }
+
+    fn coordinates(&self) -> &[Coordinate2D] {
+        &self.coordinates
+    }
+
+    fn feature_coordinate_range(&self, feature: usize) -> std::ops::Range<usize> {
+        self.line_offsets[self.feature_offsets[feature]]
+            ..self.line_offsets[self.feature_offsets[feature + 1]]
+    }
+
+    fn select(&self, features: &[usize]) -> Self {
+        Self::from_features(
+            features
+                .iter()
+                .map(|&feature| {
+                    self.feature(feature)
+                        .into_iter()
+                        .map(<[Coordinate2D]>::to_vec)
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Each feature consists of one or more polygons. Each polygon has an exterior ring followed by its holes.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MultiPolygons {
+    coordinates: Vec<Coordinate2D>,
+    ring_offsets: Vec<usize>,
+    polygon_offsets: Vec<usize>,
+    feature_offsets: Vec<usize>,
+}
+
+impl MultiPolygons {
+    /// Creates multi polygons from columns. Ring `k` owns `coordinates[ring_offsets[k]..ring_offsets[k + 1]]`,
+    /// polygon `j` owns the rings `polygon_offsets[j]..polygon_offsets[j + 1]` and
+    /// feature `i` owns the polygons `feature_offsets[i]..feature_offsets[i + 1]`.
+    ///
+    /// # Panics
+    /// If the offsets do not partition the coordinates, rings and polygons.
+    pub fn new(
+        coordinates: Vec<Coordinate2D>,
+        ring_offsets: Vec<usize>,
+        polygon_offsets: Vec<usize>,
+        feature_offsets: Vec<usize>,
+    ) -> Self {
+        assert_offsets(&ring_offsets, coordinates.len(), "ring");
+        assert_offsets(
+            &polygon_offsets,
+            ring_offsets.len().saturating_sub(1),
+            "polygon",
+        );
+        assert_offsets(
+            &feature_offsets,
+            polygon_offsets.len().saturating_sub(1),
+            "feature",
+        );
+        Self {
+            coordinates,
+            ring_offsets,
+            polygon_offsets,
+            feature_offsets,
+        }
+    }
+
+    pub fn from_features(features: Vec<Vec<Vec<Vec<Coordinate2D>>>>) -> Self {
+        let feature_offsets = offsets_from_lengths(features.iter().map(Vec::len));
+        let polygons: Vec<Vec<Vec<Coordinate2D>>> = features.into_iter().flatten().collect();
+        let polygon_offsets = offsets_from_lengths(polygons.iter().map(Vec::len));
+        let rings: Vec<Vec<Coordinate2D>> = polygons.into_iter().flatten().collect();
+        let ring_offsets = offsets_from_lengths(rings.iter().map(Vec::len));
+        Self::new(
+            rings.into_iter().flatten().collect(),
+            ring_offsets,
+            polygon_offsets,
+            feature_offsets,
+        )
+    }
+
+    pub fn ring_offsets(&self) -> &[usize] {
+        &self.ring_offsets
+    }
+
+    pub fn polygon_offsets(&self) -> &[usize] {
+        &self.polygon_offsets
+    }
+
+    pub fn feature_offsets(&self) -> &[usize] {
+        &self.feature_offsets
+    }
+
+    /// the polygons of a feature, each given by its rings
+    pub fn feature(&self, feature: usize) -> Vec<Vec<&[Coordinate2D]>> {
+        (self.feature_offsets[feature]..self.feature_offsets[feature + 1])
+            .map(|polygon| {
+                (self.polygon_offsets[polygon]..self.polygon_offsets[polygon + 1])
+                    .map(|ring| {
+                        &self.coordinates[self.ring_offsets[ring]..self.ring_offsets[ring + 1]]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Geometries for MultiPolygons {
+    const TYPE: VectorCollectionType = VectorCollectionType::MultiPolygon;
+
+    fn len(&self) -> usize {
+        self.feature_offsets.len().saturating_sub(1)
+    }
+
+    fn coordinates(&self) -> &[Coordinate2D] {
+        &self.coordinates
+    }
+
+    fn feature_coordinate_range(&self, feature: usize) -> std::ops::Range<usize> {
+        let first_ring = self.polygon_offsets[self.feature_offsets[feature]];
+        let end_ring = self.polygon_offsets[self.feature_offsets[feature + 1]];
+        self.ring_offsets[first_ring]..self.ring_offsets[end_ring]
+    }
+
+    fn select(&self, features: &[usize]) -> Self {
+        Self::from_features(
+            features
+                .iter()
+                .map(|&feature| {
+                    self.feature(feature)
+                        .into_iter()
+                        .map(|rings| rings.into_iter().map(<[Coordinate2D]>::to_vec).collect())
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::AttributeType;
+
+    fn c(x: f64, y: f64) -> Coordinate2D {
+        Coordinate2D::new(x, y)
+    }
+
+    #[test]
+    fn multi_points() {
+        let points = MultiPoints::from_features(vec![vec![c(0., 0.)], vec![c(1., 1.), c(2., 3.)]]);
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(points.feature_offsets(), &[0, 1, 3]);
+        assert_eq!(points.feature(1), &[c(1., 1.), c(2., 3.)]);
+        assert_eq!(
+            points.feature_bounding_box(1),
+            Some(BoundingBox2D::new(c(1., 1.), c(2., 3.)).unwrap())
+        );
+    }
+
+    #[test]
+    fn multi_line_strings() {
+        let lines = MultiLineStrings::from_features(vec![
+            vec![vec![c(0., 0.), c(1., 0.)], vec![c(5., 5.), c(6., 6.)]],
+            vec![vec![c(2., 2.), c(3., 3.), c(4., 2.)]],
+        ]);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines.line_offsets(), &[0, 2, 4, 7]);
+        assert_eq!(lines.feature_offsets(), &[0, 2, 3]);
+        assert_eq!(lines.feature(0)[1], &[c(5., 5.), c(6., 6.)]);
+        assert_eq!(lines.feature_coordinate_range(1), 4..7);
+    }
+
+    #[test]
+    fn multi_polygons() {
+        let square = |offset: f64| {
+            vec![
+                c(offset, offset),
+                c(offset + 1., offset),
+                c(offset + 1., offset + 1.),
+                c(offset, offset),
+            ]
+        };
+        let polygons = MultiPolygons::from_features(vec![
+            vec![vec![square(0.)], vec![square(10.)]],
+            vec![vec![square(20.), square(20.25)]],
+        ]);
+
+        assert_eq!(polygons.len(), 2);
+        assert_eq!(polygons.polygon_offsets(), &[0, 1, 2, 4]);
+        assert_eq!(polygons.feature(1)[0].len(), 2);
+        assert_eq!(
+            polygons.feature_bounding_box(0),
+            Some(BoundingBox2D::new(c(0., 0.), c(11., 11.)).unwrap())
+        );
+
+        let collection = FeatureCollection::new(polygons);
+        assert_eq!(
+            collection
+                .features_intersecting(&BoundingBox2D::new(c(15., 15.), c(30., 30.)).unwrap()),
+            vec![1]
+        );
+        assert_eq!(
+            collection.select(&[1]).geometries.feature(0),
+            collection.geometries.feature(1)
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let points = MultiPoints::from_features(vec![vec![c(0., 0.)], vec![c(1., 1.)]]);
+        let collection = FeatureCollection::new(points)
+            .with_attribute("name", AttributeColumn::Text(vec![Some("a".into()), None]))
+            .unwrap()
+            .with_attribute("value", AttributeColumn::Float(vec![Some(1.5), Some(2.)]))
+            .unwrap();
+
+        assert_eq!(
+            collection.attribute_value("value", 1),
+            Some(AttributeValue::Float(2.))
+        );
+        assert_eq!(
+            collection.attribute_value("name", 1),
+            Some(AttributeValue::Null)
+        );
+        assert_eq!(
+            collection.schema(),
+            AttributeSchema::new()
+                .with_column("name", AttributeType::Text)
+                .unwrap()
+                .with_column("value", AttributeType::Float)
+                .unwrap()
+        );
+        assert!(matches!(
+            collection
+                .clone()
+                .with_attribute("value", AttributeColumn::Int(vec![None, None])),
+            Err(Error::DuplicateAttribute(_))
+        ));
+        assert!(matches!(
+            collection.with_attribute("id", AttributeColumn::Int(vec![Some(1)])),
+            Err(Error::AttributeLengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn offsets_must_partition_coordinates() {
+        MultiPoints::new(vec![c(0., 0.)], vec![0, 2]);
+    }
+}
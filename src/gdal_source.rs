@@ -1,124 +1,416 @@
 use crate::{
-    meta_raster_operator::MetaRasterOperator,
-    primitives::Raster,
+    error::{Error, Result},
+    geotiff::{self, GeoTiffError, GeoTiffMetadata, GeoTiffPixel},
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
+    primitives::{Raster, TimeInterval},
     raster_type::RasterType,
-    source::{CreateSourceOperator, Query, RasterSource, Source},
+    result_descriptor::RasterResultDescriptor,
+    source::{CreateSourceOperator, OperatorParams, Query, RasterSource, Source},
     MetaOperator,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::marker::PhantomData;
-use typetag;
+use std::{cell::OnceCell, marker::PhantomData, path::Path};
 
-/// A GdalSource produces typed Raster<T> data
+/// A GdalSource produces typed Raster<T> data. The dataset is the path of a GeoTIFF file.
 #[derive(Debug, Clone)]
 pub struct GdalSource<T> {
     pub dataset: String,
+    /// the bands to read in this order or all bands if it is `None`
+    pub bands: Option<Vec<usize>>,
+    /// the time the data is valid or `None` if it is valid at all times
+    pub time: Option<TimeInterval>,
     pub data: PhantomData<T>,
 }
 
 // It is a Source producing Raster<T> -> its a RasterSource
 impl<T> Source for GdalSource<T>
 where
-    T: Default + Copy,
+    T: GeoTiffPixel,
 {
     type Output = Raster<T>;
-    fn query(&self, _: Query) -> Self::Output {
+    /// reads the pixels intersecting the query rectangle and resamples them to the query resolution
+    /// with nearest neighbour. Fails if the data is not valid at the query time.
+    fn query(&self, query: Query) -> Result<Self::Output> {
         println!("GdalSource query");
-        Raster {
-            v: vec![T::default(); 4],
+        if let Some(valid) = self
+            .time
+            .filter(|time| !time.intersects(&query.time_interval))
+        {
+            return Err(Error::TimeOutOfRange {
+                requested: query.time_interval,
+                valid,
+            });
+        }
+        let raster =
+            geotiff::read_bands(Path::new(&self.dataset), &query.bbox, self.bands.as_deref())
+                .map_err(|source| Error::Dataset {
+                    dataset: self.dataset.clone(),
+                    source: source.into(),
+                })?;
+        if raster.geo_transform.resolution() == query.resolution {
+            Ok(raster)
+        } else {
+            raster.resample_nearest(query.resolution)
         }
     }
 }
 
-impl<T> CreateSourceOperator<String> for GdalSource<T> {
-    fn create(params: String) -> Self {
+impl<T> CreateSourceOperator<GdalSourceParams> for GdalSource<T> {
+    fn create(params: GdalSourceParams) -> Self {
         GdalSource {
             data: PhantomData,
-            dataset: params,
+            dataset: params.dataset,
+            bands: params.bands,
+            time: params.time,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The parameters of the `MetaGdalSource`. Only the dataset is required.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct GdalSourceParams {
+    pub dataset: String,
+    /// the expected type of the dataset. If given, it must match the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raster_type: Option<RasterType>,
+    /// the bands to read in this order. All bands are read if it is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bands: Option<Vec<usize>>,
+    /// the time the data is valid. GeoTIFFs have no time, so it is valid at all times if it is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<TimeInterval>,
+}
+
+impl GdalSourceParams {
+    pub fn new(dataset: impl Into<String>) -> Self {
+        GdalSourceParams {
+            dataset: dataset.into(),
+            raster_type: None,
+            bands: None,
+            time: None,
+        }
+    }
+}
+
+impl OperatorParams for GdalSourceParams {
+    /// fails for an empty dataset path or an empty band selection
+    fn validate(&self) -> Result<()> {
+        if self.dataset.is_empty() {
+            return Err(Error::InvalidParameter {
+                name: "dataset".to_string(),
+                reason: "the path must not be empty".to_string(),
+            });
+        }
+        if self.bands.as_ref().is_some_and(Vec::is_empty) {
+            return Err(Error::InvalidParameter {
+                name: "bands".to_string(),
+                reason: "at least one band must be selected".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Reads a raster from a GeoTIFF file.
+///
+/// The MetaGdalSource only needs the dataset. Its type, bands, extent and CRS are read from the file.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct MetaGdalSource {
-    pub raster_type: RasterType,
+    #[serde(flatten)]
+    pub params: GdalSourceParams,
+    /// the metadata of the dataset, so the file is only read once
+    #[serde(skip)]
+    #[schemars(skip)]
+    metadata: OnceCell<GeoTiffMetadata>,
+}
+
+impl MetaGdalSource {
+    /// a source reading all bands of the dataset
+    pub fn new(dataset: impl Into<String>) -> Self {
+        Self::with_params(GdalSourceParams::new(dataset))
+    }
+
+    /// a source with the expected type, a band selection or a time
+    pub fn with_params(params: GdalSourceParams) -> Self {
+        MetaGdalSource {
+            params,
+            metadata: OnceCell::new(),
+        }
+    }
+
+    /// reads the metadata of the dataset and checks it against the expected `raster_type` and `bands`.
+    /// The file is read on the first successful call, later calls use its metadata.
+    pub fn dataset_metadata(&self) -> Result<GeoTiffMetadata> {
+        self.params.validate()?;
+        let dataset = &self.params.dataset;
+        let metadata = match self.metadata.get() {
+            Some(metadata) => metadata.clone(),
+            None => {
+                let metadata = geotiff::read_metadata(Path::new(dataset)).map_err(|source| {
+                    Error::Dataset {
+                        dataset: dataset.clone(),
+                        source: source.into(),
+                    }
+                })?;
+                self.metadata.get_or_init(|| metadata).clone()
+            }
+        };
+
+        if let Some(expected) = self
+            .params
+            .raster_type
+            .filter(|&t| t != metadata.raster_type)
+        {
+            return Err(Error::Dataset {
+                dataset: dataset.clone(),
+                source: GeoTiffError::DataTypeMismatch {
+                    expected,
+                    found: metadata.raster_type,
+                }
+                .into(),
+            });
+        }
+
+        if let Some(&band) = self
+            .params
+            .bands
+            .iter()
+            .flatten()
+            .find(|&&band| band >= metadata.bands)
+        {
+            return Err(Error::Dataset {
+                dataset: dataset.clone(),
+                source: GeoTiffError::BandOutOfRange {
+                    band,
+                    bands: metadata.bands,
+                }
+                .into(),
+            });
+        }
+        Ok(metadata)
+    }
+}
+
+impl MetaGdalSource {
+    fn create_typed_raster_op<T: GeoTiffPixel>(
+        &self,
+    ) -> Result<Box<dyn RasterSource<RasterType = T>>> {
+        println!("MetaGdalSource: create_typed_raster_op");
+        self.params.validate()?;
+        Ok(Box::new(GdalSource::<T>::create(self.params.clone())))
+    }
 }
 
 impl MetaOperator for MetaGdalSource {
     fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
         &[] // no sources!
     }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &[]
+    }
 }
 
 #[typetag::serde]
 impl MetaRasterOperator for MetaGdalSource {
-    fn creates_type(&self) -> RasterType {
-        self.raster_type // TODO: need to look this up!
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        self.dataset_metadata()
+            .map(|metadata| RasterCreates::SecificType(metadata.raster_type))
     }
 
-    fn create_u8_raster_op(&self) -> Box<dyn RasterSource<RasterType = u8>> {
-        println!("MetaGdalSource: create_u8_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
-        })
-    }
-    fn create_u16_raster_op(&self) -> Box<dyn RasterSource<RasterType = u16>> {
-        println!("MetaGdalSource: create_u16_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
-        })
+    fn band_count(&self) -> Result<usize> {
+        let metadata = self.dataset_metadata()?;
+        Ok(self.params.bands.as_ref().map_or(metadata.bands, Vec::len))
     }
 
-    fn create_u32_raster_op(&self) -> Box<dyn RasterSource<RasterType = u32>> {
-        println!("MetaGdalSource: create_u8_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
+    /// GeoTIFFs have no time, so their data is valid at the time of the params or at all times
+    fn result_descriptor(&self) -> Result<RasterResultDescriptor> {
+        let metadata = self.dataset_metadata()?;
+        Ok(RasterResultDescriptor {
+            data_type: metadata.raster_type,
+            bands: self.params.bands.as_ref().map_or(metadata.bands, Vec::len),
+            bbox: metadata.bounding_box(),
+            resolution: metadata.geo_transform.resolution(),
+            spatial_reference: metadata.spatial_reference,
+            time: self.params.time,
+            no_data_value: metadata.no_data_value,
         })
     }
-    fn create_u64_raster_op(&self) -> Box<dyn RasterSource<RasterType = u64>> {
-        println!("MetaGdalSource: create_u8_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
-        })
+
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaGdalSource: Raster
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::write_test_geotiff, BoundingBox2D, Coordinate2D, GridShape, SpatialReference,
+        SpatialResolution,
+    };
+
+    #[test]
+    fn looks_up_dataset_metadata() {
+        let dataset = write_test_geotiff(
+            "gdal_source_looks_up_dataset_metadata.tif",
+            GridShape::new(3, 2),
+            vec![1i16; 6],
+        );
+
+        let source = MetaGdalSource::new(dataset.as_str());
+        let metadata = source.dataset_metadata().unwrap();
+        assert_eq!(metadata.raster_type, RasterType::I16);
+        assert_eq!(metadata.bands, 1);
+        assert_eq!(metadata.shape, GridShape::new(3, 2));
+        assert_eq!(
+            metadata.spatial_reference,
+            Some(SpatialReference::epsg(32632))
+        );
+        assert_eq!(source.creates_type().unwrap(), RasterType::I16);
+
+        let source = MetaGdalSource::with_params(GdalSourceParams {
+            raster_type: Some(RasterType::I16),
+            ..GdalSourceParams::new(dataset.clone())
+        });
+        assert!(source.dataset_metadata().is_ok());
+
+        let source = MetaGdalSource::with_params(GdalSourceParams {
+            raster_type: Some(RasterType::F32),
+            ..GdalSourceParams::new(dataset)
+        });
+        assert_eq!(
+            source.dataset_metadata().unwrap_err().to_string(),
+            format!(
+                "cannot read dataset {}: the file contains I16 data but F32 was requested",
+                source.params.dataset
+            )
+        );
     }
-    fn create_i16_raster_op(&self) -> Box<dyn RasterSource<RasterType = i16>> {
-        println!("MetaGdalSource: create_u8_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
-        })
+
+    #[test]
+    fn reads_the_dataset_metadata_once() {
+        let dataset = write_test_geotiff(
+            "gdal_source_metadata_once.tif",
+            GridShape::new(2, 2),
+            vec![1u8, 2, 3, 4],
+        );
+        let source = MetaGdalSource::with_params(GdalSourceParams {
+            bands: Some(vec![0]),
+            ..GdalSourceParams::new(dataset.as_str())
+        });
+        let metadata = source.dataset_metadata().unwrap();
+
+        std::fs::remove_file(&dataset).unwrap();
+        assert_eq!(source.dataset_metadata().unwrap(), metadata);
+        assert_eq!(source.result_descriptor().unwrap().bands, 1);
+        // a new source reads the file again
+        assert!(MetaGdalSource::new(dataset).dataset_metadata().is_err());
     }
-    fn create_i32_raster_op(&self) -> Box<dyn RasterSource<RasterType = i32>> {
-        println!("MetaGdalSource: create_u8_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
-        })
+
+    #[test]
+    fn validates_params() {
+        assert_eq!(
+            MetaGdalSource::new("")
+                .creates_type()
+                .unwrap_err()
+                .to_string(),
+            "the parameter dataset is invalid: the path must not be empty"
+        );
+        let source = MetaGdalSource::with_params(GdalSourceParams {
+            bands: Some(vec![]),
+            ..GdalSourceParams::new("data.tif")
+        });
+        assert_eq!(
+            source.create_raster_op().err().unwrap().to_string(),
+            "the operator graph is invalid: MetaGdalSource: \
+             the parameter bands is invalid: at least one band must be selected"
+        );
     }
-    fn create_i64_raster_op(&self) -> Box<dyn RasterSource<RasterType = i64>> {
-        println!("MetaGdalSource: create_u8_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
-        })
+
+    #[test]
+    fn resamples_to_the_query_resolution() {
+        let source = GdalSource::<u8>::create(GdalSourceParams::new(write_test_geotiff(
+            "gdal_source_resamples.tif",
+            GridShape::new(4, 4),
+            (0..16).collect::<Vec<u8>>(),
+        )));
+        let bbox =
+            BoundingBox2D::new(Coordinate2D::new(0., 0.), Coordinate2D::new(4., 4.)).unwrap();
+        let query = |resolution| {
+            Query::new(
+                bbox,
+                TimeInterval::new(0, 1).unwrap(),
+                SpatialResolution::new_square(resolution).unwrap(),
+            )
+            .unwrap()
+        };
+
+        let coarse = source.query(query(2.)).unwrap();
+        assert_eq!(coarse.shape, GridShape::new(2, 2));
+        assert_eq!(
+            coarse.geo_transform.resolution(),
+            SpatialResolution::new_square(2.).unwrap()
+        );
+        assert_eq!(coarse.bounding_box(), bbox);
+        assert_eq!(coarse.data, vec![5, 7, 13, 15]);
+
+        let fine = source.query(query(0.5)).unwrap();
+        assert_eq!(fine.shape, GridShape::new(8, 8));
+        assert_eq!(fine.data[..8], [0, 0, 1, 1, 2, 2, 3, 3]);
+        assert_eq!(fine.data[8..16], [0, 0, 1, 1, 2, 2, 3, 3]);
     }
-    fn create_f32_raster_op(&self) -> Box<dyn RasterSource<RasterType = f32>> {
-        println!("MetaGdalSource: create_u8_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
-        })
+
+    #[test]
+    fn rejects_queries_outside_of_the_valid_time() {
+        let source = MetaGdalSource::with_params(GdalSourceParams {
+            time: Some(TimeInterval::new(10, 20).unwrap()),
+            ..GdalSourceParams::new(write_test_geotiff(
+                "gdal_source_time.tif",
+                GridShape::new(2, 2),
+                vec![1u8, 2, 3, 4],
+            ))
+        });
+        assert_eq!(
+            source.result_descriptor().unwrap().time,
+            Some(TimeInterval::new(10, 20).unwrap())
+        );
+
+        let source = source.create_raster_op().unwrap().get::<u8>().unwrap();
+        let query = |time| {
+            Query::new(
+                BoundingBox2D::new(Coordinate2D::new(0., 0.), Coordinate2D::new(2., 2.)).unwrap(),
+                time,
+                SpatialResolution::new_square(1.).unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            source
+                .raster_query(query(TimeInterval::new(15, 30).unwrap()))
+                .unwrap()
+                .data,
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            source
+                .raster_query(query(TimeInterval::new(20, 30).unwrap()))
+                .unwrap_err()
+                .to_string(),
+            "the query asks for [20, 30) but the data is valid in [10, 20)"
+        );
     }
 
-    fn create_f64_raster_op(&self) -> Box<dyn RasterSource<RasterType = f64>> {
-        println!("MetaGdalSource: create_u8_raster_op");
-        Box::new(GdalSource {
-            dataset: "meh".to_string(),
-            data: PhantomData,
-        })
+    #[test]
+    fn raster_type_is_optional_in_workflows() {
+        let source: Box<dyn MetaRasterOperator> =
+            serde_json::from_str(r#"{"type": "MetaGdalSource", "dataset": "data.tif"}"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&source).unwrap(),
+            r#"{"type":"MetaGdalSource","dataset":"data.tif"}"#
+        );
     }
 }
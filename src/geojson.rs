@@ -0,0 +1,624 @@
+use crate::{
+    attributes::{AttributeColumn, AttributeSchema, AttributeType, AttributeValue},
+    feature_collection::{
+        FeatureCollection, Geometries, MultiLineStrings, MultiPoints, MultiPolygons,
+        VectorCollectionType,
+    },
+    primitives::{BoundingBox2D, Coordinate2D},
+};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// The errors that can occur while reading or writing GeoJSON files.
+#[derive(Debug)]
+pub enum GeoJsonError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// the file is JSON but not a GeoJSON FeatureCollection
+    Format(String),
+    /// the features have geometries that belong to different collection types
+    MixedGeometryTypes {
+        first: VectorCollectionType,
+        second: VectorCollectionType,
+    },
+    /// a property has values that do not fit into one attribute column
+    MixedPropertyTypes {
+        property: String,
+        first: AttributeType,
+        second: AttributeType,
+    },
+    /// the file contains other geometries than requested
+    GeometryTypeMismatch {
+        expected: VectorCollectionType,
+        found: VectorCollectionType,
+    },
+}
+
+impl fmt::Display for GeoJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeoJsonError::Io(error) => write!(f, "io error: {}", error),
+            GeoJsonError::Json(error) => write!(f, "json error: {}", error),
+            GeoJsonError::Format(message) => write!(f, "invalid GeoJSON: {}", message),
+            GeoJsonError::MixedGeometryTypes { first, second } => {
+                write!(f, "the file mixes {:?} and {:?} geometries", first, second)
+            }
+            GeoJsonError::MixedPropertyTypes {
+                property,
+                first,
+                second,
+            } => write!(
+                f,
+                "the property {} has {:?} and {:?} values",
+                property, first, second
+            ),
+            GeoJsonError::GeometryTypeMismatch { expected, found } => write!(
+                f,
+                "the file contains {:?} geometries but {:?} was requested",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GeoJsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GeoJsonError::Io(error) => Some(error),
+            GeoJsonError::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GeoJsonError {
+    fn from(error: std::io::Error) -> Self {
+        GeoJsonError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for GeoJsonError {
+    fn from(error: serde_json::Error) -> Self {
+        GeoJsonError::Json(error)
+    }
+}
+
+/// The collection type and attributes of a GeoJSON file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoJsonMetadata {
+    /// the collection type of the geometries. Files without geometries are read as multi points.
+    pub collection_type: VectorCollectionType,
+    /// one column per property, sorted by name
+    pub schema: AttributeSchema,
+    /// the area covered by the geometries or `None` if there are none
+    pub bbox: Option<BoundingBox2D>,
+}
+
+/// Geometries that can be read from and written to GeoJSON. Single geometries are read as multi geometries with one part.
+pub trait GeoJsonGeometries: Geometries + Sized {
+    /// builds the geometries from one GeoJSON geometry per feature. `null` geometries become empty features.
+    fn from_geojson(geometries: &[&Value]) -> Result<Self, GeoJsonError>;
+
+    /// the GeoJSON geometry of a feature
+    fn to_geojson(&self, feature: usize) -> Value;
+}
+
+impl GeoJsonGeometries for MultiPoints {
+    fn from_geojson(geometries: &[&Value]) -> Result<Self, GeoJsonError> {
+        let features = geometries
+            .iter()
+            .map(|&geometry| match geometry_type(geometry)? {
+                None => Ok(Vec::new()),
+                Some("Point") => Ok(vec![position(coordinates(geometry)?)?]),
+                Some("MultiPoint") => positions(coordinates(geometry)?),
+                Some(other) => Err(unexpected_geometry(other, Self::TYPE)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self::from_features(features))
+    }
+
+    fn to_geojson(&self, feature: usize) -> Value {
+        serde_json::json!({
+            "type": "MultiPoint",
+            "coordinates": json_positions(self.feature(feature)),
+        })
+    }
+}
+
+impl GeoJsonGeometries for MultiLineStrings {
+    fn from_geojson(geometries: &[&Value]) -> Result<Self, GeoJsonError> {
+        let features = geometries
+            .iter()
+            .map(|&geometry| match geometry_type(geometry)? {
+                None => Ok(Vec::new()),
+                Some("LineString") => Ok(vec![positions(coordinates(geometry)?)?]),
+                Some("MultiLineString") => lines(coordinates(geometry)?),
+                Some(other) => Err(unexpected_geometry(other, Self::TYPE)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self::from_features(features))
+    }
+
+    fn to_geojson(&self, feature: usize) -> Value {
+        let lines: Vec<_> = self
+            .feature(feature)
+            .into_iter()
+            .map(json_positions)
+            .collect();
+        serde_json::json!({"type": "MultiLineString", "coordinates": lines})
+    }
+}
+
+impl GeoJsonGeometries for MultiPolygons {
+    fn from_geojson(geometries: &[&Value]) -> Result<Self, GeoJsonError> {
+        let features = geometries
+            .iter()
+            .map(|&geometry| match geometry_type(geometry)? {
+                None => Ok(Vec::new()),
+                Some("Polygon") => Ok(vec![lines(coordinates(geometry)?)?]),
+                Some("MultiPolygon") => array(coordinates(geometry)?)?.iter().map(lines).collect(),
+                Some(other) => Err(unexpected_geometry(other, Self::TYPE)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self::from_features(features))
+    }
+
+    fn to_geojson(&self, feature: usize) -> Value {
+        let polygons: Vec<Vec<_>> = self
+            .feature(feature)
+            .into_iter()
+            .map(|rings| rings.into_iter().map(json_positions).collect())
+            .collect();
+        serde_json::json!({"type": "MultiPolygon", "coordinates": polygons})
+    }
+}
+
+fn unexpected_geometry(geometry_type: &str, expected: VectorCollectionType) -> GeoJsonError {
+    GeoJsonError::Format(format!(
+        "a {} cannot be read as {:?}",
+        geometry_type, expected
+    ))
+}
+
+/// the type of a geometry or `None` for a `null` geometry
+fn geometry_type(geometry: &Value) -> Result<Option<&str>, GeoJsonError> {
+    if geometry.is_null() {
+        return Ok(None);
+    }
+    geometry
+        .get("type")
+        .and_then(Value::as_str)
+        .map(Some)
+        .ok_or_else(|| GeoJsonError::Format("a geometry has no type".to_string()))
+}
+
+/// the collection type a geometry is read as or `None` for a `null` geometry
+fn collection_type(geometry: &Value) -> Result<Option<VectorCollectionType>, GeoJsonError> {
+    match geometry_type(geometry)? {
+        None => Ok(None),
+        Some("Point") | Some("MultiPoint") => Ok(Some(VectorCollectionType::MultiPoint)),
+        Some("LineString") | Some("MultiLineString") => {
+            Ok(Some(VectorCollectionType::MultiLineString))
+        }
+        Some("Polygon") | Some("MultiPolygon") => Ok(Some(VectorCollectionType::MultiPolygon)),
+        Some(other) => Err(GeoJsonError::Format(format!(
+            "the geometry type {} is not supported",
+            other
+        ))),
+    }
+}
+
+fn coordinates(geometry: &Value) -> Result<&Value, GeoJsonError> {
+    geometry
+        .get("coordinates")
+        .ok_or_else(|| GeoJsonError::Format("a geometry has no coordinates".to_string()))
+}
+
+fn array(value: &Value) -> Result<&Vec<Value>, GeoJsonError> {
+    value
+        .as_array()
+        .ok_or_else(|| GeoJsonError::Format(format!("expected an array but found {}", value)))
+}
+
+/// a position with at least two numbers. Further dimensions are ignored.
+fn position(value: &Value) -> Result<Coordinate2D, GeoJsonError> {
+    match array(value)?.as_slice() {
+        [x, y, ..] => match (x.as_f64(), y.as_f64()) {
+            (Some(x), Some(y)) => Ok(Coordinate2D::new(x, y)),
+            _ => Err(GeoJsonError::Format(format!("invalid position {}", value))),
+        },
+        _ => Err(GeoJsonError::Format(format!("invalid position {}", value))),
+    }
+}
+
+fn positions(value: &Value) -> Result<Vec<Coordinate2D>, GeoJsonError> {
+    array(value)?.iter().map(position).collect()
+}
+
+fn lines(value: &Value) -> Result<Vec<Vec<Coordinate2D>>, GeoJsonError> {
+    array(value)?.iter().map(positions).collect()
+}
+
+/// adds all positions of nested coordinate arrays
+fn collect_positions(value: &Value, collected: &mut Vec<Coordinate2D>) -> Result<(), GeoJsonError> {
+    match array(value)?.first() {
+        Some(Value::Number(_)) => collected.push(position(value)?),
+        _ => {
+            for value in array(value)? {
+                collect_positions(value, collected)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn json_positions(coordinates: &[Coordinate2D]) -> Vec<[f64; 2]> {
+    coordinates.iter().map(|c| [c.x, c.y]).collect()
+}
+
+fn read_features(path: &Path) -> Result<Vec<Value>, GeoJsonError> {
+    let json: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    if json.get("type").and_then(Value::as_str) != Some("FeatureCollection") {
+        return Err(GeoJsonError::Format(
+            "expected a FeatureCollection".to_string(),
+        ));
+    }
+    match json {
+        Value::Object(mut object) => match object.remove("features") {
+            Some(Value::Array(features)) => Ok(features),
+            _ => Err(GeoJsonError::Format(
+                "a FeatureCollection needs a features array".to_string(),
+            )),
+        },
+        _ => unreachable!("only objects have a type"),
+    }
+}
+
+fn feature_geometry(feature: &Value) -> Result<&Value, GeoJsonError> {
+    feature
+        .get("geometry")
+        .ok_or_else(|| GeoJsonError::Format("a feature has no geometry".to_string()))
+}
+
+fn feature_properties(feature: &Value) -> Result<Option<&Map<String, Value>>, GeoJsonError> {
+    match feature.get("properties") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Object(properties)) => Ok(Some(properties)),
+        Some(_) => Err(GeoJsonError::Format(
+            "the properties of a feature must be an object".to_string(),
+        )),
+    }
+}
+
+/// the column type of a property value or `None` for `null`. Arrays and objects are stored as JSON text.
+fn property_type(value: &Value) -> Option<AttributeType> {
+    match value {
+        Value::Null => None,
+        Value::Bool(_) => Some(AttributeType::Bool),
+        Value::Number(number) if number.is_i64() => Some(AttributeType::Int),
+        Value::Number(_) => Some(AttributeType::Float),
+        Value::String(_) | Value::Array(_) | Value::Object(_) => Some(AttributeType::Text),
+    }
+}
+
+fn property_value(value: &Value, attribute_type: AttributeType) -> AttributeValue {
+    let value = match (attribute_type, value) {
+        (_, Value::Null) => None,
+        (AttributeType::Int, value) => value.as_i64().map(AttributeValue::Int),
+        (AttributeType::Float, value) => value.as_f64().map(AttributeValue::Float),
+        (AttributeType::Bool, value) => value.as_bool().map(AttributeValue::Bool),
+        (AttributeType::Text, Value::String(text)) => Some(AttributeValue::Text(text.clone())),
+        (AttributeType::Text, value) => Some(AttributeValue::Text(value.to_string())),
+    };
+    value.unwrap_or(AttributeValue::Null)
+}
+
+/// the collection type of the features, if any has a geometry, and their attribute schema
+fn features_metadata(
+    features: &[Value],
+) -> Result<(Option<VectorCollectionType>, AttributeSchema), GeoJsonError> {
+    let mut collection_types = None;
+    let mut property_types: BTreeMap<&str, Option<AttributeType>> = BTreeMap::new();
+
+    for feature in features {
+        if let Some(found) = collection_type(feature_geometry(feature)?)? {
+            match collection_types {
+                Some(first) if first != found => {
+                    return Err(GeoJsonError::MixedGeometryTypes {
+                        first,
+                        second: found,
+                    })
+                }
+                _ => collection_types = Some(found),
+            }
+        }
+
+        for (name, value) in feature_properties(feature)?.into_iter().flatten() {
+            let column_type = property_types.entry(name).or_insert(None);
+            *column_type = match (*column_type, property_type(value)) {
+                (first, None) => first,
+                (None, second) => second,
+                (Some(first), Some(second)) if first == second => Some(first),
+                (Some(AttributeType::Int), Some(AttributeType::Float))
+                | (Some(AttributeType::Float), Some(AttributeType::Int)) => {
+                    Some(AttributeType::Float)
+                }
+                (Some(first), Some(second)) => {
+                    return Err(GeoJsonError::MixedPropertyTypes {
+                        property: name.clone(),
+                        first,
+                        second,
+                    })
+                }
+            };
+        }
+    }
+
+    let schema = property_types.into_iter().fold(
+        AttributeSchema::new(),
+        |schema, (name, attribute_type)| {
+            // properties that are always `null` are read as text
+            schema
+                .with_column(name, attribute_type.unwrap_or(AttributeType::Text))
+                .expect("property names are unique")
+        },
+    );
+    Ok((collection_types, schema))
+}
+
+/// Reads the collection type, attribute schema and extent of a GeoJSON FeatureCollection.
+pub fn read_geojson_metadata(path: &Path) -> Result<GeoJsonMetadata, GeoJsonError> {
+    let features = read_features(path)?;
+    let (collection_type, schema) = features_metadata(&features)?;
+
+    let mut positions = Vec::new();
+    for feature in &features {
+        let geometry = feature_geometry(feature)?;
+        if geometry_type(geometry)?.is_some() {
+            collect_positions(coordinates(geometry)?, &mut positions)?;
+        }
+    }
+
+    Ok(GeoJsonMetadata {
+        collection_type: collection_type.unwrap_or(VectorCollectionType::MultiPoint),
+        schema,
+        bbox: BoundingBox2D::from_coordinates(positions),
+    })
+}
+
+/// Reads the features of a GeoJSON FeatureCollection whose bounding box intersects `bbox`.
+/// The properties become attribute columns. Missing properties are `null`.
+pub fn read_geojson<G>(
+    path: &Path,
+    bbox: &BoundingBox2D,
+) -> Result<FeatureCollection<G>, GeoJsonError>
+where
+    G: GeoJsonGeometries,
+{
+    let features = read_features(path)?;
+    let (collection_type, schema) = features_metadata(&features)?;
+    if let Some(found) = collection_type.filter(|&found| found != G::TYPE) {
+        return Err(GeoJsonError::GeometryTypeMismatch {
+            expected: G::TYPE,
+            found,
+        });
+    }
+
+    let geometries = features
+        .iter()
+        .map(feature_geometry)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut collection = FeatureCollection::new(G::from_geojson(&geometries)?);
+
+    for column in schema.columns() {
+        let mut values = AttributeColumn::empty(column.attribute_type);
+        for feature in &features {
+            let value = feature_properties(feature)?
+                .and_then(|properties| properties.get(&column.name))
+                .map_or(AttributeValue::Null, |value| {
+                    property_value(value, column.attribute_type)
+                });
+            values
+                .push(value)
+                .expect("the values match the inferred column type");
+        }
+        collection = collection
+            .with_attribute(&column.name, values)
+            .expect("the schema has one unique column per property");
+    }
+
+    let intersecting = collection.features_intersecting(bbox);
+    Ok(collection.select(&intersecting))
+}
+
+fn json_value(value: AttributeValue) -> Value {
+    match value {
+        AttributeValue::Null => Value::Null,
+        AttributeValue::Int(value) => value.into(),
+        AttributeValue::Float(value) => value.into(),
+        AttributeValue::Text(value) => value.into(),
+        AttributeValue::Bool(value) => value.into(),
+    }
+}
+
+/// Writes a collection as a GeoJSON FeatureCollection. The attributes become properties.
+pub fn write_geojson<G>(path: &Path, collection: &FeatureCollection<G>) -> Result<(), GeoJsonError>
+where
+    G: GeoJsonGeometries,
+{
+    let features: Vec<Value> = (0..collection.geometries.len())
+        .map(|feature| {
+            let properties: Map<String, Value> = collection
+                .attributes()
+                .map(|(name, column)| {
+                    let value = column.get(feature).unwrap_or(AttributeValue::Null);
+                    (name.to_string(), json_value(value))
+                })
+                .collect();
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": collection.geometries.to_geojson(feature),
+                "properties": properties,
+            })
+        })
+        .collect();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(
+        &mut writer,
+        &serde_json::json!({
+            "type": "FeatureCollection",
+            "features": features,
+        }),
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    fn c(x: f64, y: f64) -> Coordinate2D {
+        Coordinate2D::new(x, y)
+    }
+
+    fn write_test_file(name: &str, json: Value) -> std::path::PathBuf {
+        let path = temp_path(name);
+        std::fs::write(&path, json.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_points_and_properties() {
+        let path = write_test_file(
+            "geojson_reads_points.json",
+            serde_json::json!({
+                "type": "FeatureCollection",
+                "features": [
+                    {
+                        "type": "Feature",
+                        "geometry": {"type": "Point", "coordinates": [1., 2.]},
+                        "properties": {"name": "a", "value": 1, "valid": true},
+                    },
+                    {
+                        "type": "Feature",
+                        "geometry": {"type": "MultiPoint", "coordinates": [[3., 4.], [5., 6., 7.]]},
+                        "properties": {"value": 2.5, "tags": ["x"]},
+                    },
+                    {
+                        "type": "Feature",
+                        "geometry": {"type": "Point", "coordinates": [50., 50.]},
+                        "properties": null,
+                    },
+                ],
+            }),
+        );
+
+        let metadata = read_geojson_metadata(&path).unwrap();
+        assert_eq!(metadata.collection_type, VectorCollectionType::MultiPoint);
+        assert_eq!(
+            metadata.schema,
+            AttributeSchema::new()
+                .with_column("name", AttributeType::Text)
+                .unwrap()
+                .with_column("tags", AttributeType::Text)
+                .unwrap()
+                .with_column("valid", AttributeType::Bool)
+                .unwrap()
+                .with_column("value", AttributeType::Float)
+                .unwrap()
+        );
+        assert_eq!(
+            metadata.bbox,
+            Some(BoundingBox2D::new(c(1., 2.), c(50., 50.)).unwrap())
+        );
+
+        let collection: FeatureCollection<MultiPoints> =
+            read_geojson(&path, &BoundingBox2D::new(c(0., 0.), c(10., 10.)).unwrap()).unwrap();
+        assert_eq!(collection.geometries.len(), 2);
+        assert_eq!(collection.geometries.feature(1), &[c(3., 4.), c(5., 6.)]);
+        assert_eq!(
+            collection.attribute("value"),
+            Some(&AttributeColumn::Float(vec![Some(1.), Some(2.5)]))
+        );
+        assert_eq!(
+            collection.attribute_value("name", 1),
+            Some(AttributeValue::Null)
+        );
+        assert_eq!(
+            collection.attribute_value("tags", 1),
+            Some(AttributeValue::Text("[\"x\"]".to_string()))
+        );
+
+        assert!(matches!(
+            read_geojson::<MultiPolygons>(
+                &path,
+                &BoundingBox2D::new(c(0., 0.), c(10., 10.)).unwrap()
+            ),
+            Err(GeoJsonError::GeometryTypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_mixed_types() {
+        let feature = |geometry: Value, value: Value| serde_json::json!({"type": "Feature", "geometry": geometry, "properties": {"value": value}});
+        let point = serde_json::json!({"type": "Point", "coordinates": [1., 2.]});
+        let line = serde_json::json!({"type": "LineString", "coordinates": [[1., 2.], [3., 4.]]});
+
+        let path = write_test_file(
+            "geojson_mixed_geometries.json",
+            serde_json::json!({
+                "type": "FeatureCollection",
+                "features": [feature(point.clone(), 1.into()), feature(line, 2.into())],
+            }),
+        );
+        assert!(matches!(
+            read_geojson_metadata(&path),
+            Err(GeoJsonError::MixedGeometryTypes { .. })
+        ));
+
+        let path = write_test_file(
+            "geojson_mixed_properties.json",
+            serde_json::json!({
+                "type": "FeatureCollection",
+                "features": [feature(point.clone(), 1.into()), feature(point, "one".into())],
+            }),
+        );
+        assert!(matches!(
+            read_geojson_metadata(&path),
+            Err(GeoJsonError::MixedPropertyTypes { .. })
+        ));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let square = vec![c(0., 0.), c(1., 0.), c(1., 1.), c(0., 0.)];
+        let collection = FeatureCollection::new(MultiPolygons::from_features(vec![
+            vec![vec![square.clone()]],
+            vec![
+                vec![square],
+                vec![vec![c(5., 5.), c(6., 5.), c(5., 6.), c(5., 5.)]],
+            ],
+        ]))
+        .with_attribute("id", AttributeColumn::Int(vec![Some(1), None]))
+        .unwrap();
+
+        let path = temp_path("geojson_roundtrip.json");
+        write_geojson(&path, &collection).unwrap();
+
+        let read: FeatureCollection<MultiPolygons> = read_geojson(
+            &path,
+            &BoundingBox2D::new(c(-10., -10.), c(10., 10.)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(read, collection);
+    }
+}
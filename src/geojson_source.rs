@@ -0,0 +1,236 @@
+use crate::{
+    attributes::AttributeSchema,
+    error::{Error, Result},
+    feature_collection::{
+        FeatureCollection, MultiLineStringCollection, MultiPointCollection, MultiPolygonCollection,
+        VectorCollectionType,
+    },
+    geojson::{self, GeoJsonGeometries, GeoJsonMetadata},
+    meta_raster_operator::{MetaRasterOperator, MetaVectorOperator, RasterWants},
+    primitives::SpatialReference,
+    result_descriptor::VectorResultDescriptor,
+    source::{CreateSourceOperator, OperatorParams, Query, Source, VectorSource},
+    MetaOperator,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{marker::PhantomData, path::Path};
+
+/// A GeoJsonSource produces feature collections. The dataset is the path of a GeoJSON FeatureCollection.
+#[derive(Debug, Clone)]
+pub struct GeoJsonSource<G> {
+    pub dataset: String,
+    pub data: PhantomData<G>,
+}
+
+// It is a Source producing FeatureCollection<G> -> its a VectorSource
+impl<G> Source for GeoJsonSource<G>
+where
+    G: GeoJsonGeometries,
+{
+    type Output = FeatureCollection<G>;
+    /// reads the features intersecting the query rectangle
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        geojson::read_geojson(Path::new(&self.dataset), &query.bbox).map_err(|source| {
+            Error::Dataset {
+                dataset: self.dataset.clone(),
+                source: source.into(),
+            }
+        })
+    }
+}
+
+impl<G> CreateSourceOperator<GeoJsonSourceParams> for GeoJsonSource<G> {
+    fn create(params: GeoJsonSourceParams) -> Self {
+        GeoJsonSource {
+            data: PhantomData,
+            dataset: params.dataset,
+        }
+    }
+}
+
+/// The parameters of the `MetaGeoJsonSource`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct GeoJsonSourceParams {
+    pub dataset: String,
+}
+
+impl OperatorParams for GeoJsonSourceParams {
+    /// fails for an empty dataset path
+    fn validate(&self) -> Result<()> {
+        if self.dataset.is_empty() {
+            return Err(Error::InvalidParameter {
+                name: "dataset".to_string(),
+                reason: "the path must not be empty".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Reads features and their properties from a GeoJSON file.
+///
+/// The MetaGeoJsonSource only needs the dataset. Its collection type and attributes are read from the file.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct MetaGeoJsonSource {
+    #[serde(flatten)]
+    pub params: GeoJsonSourceParams,
+}
+
+impl MetaGeoJsonSource {
+    pub fn dataset_metadata(&self) -> Result<GeoJsonMetadata> {
+        self.params.validate()?;
+        let dataset = &self.params.dataset;
+        geojson::read_geojson_metadata(Path::new(dataset)).map_err(|source| Error::Dataset {
+            dataset: dataset.clone(),
+            source: source.into(),
+        })
+    }
+
+    fn create_typed_vector_op<G>(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = FeatureCollection<G>>>>
+    where
+        G: GeoJsonGeometries + 'static,
+    {
+        self.params.validate()?;
+        Ok(Box::new(GeoJsonSource::<G>::create(self.params.clone())))
+    }
+}
+
+impl MetaOperator for MetaGeoJsonSource {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        &[] // no sources!
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &[]
+    }
+}
+
+#[typetag::serde]
+impl MetaVectorOperator for MetaGeoJsonSource {
+    fn creates_collection_type(&self) -> Result<VectorCollectionType> {
+        self.dataset_metadata()
+            .map(|metadata| metadata.collection_type)
+    }
+
+    fn attribute_schema(&self) -> Result<AttributeSchema> {
+        self.dataset_metadata().map(|metadata| metadata.schema)
+    }
+
+    /// GeoJSON coordinates are WGS 84 longitudes and latitudes
+    fn result_descriptor(&self) -> Result<VectorResultDescriptor> {
+        let metadata = self.dataset_metadata()?;
+        Ok(VectorResultDescriptor {
+            bbox: metadata.bbox,
+            spatial_reference: Some(SpatialReference::wgs84()),
+            ..VectorResultDescriptor::new(metadata.collection_type, metadata.schema)
+        })
+    }
+
+    fn create_multi_point_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiPointCollection>>> {
+        self.create_typed_vector_op()
+    }
+
+    fn create_multi_line_string_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiLineStringCollection>>> {
+        self.create_typed_vector_op()
+    }
+
+    fn create_multi_polygon_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiPolygonCollection>>> {
+        self.create_typed_vector_op()
+    }
+}
+
+crate::register_operator! {
+    MetaGeoJsonSource: Vector
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        feature_collection::VectorData, test_util::temp_path, validate_vector_graph,
+        AttributeValue, BoundingBox2D, Coordinate2D, SpatialResolution, TimeInterval,
+    };
+
+    #[test]
+    fn reads_features_in_the_query_rectangle() {
+        let path = temp_path("geojson_source_lines.json");
+        std::fs::write(
+            &path,
+            serde_json::json!({
+                "type": "FeatureCollection",
+                "features": [
+                    {
+                        "type": "Feature",
+                        "geometry": {"type": "LineString", "coordinates": [[0., 0.], [1., 1.]]},
+                        "properties": {"id": 1},
+                    },
+                    {
+                        "type": "Feature",
+                        "geometry": {"type": "LineString", "coordinates": [[10., 10.], [11., 11.]]},
+                        "properties": {"id": 2},
+                    },
+                ],
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let source: Box<dyn MetaVectorOperator> = serde_json::from_value(serde_json::json!({
+            "type": "MetaGeoJsonSource",
+            "dataset": path,
+        }))
+        .unwrap();
+        validate_vector_graph(source.as_ref()).unwrap();
+        assert_eq!(
+            source.creates_collection_type().unwrap(),
+            VectorCollectionType::MultiLineString
+        );
+        assert_eq!(
+            source.result_descriptor().unwrap().bbox,
+            Some(
+                BoundingBox2D::new(Coordinate2D::new(0., 0.), Coordinate2D::new(11., 11.)).unwrap()
+            )
+        );
+
+        let collection = source
+            .create_vector_op()
+            .unwrap()
+            .get::<MultiLineStringCollection>()
+            .unwrap()
+            .vector_query(
+                Query::new(
+                    BoundingBox2D::new(Coordinate2D::new(5., 5.), Coordinate2D::new(20., 20.))
+                        .unwrap(),
+                    TimeInterval::new(0, 1).unwrap(),
+                    SpatialResolution::new_square(1.).unwrap(),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(collection.len(), 1);
+        assert_eq!(
+            collection.attribute_value("id", 0),
+            Some(AttributeValue::Int(2))
+        );
+
+        let source = MetaGeoJsonSource {
+            params: GeoJsonSourceParams {
+                dataset: String::new(),
+            },
+        };
+        assert_eq!(
+            validate_vector_graph(&source).unwrap_err().to_string(),
+            "the operator graph is invalid: MetaGeoJsonSource: \
+             the parameter dataset is invalid: the path must not be empty"
+        );
+    }
+}
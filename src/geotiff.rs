@@ -0,0 +1,1426 @@
+use crate::{
+    primitives::{BoundingBox2D, GeoTransform, GridShape, Raster, SpatialReference},
+    raster_type::{RasterType, StaticRasterType},
+};
+use num_traits::NumCast;
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    str::FromStr,
+};
+use tiff::{
+    decoder::{ChunkType, Decoder, DecodingResult},
+    encoder::{
+        colortype::{self, ColorType},
+        compression::Deflate,
+        DirectoryEncoder, TiffEncoder, TiffKind,
+    },
+    tags::{
+        CompressionMethod, PhotometricInterpretation, PlanarConfiguration, Predictor, SampleFormat,
+        Tag,
+    },
+    TiffError,
+};
+
+/// GeoKey: projected (1) or geographic (2) model
+const GT_MODEL_TYPE_GEO_KEY: u16 = 1024;
+const MODEL_TYPE_PROJECTED: u16 = 1;
+const MODEL_TYPE_GEOGRAPHIC: u16 = 2;
+/// GeoKey: pixel is area (1) or pixel is point (2)
+const GT_RASTER_TYPE_GEO_KEY: u16 = 1025;
+const RASTER_PIXEL_IS_AREA: u16 = 1;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+/// GeoKey: EPSG code of a geographic CRS
+const GEOGRAPHIC_TYPE_GEO_KEY: u16 = 2048;
+/// GeoKey: EPSG code of a projected CRS
+const PROJECTED_CS_TYPE_GEO_KEY: u16 = 3072;
+/// GeoKey value for a CRS that has no EPSG code
+const USER_DEFINED: u16 = 32767;
+
+/// The errors that can occur while reading or writing GeoTIFF files.
+#[derive(Debug)]
+pub enum GeoTiffError {
+    Io(std::io::Error),
+    Tiff(TiffError),
+    /// the file has neither a model transformation nor a tie point with a pixel scale
+    MissingGeoReference,
+    /// the sample format and bit depth do not map to a `RasterType`
+    UnsupportedDataType {
+        sample_format: String,
+        bits_per_sample: u16,
+    },
+    /// the file stores a different type than requested
+    DataTypeMismatch {
+        expected: RasterType,
+        found: RasterType,
+    },
+    /// the file has no band with this index
+    BandOutOfRange {
+        band: usize,
+        bands: usize,
+    },
+    /// the chunks of the file are encoded in a way that cannot be decoded
+    UnsupportedEncoding(String),
+}
+
+impl fmt::Display for GeoTiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeoTiffError::Io(error) => write!(f, "io error: {}", error),
+            GeoTiffError::Tiff(error) => write!(f, "tiff error: {}", error),
+            GeoTiffError::MissingGeoReference => write!(f, "the file is not georeferenced"),
+            GeoTiffError::UnsupportedDataType {
+                sample_format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "unsupported data type: {} bit {}",
+                bits_per_sample, sample_format
+            ),
+            GeoTiffError::DataTypeMismatch { expected, found } => write!(
+                f,
+                "the file contains {:?} data but {:?} was requested",
+                found, expected
+            ),
+            GeoTiffError::BandOutOfRange { band, bands } => {
+                write!(
+                    f,
+                    "there is no band {} in a file with {} bands",
+                    band, bands
+                )
+            }
+            GeoTiffError::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported encoding: {}", encoding)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GeoTiffError {}
+
+impl From<std::io::Error> for GeoTiffError {
+    fn from(error: std::io::Error) -> Self {
+        GeoTiffError::Io(error)
+    }
+}
+
+impl From<TiffError> for GeoTiffError {
+    fn from(error: TiffError) -> Self {
+        GeoTiffError::Tiff(error)
+    }
+}
+
+/// The information in a GeoTIFF header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoTiffMetadata {
+    pub shape: GridShape,
+    pub geo_transform: GeoTransform,
+    pub raster_type: RasterType,
+    pub bands: usize,
+    pub no_data_value: Option<f64>,
+    pub spatial_reference: Option<SpatialReference>,
+}
+
+impl GeoTiffMetadata {
+    /// the area covered by the file
+    pub fn bounding_box(&self) -> BoundingBox2D {
+        self.geo_transform.grid_bounding_box(self.shape)
+    }
+}
+
+/// A pixel type that can be read from and written to GeoTIFF files.
+pub trait GeoTiffPixel: StaticRasterType + NumCast + FromStr + fmt::Display {
+    type ColorType: ColorType<Inner = Self>;
+
+    /// unpacks decoded samples if they have the type `Self`
+    fn from_decoding_result(result: DecodingResult) -> Option<Vec<Self>>;
+
+    /// a sample from its `size_of::<Self>()` bytes
+    fn from_bytes(bytes: &[u8], little_endian: bool) -> Self;
+}
+
+macro_rules! impl_geo_tiff_pixel {
+    ($type:ty, $color_type:ty, $variant:ident) => {
+        impl GeoTiffPixel for $type {
+            type ColorType = $color_type;
+
+            fn from_decoding_result(result: DecodingResult) -> Option<Vec<Self>> {
+                match result {
+                    DecodingResult::$variant(data) => Some(data),
+                    _ => None,
+                }
+            }
+
+            fn from_bytes(bytes: &[u8], little_endian: bool) -> Self {
+                let bytes = bytes.try_into().expect("the bytes of one sample");
+                if little_endian {
+                    <$type>::from_le_bytes(bytes)
+                } else {
+                    <$type>::from_be_bytes(bytes)
+                }
+            }
+        }
+    };
+}
+
+impl_geo_tiff_pixel!(u8, colortype::Gray8, U8);
+impl_geo_tiff_pixel!(u16, colortype::Gray16, U16);
+impl_geo_tiff_pixel!(u32, colortype::Gray32, U32);
+impl_geo_tiff_pixel!(u64, colortype::Gray64, U64);
+impl_geo_tiff_pixel!(i16, colortype::GrayI16, I16);
+impl_geo_tiff_pixel!(i32, colortype::GrayI32, I32);
+impl_geo_tiff_pixel!(i64, colortype::GrayI64, I64);
+impl_geo_tiff_pixel!(f32, colortype::Gray32Float, F32);
+impl_geo_tiff_pixel!(f64, colortype::Gray64Float, F64);
+
+fn open(path: &Path) -> Result<Decoder<BufReader<File>>, GeoTiffError> {
+    Ok(Decoder::new(BufReader::new(File::open(path)?))?)
+}
+
+/// Reads the header of a GeoTIFF file.
+pub fn read_metadata(path: &Path) -> Result<GeoTiffMetadata, GeoTiffError> {
+    read_metadata_from_decoder(&mut open(path)?)
+}
+
+fn read_metadata_from_decoder<R>(decoder: &mut Decoder<R>) -> Result<GeoTiffMetadata, GeoTiffError>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    let (width, height) = decoder.dimensions()?;
+    let shape = GridShape::new(width as usize, height as usize);
+
+    let bands = decoder
+        .find_tag_unsigned::<u16>(Tag::SamplesPerPixel)?
+        .unwrap_or(1) as usize;
+
+    Ok(GeoTiffMetadata {
+        shape,
+        geo_transform: read_geo_transform(decoder)?,
+        raster_type: read_raster_type(decoder)?,
+        bands,
+        no_data_value: read_no_data_value(decoder)?,
+        spatial_reference: read_spatial_reference(decoder)?,
+    })
+}
+
+fn read_spatial_reference<R>(
+    decoder: &mut Decoder<R>,
+) -> Result<Option<SpatialReference>, GeoTiffError>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    let geo_keys = read_geo_keys(decoder)?;
+
+    let code_key = match geo_keys.get(&GT_MODEL_TYPE_GEO_KEY) {
+        Some(&MODEL_TYPE_GEOGRAPHIC) => GEOGRAPHIC_TYPE_GEO_KEY,
+        _ => PROJECTED_CS_TYPE_GEO_KEY,
+    };
+
+    Ok(geo_keys
+        .get(&code_key)
+        .filter(|&&code| code != USER_DEFINED)
+        .map(|&code| SpatialReference::epsg(code.into())))
+}
+
+fn read_raster_type<R>(decoder: &mut Decoder<R>) -> Result<RasterType, GeoTiffError>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    let bits_per_sample = decoder
+        .find_tag_unsigned_vec::<u16>(Tag::BitsPerSample)?
+        .and_then(|bits| bits.first().copied())
+        .unwrap_or(1);
+    let sample_format = decoder
+        .find_tag_unsigned_vec::<u16>(Tag::SampleFormat)?
+        .and_then(|formats| formats.first().copied())
+        .map_or(SampleFormat::Uint, SampleFormat::from_u16_exhaustive);
+
+    match (sample_format, bits_per_sample) {
+        (SampleFormat::Uint, 8) => Ok(RasterType::U8),
+        (SampleFormat::Uint, 16) => Ok(RasterType::U16),
+        (SampleFormat::Uint, 32) => Ok(RasterType::U32),
+        (SampleFormat::Uint, 64) => Ok(RasterType::U64),
+        (SampleFormat::Int, 16) => Ok(RasterType::I16),
+        (SampleFormat::Int, 32) => Ok(RasterType::I32),
+        (SampleFormat::Int, 64) => Ok(RasterType::I64),
+        (SampleFormat::IEEEFP, 32) => Ok(RasterType::F32),
+        (SampleFormat::IEEEFP, 64) => Ok(RasterType::F64),
+        (sample_format, bits_per_sample) => Err(GeoTiffError::UnsupportedDataType {
+            sample_format: format!("{:?}", sample_format),
+            bits_per_sample,
+        }),
+    }
+}
+
+/// The GeoKeys with a short value, i.e. the ones stored directly in the key directory
+fn read_geo_keys<R>(decoder: &mut Decoder<R>) -> Result<HashMap<u16, u16>, GeoTiffError>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    let directory = match decoder.find_tag_unsigned_vec::<u16>(Tag::GeoKeyDirectoryTag)? {
+        Some(directory) => directory,
+        None => return Ok(HashMap::new()),
+    };
+
+    // header: version, revision, minor revision, number of keys followed by (id, location, count, value) entries
+    Ok(directory
+        .get(4..)
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .filter(|entry| entry[1] == 0)
+        .map(|entry| (entry[0], entry[3]))
+        .collect())
+}
+
+fn read_geo_transform<R>(decoder: &mut Decoder<R>) -> Result<GeoTransform, GeoTiffError>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    let mut geo_transform = if let Some(matrix) = decoder
+        .find_tag(Tag::ModelTransformationTag)?
+        .map(|value| value.into_f64_vec())
+        .transpose()?
+        .filter(|matrix| matrix.len() == 16)
+    {
+        GeoTransform::from_gdal([
+            matrix[3], matrix[0], matrix[1], matrix[7], matrix[4], matrix[5],
+        ])
+    } else {
+        let scale = decoder
+            .find_tag(Tag::ModelPixelScaleTag)?
+            .map(|value| value.into_f64_vec())
+            .transpose()?
+            .filter(|scale| scale.len() >= 2)
+            .ok_or(GeoTiffError::MissingGeoReference)?;
+        let tie_point = decoder
+            .find_tag(Tag::ModelTiepointTag)?
+            .map(|value| value.into_f64_vec())
+            .transpose()?
+            .filter(|tie_point| tie_point.len() >= 6)
+            .ok_or(GeoTiffError::MissingGeoReference)?;
+
+        GeoTransform::from_gdal([
+            tie_point[3] - tie_point[0] * scale[0],
+            scale[0],
+            0.,
+            tie_point[4] + tie_point[1] * scale[1],
+            0.,
+            -scale[1],
+        ])
+    };
+
+    // the model coordinates refer to the pixel center, move the origin to the corner of the first pixel
+    if read_geo_keys(decoder)?.get(&GT_RASTER_TYPE_GEO_KEY) == Some(&RASTER_PIXEL_IS_POINT) {
+        let corner = geo_transform.pixel_to_coordinate(-0.5, -0.5);
+        geo_transform.origin_x = corner.x;
+        geo_transform.origin_y = corner.y;
+    }
+
+    Ok(geo_transform)
+}
+
+fn read_no_data_value<R>(decoder: &mut Decoder<R>) -> Result<Option<f64>, GeoTiffError>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    Ok(decoder
+        .find_tag(Tag::GdalNodata)?
+        .map(|value| value.into_string())
+        .transpose()?
+        .and_then(|no_data| no_data.trim_end_matches('\0').trim().parse().ok()))
+}
+
+/// The pixel window `[start, end)` of a grid that intersects a bounding box
+fn pixel_window(
+    geo_transform: &GeoTransform,
+    shape: GridShape,
+    bbox: &BoundingBox2D,
+) -> Option<((usize, usize), (usize, usize))> {
+    // ignore intersections that are only due to floating point inaccuracies
+    const EPSILON: f64 = 1e-9;
+
+    let corners = [
+        bbox.lower_left(),
+        bbox.upper_right(),
+        crate::Coordinate2D::new(bbox.lower_left().x, bbox.upper_right().y),
+        crate::Coordinate2D::new(bbox.upper_right().x, bbox.lower_left().y),
+    ];
+
+    let mut min = (f64::INFINITY, f64::INFINITY);
+    let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for corner in &corners {
+        let (column, row) = geo_transform.coordinate_to_pixel(*corner)?;
+        min = (min.0.min(column), min.1.min(row));
+        max = (max.0.max(column), max.1.max(row));
+    }
+
+    let start = (
+        (min.0 + EPSILON).floor().max(0.) as usize,
+        (min.1 + EPSILON).floor().max(0.) as usize,
+    );
+    let end = (
+        ((max.0 - EPSILON).ceil().max(0.) as usize).min(shape.width),
+        ((max.1 - EPSILON).ceil().max(0.) as usize).min(shape.height),
+    );
+
+    if start.0 < end.0 && start.1 < end.1 {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+/// Reads the pixels of all bands of a GeoTIFF file that intersect `bbox`.
+///
+/// The result has the native resolution of the file. If the file does not intersect `bbox`, the result is empty.
+pub fn read_window<T>(path: &Path, bbox: &BoundingBox2D) -> Result<Raster<T>, GeoTiffError>
+where
+    T: GeoTiffPixel,
+{
+    read_bands(path, bbox, None)
+}
+
+/// Reads the pixels of the given bands, or all bands if `bands` is `None`, that intersect `bbox`.
+pub fn read_bands<T>(
+    path: &Path,
+    bbox: &BoundingBox2D,
+    bands: Option<&[usize]>,
+) -> Result<Raster<T>, GeoTiffError>
+where
+    T: GeoTiffPixel,
+{
+    let mut decoder = open(path)?;
+    let metadata = read_metadata_from_decoder(&mut decoder)?;
+
+    if metadata.raster_type != T::TYPE {
+        return Err(GeoTiffError::DataTypeMismatch {
+            expected: T::TYPE,
+            found: metadata.raster_type,
+        });
+    }
+
+    let all_bands: Vec<usize> = (0..metadata.bands).collect();
+    let bands = bands.unwrap_or(&all_bands);
+    if let Some(&band) = bands.iter().find(|&&band| band >= metadata.bands) {
+        return Err(GeoTiffError::BandOutOfRange {
+            band,
+            bands: metadata.bands,
+        });
+    }
+
+    let no_data_value = metadata.no_data_value.and_then(NumCast::from);
+
+    let ((column_start, row_start), (column_end, row_end)) =
+        match pixel_window(&metadata.geo_transform, metadata.shape, bbox) {
+            Some(window) => window,
+            None => {
+                return Ok(Raster::new_with_bands(
+                    GridShape::new(0, 0),
+                    bands.len(),
+                    metadata.geo_transform,
+                    Vec::new(),
+                    no_data_value,
+                ))
+            }
+        };
+
+    let window_shape = GridShape::new(column_end - column_start, row_end - row_start);
+    let window_pixels = window_shape.number_of_pixels();
+    let mut data = vec![T::default(); window_pixels * bands.len()];
+
+    let planar = decoder
+        .find_tag_unsigned::<u16>(Tag::PlanarConfiguration)?
+        .and_then(PlanarConfiguration::from_u16)
+        == Some(PlanarConfiguration::Planar);
+
+    let sample_stride = if planar { 1 } else { metadata.bands };
+    let mut chunk_reader = ChunkReader::new(path, &mut decoder, metadata.bands, sample_stride)?;
+    let (chunk_width, chunk_height) = decoder.chunk_dimensions();
+    let (chunk_width, chunk_height) = (chunk_width as usize, chunk_height as usize);
+    let chunks_across = metadata.shape.width.div_ceil(chunk_width);
+    let chunks_down = metadata.shape.height.div_ceil(chunk_height);
+
+    for chunk_y in (row_start / chunk_height)..row_end.div_ceil(chunk_height) {
+        for chunk_x in (column_start / chunk_width)..column_end.div_ceil(chunk_width) {
+            let chunk_in_band = chunk_y * chunks_across + chunk_x;
+            // with a planar configuration each band is stored in separate chunks, otherwise samples are interleaved.
+            // Each read is a chunk index and the (output band, sample offset) pairs it contains.
+            let reads: Vec<(usize, Vec<(usize, usize)>)> = if planar {
+                bands
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &band)| {
+                        (
+                            band * chunks_across * chunks_down + chunk_in_band,
+                            vec![(i, 0)],
+                        )
+                    })
+                    .collect()
+            } else {
+                vec![(chunk_in_band, bands.iter().copied().enumerate().collect())]
+            };
+
+            for (chunk_index, samples) in reads {
+                let (data_width, data_height) = decoder.chunk_data_dimensions(chunk_index as u32);
+                // the tiff crate crops the chunks to the image, the raw chunks of tiles are padded to the full tile size
+                let (chunk, row_length): (Vec<T>, usize) = match &mut chunk_reader {
+                    ChunkReader::Tiff => (
+                        T::from_decoding_result(decoder.read_chunk(chunk_index as u32)?).ok_or(
+                            GeoTiffError::DataTypeMismatch {
+                                expected: T::TYPE,
+                                found: metadata.raster_type,
+                            },
+                        )?,
+                        data_width as usize,
+                    ),
+                    ChunkReader::Raw(reader) => {
+                        let rows = if reader.tiled {
+                            chunk_height
+                        } else {
+                            data_height as usize
+                        };
+                        (
+                            reader.read_chunk(chunk_index, chunk_width * sample_stride, rows)?,
+                            chunk_width,
+                        )
+                    }
+                };
+
+                let chunk_column = chunk_x * chunk_width;
+                let chunk_row = chunk_y * chunk_height;
+                let columns =
+                    column_start.max(chunk_column)..column_end.min(chunk_column + chunk_width);
+
+                for &(output_band, sample_offset) in &samples {
+                    let band_data =
+                        &mut data[output_band * window_pixels..(output_band + 1) * window_pixels];
+                    for row in row_start.max(chunk_row)..row_end.min(chunk_row + chunk_height) {
+                        for column in columns.clone() {
+                            let chunk_pixel =
+                                (row - chunk_row) * row_length + column - chunk_column;
+                            band_data
+                                [(row - row_start) * window_shape.width + column - column_start] =
+                                chunk[chunk_pixel * sample_stride + sample_offset];
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let origin = metadata
+        .geo_transform
+        .pixel_to_coordinate(column_start as f64, row_start as f64);
+    let geo_transform = GeoTransform {
+        origin_x: origin.x,
+        origin_y: origin.y,
+        ..metadata.geo_transform
+    };
+
+    Ok(Raster::new_with_bands(
+        window_shape,
+        bands.len(),
+        geo_transform,
+        data,
+        no_data_value,
+    ))
+}
+
+/// Reads the chunks of a file. The tiff crate decodes a single gray band or three to four RGB(A) bands.
+/// The chunks of all other files, e.g. with two bands or extra samples, are decoded by `RawChunkReader`.
+enum ChunkReader {
+    Tiff,
+    Raw(RawChunkReader),
+}
+
+impl ChunkReader {
+    fn new<R>(
+        path: &Path,
+        decoder: &mut Decoder<R>,
+        bands: usize,
+        samples_per_pixel: usize,
+    ) -> Result<Self, GeoTiffError>
+    where
+        R: Read + Seek,
+    {
+        let photometric = decoder
+            .find_tag_unsigned::<u16>(Tag::PhotometricInterpretation)?
+            .and_then(PhotometricInterpretation::from_u16);
+        let tiff_decodes = matches!(
+            (photometric, bands),
+            (
+                Some(
+                    PhotometricInterpretation::BlackIsZero | PhotometricInterpretation::WhiteIsZero
+                ),
+                1
+            ) | (Some(PhotometricInterpretation::RGB), 3 | 4)
+        );
+        if tiff_decodes {
+            Ok(ChunkReader::Tiff)
+        } else {
+            Ok(ChunkReader::Raw(RawChunkReader::new(
+                path,
+                decoder,
+                samples_per_pixel,
+            )?))
+        }
+    }
+}
+
+/// Decodes uncompressed, deflate, LZW and PackBits compressed chunks with horizontal or floating point prediction.
+struct RawChunkReader {
+    file: BufReader<File>,
+    little_endian: bool,
+    offsets: Vec<u64>,
+    byte_counts: Vec<u64>,
+    compression: CompressionMethod,
+    /// tiles are stored padded to the full tile size, strips only hold the rows of the image
+    tiled: bool,
+    predictor: Predictor,
+    /// the interleaved samples of a pixel in a chunk, one with a planar configuration
+    samples_per_pixel: usize,
+}
+
+impl RawChunkReader {
+    fn new<R>(
+        path: &Path,
+        decoder: &mut Decoder<R>,
+        samples_per_pixel: usize,
+    ) -> Result<Self, GeoTiffError>
+    where
+        R: Read + Seek,
+    {
+        let tiled = decoder.get_chunk_type() == ChunkType::Tile;
+        let (offsets_tag, byte_counts_tag) = if tiled {
+            (Tag::TileOffsets, Tag::TileByteCounts)
+        } else {
+            (Tag::StripOffsets, Tag::StripByteCounts)
+        };
+        let compression = decoder.find_tag_unsigned::<u16>(Tag::Compression)?.map_or(
+            CompressionMethod::None,
+            CompressionMethod::from_u16_exhaustive,
+        );
+        let predictor = decoder
+            .find_tag_unsigned::<u16>(Tag::Predictor)?
+            .unwrap_or(1);
+        let predictor = Predictor::from_u16(predictor)
+            .ok_or_else(|| GeoTiffError::UnsupportedEncoding(format!("predictor {}", predictor)))?;
+
+        let mut file = BufReader::new(File::open(path)?);
+        let mut byte_order = [0; 2];
+        file.read_exact(&mut byte_order)?;
+
+        Ok(RawChunkReader {
+            file,
+            little_endian: &byte_order == b"II",
+            offsets: decoder.get_tag_u64_vec(offsets_tag)?,
+            byte_counts: decoder.get_tag_u64_vec(byte_counts_tag)?,
+            compression,
+            tiled,
+            predictor,
+            samples_per_pixel,
+        })
+    }
+
+    /// the first `rows` rows of `row_samples` samples of a chunk
+    fn read_chunk<T: GeoTiffPixel>(
+        &mut self,
+        chunk_index: usize,
+        row_samples: usize,
+        rows: usize,
+    ) -> Result<Vec<T>, GeoTiffError> {
+        let missing_chunk = || {
+            GeoTiffError::Tiff(TiffError::FormatError(
+                tiff::TiffFormatError::InconsistentSizesEncountered,
+            ))
+        };
+        let offset = *self.offsets.get(chunk_index).ok_or_else(missing_chunk)?;
+        let byte_count = *self
+            .byte_counts
+            .get(chunk_index)
+            .ok_or_else(missing_chunk)?;
+
+        let mut compressed = vec![0; byte_count as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut compressed)?;
+
+        let mut bytes = match self.compression {
+            CompressionMethod::None => compressed,
+            CompressionMethod::Deflate | CompressionMethod::OldDeflate => {
+                let mut bytes = Vec::new();
+                flate2::read::ZlibDecoder::new(&compressed[..]).read_to_end(&mut bytes)?;
+                bytes
+            }
+            CompressionMethod::LZW => {
+                weezl::decode::Decoder::with_tiff_size_switch(weezl::BitOrder::Msb, 8)
+                    .decode(&compressed)
+                    .map_err(|error| GeoTiffError::Io(std::io::Error::other(error)))?
+            }
+            CompressionMethod::PackBits => unpack_bits(&compressed),
+            compression => {
+                return Err(GeoTiffError::UnsupportedEncoding(format!(
+                    "{:?} compression for this band layout",
+                    compression
+                )))
+            }
+        };
+
+        let sample_size = std::mem::size_of::<T>();
+        let row_bytes = row_samples * sample_size;
+        if bytes.len() < rows * row_bytes {
+            return Err(missing_chunk());
+        }
+        bytes.truncate(rows * row_bytes);
+        for row in bytes.chunks_exact_mut(row_bytes) {
+            match self.predictor {
+                Predictor::Horizontal => undo_horizontal_differencing(
+                    row,
+                    sample_size,
+                    self.samples_per_pixel,
+                    self.little_endian,
+                ),
+                Predictor::FloatingPoint => undo_floating_point_differencing(
+                    row,
+                    sample_size,
+                    self.samples_per_pixel,
+                    self.little_endian,
+                ),
+                _ => {}
+            }
+        }
+        Ok(bytes
+            .chunks_exact(sample_size)
+            .map(|sample| T::from_bytes(sample, self.little_endian))
+            .collect())
+    }
+}
+
+/// Predictor 2 stores each sample of a row as the difference to the same sample of the previous pixel.
+fn undo_horizontal_differencing(
+    row: &mut [u8],
+    sample_size: usize,
+    samples_per_pixel: usize,
+    little_endian: bool,
+) {
+    // the byte positions of a sample from the least to the most significant byte
+    let significance = |byte: usize| {
+        if little_endian {
+            byte
+        } else {
+            sample_size - 1 - byte
+        }
+    };
+    let stride = samples_per_pixel * sample_size;
+    for sample in (stride..row.len()).step_by(sample_size) {
+        let mut carry = 0;
+        for byte in 0..sample_size {
+            let index = sample + significance(byte);
+            let sum = row[index] as u16 + row[index - stride] as u16 + carry;
+            row[index] = sum as u8;
+            carry = sum >> 8;
+        }
+    }
+}
+
+/// Predictor 3 splits the samples of a row into planes of their bytes, from the most significant one,
+/// and stores each byte as the difference to the byte of the previous pixel.
+fn undo_floating_point_differencing(
+    row: &mut [u8],
+    sample_size: usize,
+    samples_per_pixel: usize,
+    little_endian: bool,
+) {
+    for index in samples_per_pixel..row.len() {
+        row[index] = row[index].wrapping_add(row[index - samples_per_pixel]);
+    }
+    let samples = row.len() / sample_size;
+    let planes = row.to_vec();
+    for sample in 0..samples {
+        for byte in 0..sample_size {
+            let position = if little_endian {
+                sample_size - 1 - byte
+            } else {
+                byte
+            };
+            row[sample * sample_size + position] = planes[byte * samples + sample];
+        }
+    }
+}
+
+/// decodes PackBits, a run-length encoding of bytes
+fn unpack_bits(mut packed: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    while let Some((&header, rest)) = packed.split_first() {
+        let header = header as i8;
+        packed = match header {
+            // a run of one byte
+            -127..=-1 => {
+                let (&byte, rest) = match rest.split_first() {
+                    Some(run) => run,
+                    None => break,
+                };
+                bytes.extend(std::iter::repeat_n(byte, (1 - header as isize) as usize));
+                rest
+            }
+            // literal bytes
+            0..=127 => {
+                let (literal, rest) = rest.split_at((header as usize + 1).min(rest.len()));
+                bytes.extend_from_slice(literal);
+                rest
+            }
+            // no-op
+            -128 => rest,
+        };
+    }
+    bytes
+}
+
+/// Writes a raster as a GeoTIFF file. Single band rasters are deflate compressed and stripped.
+/// Rasters with several bands are stored uncompressed with interleaved samples, as RGB(A) if they have three or four bands.
+pub fn write_geotiff<T>(
+    path: &Path,
+    raster: &Raster<T>,
+    spatial_reference: Option<SpatialReference>,
+) -> Result<(), GeoTiffError>
+where
+    T: GeoTiffPixel,
+    [T]: tiff::encoder::TiffValue,
+{
+    let mut encoder = TiffEncoder::new(BufWriter::new(File::create(path)?))?;
+    if raster.bands != 1 {
+        return write_interleaved_bands(&mut encoder, raster, spatial_reference);
+    }
+
+    let mut image = encoder.new_image_with_compression::<T::ColorType, _>(
+        raster.width() as u32,
+        raster.height() as u32,
+        Deflate::default(),
+    )?;
+    write_geo_tags(image.encoder(), raster, spatial_reference)?;
+    image.write_data(&raster.data)?;
+
+    Ok(())
+}
+
+/// writes the pixels of all bands as one strip, pixel by pixel
+fn write_interleaved_bands<W, T>(
+    encoder: &mut TiffEncoder<W>,
+    raster: &Raster<T>,
+    spatial_reference: Option<SpatialReference>,
+) -> Result<(), GeoTiffError>
+where
+    W: Write + Seek,
+    T: GeoTiffPixel,
+    [T]: tiff::encoder::TiffValue,
+{
+    let pixels = raster.shape.number_of_pixels();
+    let interleaved: Vec<T> = (0..pixels)
+        .flat_map(|pixel| (0..raster.bands).map(move |band| raster.data[band * pixels + pixel]))
+        .collect();
+
+    let bits = (std::mem::size_of::<T>() * 8) as u16;
+    let sample_format = if T::TYPE.is_float() {
+        SampleFormat::IEEEFP
+    } else if T::TYPE.is_signed() {
+        SampleFormat::Int
+    } else {
+        SampleFormat::Uint
+    };
+    // RGB(A) is the only multi-band interpretation many readers, e.g. the tiff crate, can decode
+    let (photometric, color_bands) = match raster.bands {
+        3 | 4 => (PhotometricInterpretation::RGB, 3),
+        _ => (PhotometricInterpretation::BlackIsZero, 1),
+    };
+
+    let mut directory = encoder.new_directory()?;
+    let offset = directory.write_data(&interleaved[..])?;
+    directory.write_tag(Tag::ImageWidth, raster.width() as u32)?;
+    directory.write_tag(Tag::ImageLength, raster.height() as u32)?;
+    directory.write_tag(Tag::BitsPerSample, &vec![bits; raster.bands][..])?;
+    directory.write_tag(
+        Tag::SampleFormat,
+        &vec![sample_format.to_u16(); raster.bands][..],
+    )?;
+    directory.write_tag(Tag::Compression, CompressionMethod::None.to_u16())?;
+    directory.write_tag(Tag::PhotometricInterpretation, photometric.to_u16())?;
+    directory.write_tag(Tag::SamplesPerPixel, raster.bands as u16)?;
+    directory.write_tag(
+        Tag::PlanarConfiguration,
+        PlanarConfiguration::Chunky.to_u16(),
+    )?;
+    if raster.bands > color_bands {
+        // unspecified extra samples
+        directory.write_tag(
+            Tag::ExtraSamples,
+            &vec![0u16; raster.bands - color_bands][..],
+        )?;
+    }
+    directory.write_tag(Tag::RowsPerStrip, raster.height() as u32)?;
+    directory.write_tag(Tag::StripOffsets, offset as u32)?;
+    directory.write_tag(
+        Tag::StripByteCounts,
+        (interleaved.len() * std::mem::size_of::<T>()) as u32,
+    )?;
+    write_geo_tags(&mut directory, raster, spatial_reference)?;
+    directory.finish()?;
+
+    Ok(())
+}
+
+fn write_geo_tags<W, K, T>(
+    directory: &mut DirectoryEncoder<W, K>,
+    raster: &Raster<T>,
+    spatial_reference: Option<SpatialReference>,
+) -> Result<(), GeoTiffError>
+where
+    W: Write + Seek,
+    K: TiffKind,
+    T: GeoTiffPixel,
+{
+    let geo_transform = raster.geo_transform;
+    if geo_transform.x_rotation == 0. && geo_transform.y_rotation == 0. {
+        directory.write_tag(
+            Tag::ModelPixelScaleTag,
+            &[geo_transform.x_pixel_size, -geo_transform.y_pixel_size, 0.][..],
+        )?;
+        directory.write_tag(
+            Tag::ModelTiepointTag,
+            &[
+                0.,
+                0.,
+                0.,
+                geo_transform.origin_x,
+                geo_transform.origin_y,
+                0.,
+            ][..],
+        )?;
+    } else {
+        directory.write_tag(
+            Tag::ModelTransformationTag,
+            &[
+                geo_transform.x_pixel_size,
+                geo_transform.x_rotation,
+                0.,
+                geo_transform.origin_x,
+                geo_transform.y_rotation,
+                geo_transform.y_pixel_size,
+                0.,
+                geo_transform.origin_y,
+                0.,
+                0.,
+                0.,
+                0.,
+                0.,
+                0.,
+                0.,
+                1.,
+            ][..],
+        )?;
+    }
+
+    let mut geo_keys = vec![(GT_RASTER_TYPE_GEO_KEY, RASTER_PIXEL_IS_AREA)];
+    if let Some(spatial_reference) = spatial_reference {
+        let code = u16::try_from(spatial_reference.epsg_code).unwrap_or(USER_DEFINED);
+        if spatial_reference.is_geographic() {
+            geo_keys.push((GT_MODEL_TYPE_GEO_KEY, MODEL_TYPE_GEOGRAPHIC));
+            geo_keys.push((GEOGRAPHIC_TYPE_GEO_KEY, code));
+        } else {
+            geo_keys.push((GT_MODEL_TYPE_GEO_KEY, MODEL_TYPE_PROJECTED));
+            geo_keys.push((PROJECTED_CS_TYPE_GEO_KEY, code));
+        }
+    }
+    // the keys must be sorted by their id
+    geo_keys.sort_unstable();
+
+    let mut geo_key_directory = vec![1, 1, 0, geo_keys.len() as u16];
+    for (key, value) in geo_keys {
+        geo_key_directory.extend_from_slice(&[key, 0, 1, value]);
+    }
+    directory.write_tag(Tag::GeoKeyDirectoryTag, &geo_key_directory[..])?;
+    if let Some(no_data_value) = raster.no_data_value {
+        directory.write_tag(Tag::GdalNodata, no_data_value.to_string().as_str())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::temp_path, Coordinate2D, SpatialResolution};
+    use std::io::{Seek, Write};
+    use tiff::encoder::compression::Lzw;
+
+    fn bbox(x_min: f64, y_min: f64, x_max: f64, y_max: f64) -> BoundingBox2D {
+        BoundingBox2D::new(
+            Coordinate2D::new(x_min, y_min),
+            Coordinate2D::new(x_max, y_max),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn write_and_read_window() {
+        let path = temp_path("geotiff_write_and_read_window.tif");
+        let raster = Raster::new(
+            GridShape::new(4, 3),
+            GeoTransform::new_north_up(
+                Coordinate2D::new(10., 20.),
+                SpatialResolution::new(2., 1.).unwrap(),
+            ),
+            (0..12).map(|v| v as f32).collect(),
+            Some(f32::NAN),
+        );
+        write_geotiff(&path, &raster, Some(SpatialReference::wgs84())).unwrap();
+
+        let metadata = read_metadata(&path).unwrap();
+        assert_eq!(metadata.shape, raster.shape);
+        assert_eq!(metadata.bands, 1);
+        assert_eq!(metadata.spatial_reference, Some(SpatialReference::wgs84()));
+        assert_eq!(metadata.bounding_box(), bbox(10., 17., 18., 20.));
+        assert!(metadata.geo_transform.approx_eq(&raster.geo_transform));
+        assert_eq!(metadata.raster_type, RasterType::F32);
+        assert!(metadata.no_data_value.unwrap().is_nan());
+
+        let all: Raster<f32> = read_window(&path, &bbox(0., 0., 100., 100.)).unwrap();
+        assert_eq!(all.data, raster.data);
+
+        // the second and third column of the last two rows
+        let window: Raster<f32> = read_window(&path, &bbox(12.5, 17., 15., 18.5)).unwrap();
+        assert_eq!(window.shape, GridShape::new(2, 2));
+        assert_eq!(window.data, vec![5., 6., 9., 10.]);
+        assert!(window.geo_transform.approx_eq(&GeoTransform::new_north_up(
+            Coordinate2D::new(12., 19.),
+            SpatialResolution::new(2., 1.).unwrap()
+        )));
+
+        let outside: Raster<f32> = read_window(&path, &bbox(50., 50., 60., 60.)).unwrap();
+        assert_eq!(outside.shape, GridShape::new(0, 0));
+
+        assert!(matches!(
+            read_window::<u8>(&path, &bbox(0., 0., 100., 100.)),
+            Err(GeoTiffError::DataTypeMismatch {
+                expected: RasterType::U8,
+                found: RasterType::F32
+            })
+        ));
+    }
+
+    #[test]
+    fn write_and_read_bands() {
+        let path = temp_path("geotiff_write_and_read_bands.tif");
+        // four bands of 3x2 pixels, each pixel is 10 * band + index
+        let raster = Raster::new_with_bands(
+            GridShape::new(3, 2),
+            4,
+            GeoTransform::new_north_up(
+                Coordinate2D::new(0., 2.),
+                SpatialResolution::new_square(1.).unwrap(),
+            ),
+            (0..4)
+                .flat_map(|band| (0..6).map(move |pixel| (10 * band + pixel) as i16))
+                .collect(),
+            Some(-1),
+        );
+        write_geotiff(&path, &raster, None).unwrap();
+
+        let metadata = read_metadata(&path).unwrap();
+        assert_eq!(metadata.bands, 4);
+        assert_eq!(metadata.raster_type, RasterType::I16);
+        assert_eq!(metadata.no_data_value, Some(-1.));
+
+        let all: Raster<i16> = read_window(&path, &bbox(0., 0., 3., 2.)).unwrap();
+        assert_eq!(all, raster);
+
+        // the last two columns of the bands 3 and 1
+        let window: Raster<i16> = read_bands(&path, &bbox(1., 0., 3., 2.), Some(&[3, 1])).unwrap();
+        assert_eq!(window.bands, 2);
+        assert_eq!(window.band(0), Some(&[31, 32, 34, 35][..]));
+        assert_eq!(window.band(1), Some(&[11, 12, 14, 15][..]));
+
+        assert!(matches!(
+            read_bands::<i16>(&path, &bbox(0., 0., 3., 2.), Some(&[4])),
+            Err(GeoTiffError::BandOutOfRange { band: 4, bands: 4 })
+        ));
+    }
+
+    #[test]
+    fn write_and_read_band_counts_the_tiff_crate_cannot_decode() {
+        for bands in [2usize, 6] {
+            let path = temp_path(&format!("geotiff_write_and_read_{}_bands.tif", bands));
+            let raster = Raster::new_with_bands(
+                GridShape::new(3, 2),
+                bands,
+                GeoTransform::new_north_up(
+                    Coordinate2D::new(0., 2.),
+                    SpatialResolution::new_square(1.).unwrap(),
+                ),
+                (0..bands * 6).map(|value| value as f32 * 1.5).collect(),
+                Some(-1.),
+            );
+            write_geotiff(&path, &raster, None).unwrap();
+
+            assert_eq!(read_metadata(&path).unwrap().bands, bands);
+            let all: Raster<f32> = read_window(&path, &bbox(0., 0., 3., 2.)).unwrap();
+            assert_eq!(all, raster);
+
+            let window: Raster<f32> =
+                read_bands(&path, &bbox(1., 0., 3., 1.), Some(&[bands - 1])).unwrap();
+            let last = (bands - 1) * 6;
+            assert_eq!(
+                window.data,
+                vec![(last + 4) as f32 * 1.5, (last + 5) as f32 * 1.5]
+            );
+        }
+    }
+
+    #[test]
+    fn read_planar_deflate_compressed_bands() {
+        let path = temp_path("geotiff_read_planar_deflate_bands.tif");
+        let (width, height) = (4u32, 3u32);
+
+        // one strip per band, assembled by hand because the encoder only writes chunky samples
+        let mut file = File::create(&path).unwrap();
+        let mut encoder = TiffEncoder::new(&mut file).unwrap();
+        let mut directory = encoder.new_directory().unwrap();
+        let mut offsets = Vec::new();
+        let mut byte_counts = Vec::new();
+        for band in 0..2u16 {
+            let samples: Vec<u8> = (0..width * height)
+                .flat_map(|pixel| (band * 100 + pixel as u16).to_le_bytes())
+                .collect();
+            let mut compressor =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            compressor.write_all(&samples).unwrap();
+            let compressed = compressor.finish().unwrap();
+            offsets.push(directory.write_data(&compressed[..]).unwrap() as u32);
+            byte_counts.push(compressed.len() as u32);
+        }
+        directory.write_tag(Tag::ImageWidth, width).unwrap();
+        directory.write_tag(Tag::ImageLength, height).unwrap();
+        directory
+            .write_tag(Tag::BitsPerSample, &[16u16, 16][..])
+            .unwrap();
+        directory
+            .write_tag(Tag::SampleFormat, &[1u16, 1][..])
+            .unwrap();
+        directory
+            .write_tag(Tag::Compression, CompressionMethod::Deflate.to_u16())
+            .unwrap();
+        directory
+            .write_tag(Tag::PhotometricInterpretation, 1u16)
+            .unwrap();
+        directory.write_tag(Tag::SamplesPerPixel, 2u16).unwrap();
+        directory
+            .write_tag(
+                Tag::PlanarConfiguration,
+                PlanarConfiguration::Planar.to_u16(),
+            )
+            .unwrap();
+        directory.write_tag(Tag::ExtraSamples, 0u16).unwrap();
+        directory.write_tag(Tag::RowsPerStrip, height).unwrap();
+        directory
+            .write_tag(Tag::StripOffsets, &offsets[..])
+            .unwrap();
+        directory
+            .write_tag(Tag::StripByteCounts, &byte_counts[..])
+            .unwrap();
+        directory
+            .write_tag(Tag::ModelPixelScaleTag, &[1., 1., 0.][..])
+            .unwrap();
+        directory
+            .write_tag(Tag::ModelTiepointTag, &[0., 0., 0., 0., 3., 0.][..])
+            .unwrap();
+        directory.finish().unwrap();
+        file.flush().unwrap();
+
+        let window: Raster<u16> = read_window(&path, &bbox(1., 0., 3., 1.)).unwrap();
+        assert_eq!(window.bands, 2);
+        assert_eq!(window.band(0), Some(&[9, 10][..]));
+        assert_eq!(window.band(1), Some(&[109, 110][..]));
+    }
+
+    #[test]
+    fn unpacks_pack_bits() {
+        // a literal of three bytes, a run of four and a no-op
+        let packed = [2, 1, 2, 3, -3i8 as u8, 7, -128i8 as u8];
+        assert_eq!(unpack_bits(&packed), vec![1, 2, 3, 7, 7, 7, 7]);
+    }
+
+    #[test]
+    fn read_lzw_compressed_strips() {
+        let path = temp_path("geotiff_read_lzw_compressed_strips.tif");
+        let data: Vec<u16> = (0..40 * 30).map(|v| v as u16).collect();
+
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut image = encoder
+            .new_image_with_compression::<colortype::Gray16, _>(40, 30, Lzw)
+            .unwrap();
+        image.rows_per_strip(7).unwrap();
+        image
+            .encoder()
+            .write_tag(Tag::ModelPixelScaleTag, &[1., 1., 0.][..])
+            .unwrap();
+        image
+            .encoder()
+            .write_tag(Tag::ModelTiepointTag, &[0., 0., 0., 0., 30., 0.][..])
+            .unwrap();
+        image.encoder().write_tag(Tag::GdalNodata, "0").unwrap();
+        image.write_data(&data).unwrap();
+
+        let window: Raster<u16> = read_window(&path, &bbox(5., 5., 8., 25.)).unwrap();
+        assert_eq!(window.shape, GridShape::new(3, 20));
+        assert_eq!(window.no_data_value, Some(0));
+        for row in 0..20 {
+            for column in 0..3 {
+                assert_eq!(
+                    window.pixel(column, row),
+                    Some(&(((row + 5) * 40 + column + 5) as u16))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn read_tiles() {
+        let path = temp_path("geotiff_read_tiles.tif");
+        let (width, height, tile_size) = (20u32, 18u32, 16u32);
+
+        // the encoder only writes strips, so the tiles are assembled by hand
+        let mut file = File::create(&path).unwrap();
+        let mut encoder = TiffEncoder::new(&mut file).unwrap();
+        let mut directory = encoder.new_directory().unwrap();
+        let mut offsets = Vec::new();
+        let mut byte_counts = Vec::new();
+        for tile_y in 0..2 {
+            for tile_x in 0..2 {
+                let mut tile = vec![0i16; (tile_size * tile_size) as usize];
+                for row in 0..tile_size {
+                    for column in 0..tile_size {
+                        let (x, y) = (tile_x * tile_size + column, tile_y * tile_size + row);
+                        if x < width && y < height {
+                            tile[(row * tile_size + column) as usize] = (y * width + x) as i16;
+                        }
+                    }
+                }
+                offsets.push(directory.write_data(&tile[..]).unwrap() as u32);
+                byte_counts.push(tile.len() as u32 * 2);
+            }
+        }
+        directory.write_tag(Tag::ImageWidth, width).unwrap();
+        directory.write_tag(Tag::ImageLength, height).unwrap();
+        directory.write_tag(Tag::BitsPerSample, 16u16).unwrap();
+        directory.write_tag(Tag::SampleFormat, 2u16).unwrap();
+        directory.write_tag(Tag::Compression, 1u16).unwrap();
+        directory
+            .write_tag(Tag::PhotometricInterpretation, 1u16)
+            .unwrap();
+        directory.write_tag(Tag::SamplesPerPixel, 1u16).unwrap();
+        directory.write_tag(Tag::TileWidth, tile_size).unwrap();
+        directory.write_tag(Tag::TileLength, tile_size).unwrap();
+        directory.write_tag(Tag::TileOffsets, &offsets[..]).unwrap();
+        directory
+            .write_tag(Tag::TileByteCounts, &byte_counts[..])
+            .unwrap();
+        directory
+            .write_tag(
+                Tag::ModelTransformationTag,
+                &[
+                    1., 0., 0., 100., 0., -1., 0., 50., 0., 0., 0., 0., 0., 0., 0., 1.,
+                ][..],
+            )
+            .unwrap();
+        directory.finish().unwrap();
+        file.flush().unwrap();
+        file.rewind().unwrap();
+
+        // a window spanning all four tiles
+        let window: Raster<i16> = read_window(&path, &bbox(114., 33., 118., 36.)).unwrap();
+        assert_eq!(window.shape, GridShape::new(4, 3));
+        assert_eq!(window.pixel(0, 0), Some(&(14 * 20 + 14)));
+        assert_eq!(window.pixel(3, 2), Some(&(16 * 20 + 17)));
+        assert_eq!(
+            window.geo_transform.pixel_to_coordinate(0., 0.),
+            Coordinate2D::new(114., 36.)
+        );
+    }
+
+    #[test]
+    fn read_tiles_with_two_bands() {
+        let path = temp_path("geotiff_read_tiles_with_two_bands.tif");
+        let (width, height, tile_size) = (20u32, 18u32, 16u32);
+
+        // the tiles of the right and bottom edges are padded to the full tile size
+        let mut file = File::create(&path).unwrap();
+        let mut encoder = TiffEncoder::new(&mut file).unwrap();
+        let mut directory = encoder.new_directory().unwrap();
+        let mut offsets = Vec::new();
+        let mut byte_counts = Vec::new();
+        for tile_y in 0..2 {
+            for tile_x in 0..2 {
+                let mut tile = vec![0u16; (tile_size * tile_size * 2) as usize];
+                for row in 0..tile_size {
+                    for column in 0..tile_size {
+                        let (x, y) = (tile_x * tile_size + column, tile_y * tile_size + row);
+                        if x < width && y < height {
+                            let pixel = ((row * tile_size + column) * 2) as usize;
+                            tile[pixel] = (y * width + x) as u16;
+                            tile[pixel + 1] = (1000 + y * width + x) as u16;
+                        }
+                    }
+                }
+                offsets.push(directory.write_data(&tile[..]).unwrap() as u32);
+                byte_counts.push(tile.len() as u32 * 2);
+            }
+        }
+        directory.write_tag(Tag::ImageWidth, width).unwrap();
+        directory.write_tag(Tag::ImageLength, height).unwrap();
+        directory
+            .write_tag(Tag::BitsPerSample, &[16u16, 16][..])
+            .unwrap();
+        directory
+            .write_tag(Tag::SampleFormat, &[1u16, 1][..])
+            .unwrap();
+        directory.write_tag(Tag::Compression, 1u16).unwrap();
+        directory
+            .write_tag(Tag::PhotometricInterpretation, 1u16)
+            .unwrap();
+        directory.write_tag(Tag::SamplesPerPixel, 2u16).unwrap();
+        directory.write_tag(Tag::ExtraSamples, 0u16).unwrap();
+        directory.write_tag(Tag::TileWidth, tile_size).unwrap();
+        directory.write_tag(Tag::TileLength, tile_size).unwrap();
+        directory.write_tag(Tag::TileOffsets, &offsets[..]).unwrap();
+        directory
+            .write_tag(Tag::TileByteCounts, &byte_counts[..])
+            .unwrap();
+        directory
+            .write_tag(Tag::ModelPixelScaleTag, &[1., 1., 0.][..])
+            .unwrap();
+        directory
+            .write_tag(Tag::ModelTiepointTag, &[0., 0., 0., 0., 18., 0.][..])
+            .unwrap();
+        directory.finish().unwrap();
+        file.flush().unwrap();
+
+        // a window spanning all four tiles up to the lower right corner of the image
+        let window: Raster<u16> = read_window(&path, &bbox(14., 0., 20., 4.)).unwrap();
+        assert_eq!(window.shape, GridShape::new(6, 4));
+        let expected: Vec<u16> = (14..18)
+            .flat_map(|y| (14..20).map(move |x| y * 20 + x))
+            .collect();
+        assert_eq!(window.band(0), Some(&expected[..]));
+        let expected: Vec<u16> = expected.iter().map(|value| value + 1000).collect();
+        assert_eq!(window.band(1), Some(&expected[..]));
+    }
+
+    #[test]
+    fn read_predicted_bands() {
+        let (width, height) = (4usize, 3usize);
+        // two interleaved bands in one deflate compressed strip, as GDAL writes them with `PREDICTOR=2` or `3`
+        let write = |name: &str, samples: Vec<u8>, bits: u16, format: SampleFormat, predictor| {
+            let path = temp_path(name);
+            let mut file = File::create(&path).unwrap();
+            let mut encoder = TiffEncoder::new(&mut file).unwrap();
+            let mut directory = encoder.new_directory().unwrap();
+            let mut compressor =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            compressor.write_all(&samples).unwrap();
+            let compressed = compressor.finish().unwrap();
+            let offset = directory.write_data(&compressed[..]).unwrap() as u32;
+            directory.write_tag(Tag::ImageWidth, width as u32).unwrap();
+            directory
+                .write_tag(Tag::ImageLength, height as u32)
+                .unwrap();
+            directory
+                .write_tag(Tag::BitsPerSample, &[bits, bits][..])
+                .unwrap();
+            directory
+                .write_tag(Tag::SampleFormat, &[format.to_u16(); 2][..])
+                .unwrap();
+            directory
+                .write_tag(Tag::Compression, CompressionMethod::Deflate.to_u16())
+                .unwrap();
+            directory
+                .write_tag(Tag::PhotometricInterpretation, 1u16)
+                .unwrap();
+            directory.write_tag(Tag::SamplesPerPixel, 2u16).unwrap();
+            directory.write_tag(Tag::ExtraSamples, 0u16).unwrap();
+            directory.write_tag(Tag::Predictor, predictor).unwrap();
+            directory
+                .write_tag(Tag::RowsPerStrip, height as u32)
+                .unwrap();
+            directory.write_tag(Tag::StripOffsets, offset).unwrap();
+            directory
+                .write_tag(Tag::StripByteCounts, compressed.len() as u32)
+                .unwrap();
+            directory
+                .write_tag(Tag::ModelPixelScaleTag, &[1., 1., 0.][..])
+                .unwrap();
+            directory
+                .write_tag(Tag::ModelTiepointTag, &[0., 0., 0., 0., 3., 0.][..])
+                .unwrap();
+            directory.finish().unwrap();
+            path
+        };
+        // the value of a band at a pixel, rows go down
+        let value = |band: usize, x: usize, y: usize| (band * 1000 + y * 100 + x * x * 7) as u16;
+        let window = bbox(0., 0., 4., 3.);
+
+        let mut differences = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                for band in 0..2 {
+                    let previous = if x == 0 { 0 } else { value(band, x - 1, y) };
+                    differences.extend(value(band, x, y).wrapping_sub(previous).to_ne_bytes());
+                }
+            }
+        }
+        let path = write(
+            "geotiff_read_horizontal_predictor.tif",
+            differences,
+            16,
+            SampleFormat::Uint,
+            2u16,
+        );
+        let raster: Raster<u16> = read_window(&path, &window).unwrap();
+        for band in 0..2 {
+            let expected: Vec<u16> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| value(band, x, y)))
+                .collect();
+            assert_eq!(raster.band(band), Some(&expected[..]));
+        }
+
+        let mut differences = Vec::new();
+        for y in 0..height {
+            let row: Vec<f32> = (0..width)
+                .flat_map(|x| (0..2).map(move |band| value(band, x, y) as f32 / 3.))
+                .collect();
+            // the byte planes of the row, from the most significant byte
+            let mut planes: Vec<u8> = (0..4)
+                .flat_map(|byte| row.iter().map(move |sample| sample.to_be_bytes()[byte]))
+                .collect();
+            for index in (2..planes.len()).rev() {
+                planes[index] = planes[index].wrapping_sub(planes[index - 2]);
+            }
+            differences.extend(planes);
+        }
+        let path = write(
+            "geotiff_read_floating_point_predictor.tif",
+            differences,
+            32,
+            SampleFormat::IEEEFP,
+            3u16,
+        );
+        let raster: Raster<f32> = read_window(&path, &window).unwrap();
+        for band in 0..2 {
+            let expected: Vec<f32> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| value(band, x, y) as f32 / 3.))
+                .collect();
+            assert_eq!(raster.band(band), Some(&expected[..]));
+        }
+    }
+}
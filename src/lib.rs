@@ -1,23 +1,65 @@
+mod add_constant_operator;
 mod add_raster_operator;
+mod attributes;
+mod band_select_operator;
+mod band_stack_operator;
+mod binary_raster_operator;
+mod cast_operator;
+mod convert_raster_operator;
+mod csv_source;
+mod error;
+mod expression;
+mod expression_operator;
+mod feature_collection;
 mod gdal_source;
+mod geojson;
+mod geojson_source;
+mod geotiff;
 mod meta_raster_operator;
 mod my_vector_source;
 mod noop_operator;
 mod operator_ext;
+mod optimizer;
 mod plus_one_operator;
 mod primitives;
 mod raster_type;
 mod raster_vector_operator;
+mod registry;
+mod result_descriptor;
 mod source;
+#[cfg(test)]
+mod test_util;
+mod validation;
+mod workflow;
 
+pub use add_constant_operator::*;
 pub use add_raster_operator::*;
+pub use attributes::*;
+pub use band_select_operator::*;
+pub use band_stack_operator::*;
+pub use binary_raster_operator::*;
+pub use cast_operator::*;
+pub use convert_raster_operator::*;
+pub use csv_source::*;
+pub use error::*;
+pub use expression::*;
+pub use expression_operator::*;
+pub use feature_collection::*;
 pub use gdal_source::*;
+pub use geojson::*;
+pub use geojson_source::*;
+pub use geotiff::*;
 pub use meta_raster_operator::*;
 pub use my_vector_source::*;
 pub use noop_operator::*;
 pub use operator_ext::*;
+pub use optimizer::*;
 pub use plus_one_operator::*;
 pub use primitives::*;
 pub use raster_type::*;
 pub use raster_vector_operator::*;
+pub use registry::*;
+pub use result_descriptor::*;
 pub use source::*;
+pub use validation::*;
+pub use workflow::*;
@@ -1,4 +1,532 @@
+use clap::{Parser, ValueEnum};
+use engine_x::{
+    dispatch_raster_types, optimize_raster_graph, optimize_vector_graph, optimize_workflow,
+    write_geojson, write_geotiff, AttributeValue, BoundingBox2D, BoxedVectorOperatorInstance,
+    Coordinate2D, FeatureCollection, GeoJsonGeometries, MetaRasterOperator, MetaVectorOperator,
+    MultiLineStrings, MultiPoints, MultiPolygons, Query, RasterSource, SpatialReference,
+    SpatialResolution, TimeInterval, VectorSource, Workflow as NodeWorkflow, WorkflowNodeKind,
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Runs a workflow and writes its result to a file.
+#[derive(Parser, Debug)]
+#[command(name = "mybin")]
+struct Args {
+    /// the JSON file of a MetaRasterOperator or MetaVectorOperator graph or of a workflow with `nodes`
+    workflow: PathBuf,
+
+    /// the file to write the result to
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// the query rectangle as `min_x,min_y,max_x,max_y`
+    #[arg(long, value_parser = parse_bbox)]
+    bbox: BoundingBox2D,
+
+    /// the query time as `start,end` in milliseconds or a single instant
+    #[arg(long, value_parser = parse_time, default_value = "0")]
+    time: TimeInterval,
+
+    /// the query resolution as `size` or `x_size,y_size`
+    #[arg(long, value_parser = parse_resolution, default_value = "1")]
+    resolution: SpatialResolution,
+
+    /// the output format. Derived from the extension of the output file if not given.
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// the EPSG code written to raster results
+    #[arg(long)]
+    epsg: Option<u32>,
+
+    /// removes and fuses redundant operators before running the workflow
+    #[arg(long)]
+    optimize: bool,
+
+    /// prints the workflow before and after the optimization and the applied rewrites to stderr. Implies `--optimize`.
+    #[arg(long)]
+    explain: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    GeoTiff,
+    GeoJson,
+    Csv,
+}
+
+impl OutputFormat {
+    fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "tif" | "tiff" => Some(OutputFormat::GeoTiff),
+            "geojson" | "json" => Some(OutputFormat::GeoJson),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// The deserialized workflow, either a single operator graph or a workflow with shared nodes.
+enum Workflow {
+    Graph(Graph),
+    Nodes(NodeWorkflow),
+}
+
+/// An operator graph. Its kind is determined by the type of the root or output operator.
+enum Graph {
+    Raster(Box<dyn MetaRasterOperator>),
+    Vector(Box<dyn MetaVectorOperator>),
+}
+
+impl Workflow {
+    /// the graph that produces the result
+    fn into_graph(self) -> Result<Graph, Box<dyn Error>> {
+        Ok(match self {
+            Workflow::Graph(graph) => graph,
+            Workflow::Nodes(workflow) => match workflow.output().operator.kind() {
+                WorkflowNodeKind::Raster => Graph::Raster(Box::new(workflow.raster_output()?)),
+                WorkflowNodeKind::Vector => Graph::Vector(Box::new(workflow.vector_output()?)),
+            },
+        })
+    }
+}
+
 fn main() {
+    let args = Args::parse();
+
+    if let Err(error) = run(&args) {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let mut workflow = load_workflow(&args.workflow)?;
+    if args.optimize || args.explain {
+        workflow = optimize(workflow, args.explain)?;
+    }
+    let query = Query::new(args.bbox, args.time, args.resolution)?;
+
+    let format = args
+        .format
+        .or_else(|| OutputFormat::from_extension(&args.output))
+        .ok_or("cannot derive the output format from the file name, use --format")?;
+
+    match workflow.into_graph()? {
+        Graph::Raster(operator) => {
+            if format != OutputFormat::GeoTiff {
+                return Err(format!("raster results cannot be written as {:?}", format).into());
+            }
+            let spatial_reference = args.epsg.map(SpatialReference::epsg);
+            let instance = operator.create_raster_op()?;
+            dispatch_raster_types!(match_instance: instance, source => {
+                let raster = source.raster_query(query)?;
+                write_geotiff(&args.output, &raster, spatial_reference)?
+            });
+        }
+        Graph::Vector(operator) => match operator.create_vector_op()? {
+            BoxedVectorOperatorInstance::MultiPoints(source) => {
+                write_collection(&args.output, format, &source.vector_query(query)?)?
+            }
+            BoxedVectorOperatorInstance::MultiLineStrings(source) => {
+                write_collection(&args.output, format, &source.vector_query(query)?)?
+            }
+            BoxedVectorOperatorInstance::MultiPolygons(source) => {
+                write_collection(&args.output, format, &source.vector_query(query)?)?
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn load_workflow(path: &Path) -> Result<Workflow, Box<dyn Error>> {
+    let json = fs::read_to_string(path)
+        .map_err(|error| format!("cannot read workflow {}: {}", path.display(), error))?;
+
+    let value: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|error| format!("{} is not valid JSON: {}", path.display(), error))?;
+    if value.get("nodes").is_some() {
+        let workflow: NodeWorkflow = serde_json::from_value(value)
+            .map_err(|error| format!("{} is not a valid workflow: {}", path.display(), error))?;
+        return Ok(Workflow::Nodes(workflow));
+    }
+
+    let raster_error = match serde_json::from_str::<Box<dyn MetaRasterOperator>>(&json) {
+        Ok(operator) => return Ok(Workflow::Graph(Graph::Raster(operator))),
+        Err(error) => error,
+    };
+    match serde_json::from_str::<Box<dyn MetaVectorOperator>>(&json) {
+        Ok(operator) => Ok(Workflow::Graph(Graph::Vector(operator))),
+        Err(vector_error) => Err(format!(
+            "{} is neither a raster workflow ({}) nor a vector workflow ({})",
+            path.display(),
+            raster_error,
+            vector_error
+        )
+        .into()),
+    }
+}
+
+/// Optimizes the graph or every node of the workflow. The explanation goes to stderr, so it does not mix with results.
+fn optimize(workflow: Workflow, explain: bool) -> Result<Workflow, Box<dyn Error>> {
+    let (explanation, workflow) = match workflow {
+        Workflow::Graph(Graph::Raster(operator)) => {
+            let optimized = optimize_raster_graph(operator)?;
+            let explanation = optimized.explain();
+            (
+                explanation,
+                Workflow::Graph(Graph::Raster(optimized.operator)),
+            )
+        }
+        Workflow::Graph(Graph::Vector(operator)) => {
+            let optimized = optimize_vector_graph(operator)?;
+            let explanation = optimized.explain();
+            (
+                explanation,
+                Workflow::Graph(Graph::Vector(optimized.operator)),
+            )
+        }
+        Workflow::Nodes(workflow) => {
+            let optimized = optimize_workflow(workflow)?;
+            (optimized.explain(), Workflow::Nodes(*optimized.operator))
+        }
+    };
+    if explain {
+        eprintln!("{}", explanation);
+    }
+    Ok(workflow)
+}
+
+/// The geometries of a collection in the output formats of vector results.
+trait WriteGeometries: GeoJsonGeometries {
+    /// the WKT of a feature
+    fn wkt(&self, feature: usize) -> String;
+}
+
+fn wkt_coordinates(coordinates: &[Coordinate2D]) -> String {
+    let coordinates: Vec<String> = coordinates
+        .iter()
+        .map(|c| format!("{} {}", c.x, c.y))
+        .collect();
+    format!("({})", coordinates.join(", "))
+}
+
+impl WriteGeometries for MultiPoints {
+    fn wkt(&self, feature: usize) -> String {
+        let points: Vec<String> = self
+            .feature(feature)
+            .iter()
+            .map(|&point| wkt_coordinates(&[point]))
+            .collect();
+        format!("MULTIPOINT ({})", points.join(", "))
+    }
+}
+
+impl WriteGeometries for MultiLineStrings {
+    fn wkt(&self, feature: usize) -> String {
+        let lines: Vec<String> = self
+            .feature(feature)
+            .into_iter()
+            .map(wkt_coordinates)
+            .collect();
+        format!("MULTILINESTRING ({})", lines.join(", "))
+    }
+}
+
+impl WriteGeometries for MultiPolygons {
+    fn wkt(&self, feature: usize) -> String {
+        let polygons: Vec<String> = self
+            .feature(feature)
+            .into_iter()
+            .map(|rings| {
+                let rings: Vec<String> = rings.into_iter().map(wkt_coordinates).collect();
+                format!("({})", rings.join(", "))
+            })
+            .collect();
+        format!("MULTIPOLYGON ({})", polygons.join(", "))
+    }
+}
+
+fn write_collection<G: WriteGeometries>(
+    path: &Path,
+    format: OutputFormat,
+    collection: &FeatureCollection<G>,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::GeoJson => write_geojson(path, collection)?,
+        OutputFormat::Csv => write_csv(path, collection)?,
+        OutputFormat::GeoTiff => return Err("vector results cannot be written as GeoTiff".into()),
+    }
+    Ok(())
+}
+
+/// writes the WKT of the features followed by their attributes
+fn write_csv<G: WriteGeometries>(
+    path: &Path,
+    collection: &FeatureCollection<G>,
+) -> Result<(), Box<dyn Error>> {
+    let geometries = &collection.geometries;
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut header = vec![csv_field("wkt")];
+    header.extend(collection.attributes().map(|(name, _)| csv_field(name)));
+    writeln!(writer, "{}", header.join(","))?;
+    for feature in 0..geometries.len() {
+        let mut fields = vec![csv_field(&geometries.wkt(feature))];
+        fields.extend(collection.attributes().map(|(_, column)| {
+            match column.get(feature).unwrap_or(AttributeValue::Null) {
+                AttributeValue::Text(text) => csv_field(&text),
+                value => value.to_string(),
+            }
+        }));
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// quotes a CSV field and escapes its quotes
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn parse_numbers(value: &str, expected: &[usize]) -> Result<Vec<f64>, String> {
+    let numbers = value
+        .split(',')
+        .map(|number| number.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| format!("{}: {}", value, error))?;
+    if numbers.iter().any(|number| !number.is_finite()) {
+        return Err(format!("{}: all numbers must be finite", value));
+    }
+    if !expected.contains(&numbers.len()) {
+        return Err(format!(
+            "expected {:?} comma separated numbers but got {}",
+            expected,
+            numbers.len()
+        ));
+    }
+    Ok(numbers)
+}
+
+fn parse_bbox(value: &str) -> Result<BoundingBox2D, String> {
+    let numbers = parse_numbers(value, &[4])?;
+    BoundingBox2D::new(
+        Coordinate2D::new(numbers[0], numbers[1]),
+        Coordinate2D::new(numbers[2], numbers[3]),
+    )
+    .map_err(|error| error.to_string())
+}
+
+fn parse_time(value: &str) -> Result<TimeInterval, String> {
+    let times = value
+        .split(',')
+        .map(|time| time.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| format!("{}: {}", value, error))?;
+    match times.as_slice() {
+        [instant] => Ok(TimeInterval::new_instant(*instant)),
+        [start, end] => TimeInterval::new(*start, *end).map_err(|error| error.to_string()),
+        _ => Err("expected `start,end` or a single instant".into()),
+    }
+}
+
+fn parse_resolution(value: &str) -> Result<SpatialResolution, String> {
+    let numbers = parse_numbers(value, &[1, 2])?;
+    let resolution = match numbers.as_slice() {
+        [size] => SpatialResolution::new_square(*size),
+        [x, y] => SpatialResolution::new(*x, *y),
+        _ => unreachable!("the number of values is checked"),
+    };
+    resolution.map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine_x::{read_window, GeoTransform, GridShape, Raster};
+
+    /// writes a 2x2 u8 raster into `directory`
+    fn write_input(directory: &Path) -> PathBuf {
+        let path = directory.join("input.tif");
+        let raster = Raster::new(
+            GridShape::new(2, 2),
+            GeoTransform::new_north_up(
+                Coordinate2D::new(0., 2.),
+                SpatialResolution::new_square(1.).unwrap(),
+            ),
+            vec![1u8, 2, 3, 4],
+            None,
+        );
+        write_geotiff(&path, &raster, None).unwrap();
+        path
+    }
+
+    fn args(workflow: &Path, output: &Path) -> Args {
+        Args::try_parse_from([
+            "mybin",
+            workflow.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+            "--bbox",
+            "0,0,2,2",
+            "--epsg",
+            "4326",
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn runs_raster_workflows() {
+        let directory = tempfile::tempdir().unwrap();
+        let input = write_input(directory.path());
+        let workflow = directory.path().join("workflow.json");
+        fs::write(
+            &workflow,
+            serde_json::json!({
+                "type": "MetaPlusOneOperator",
+                "sources": [{"type": "MetaGdalSource", "dataset": input}],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let output = directory.path().join("output.tif");
+
+        run(&args(&workflow, &output)).unwrap();
+
+        let bbox = parse_bbox("0,0,2,2").unwrap();
+        let raster = read_window::<u8>(&output, &bbox).unwrap();
+        assert_eq!(raster.data, vec![2, 3, 4, 5]);
+    }
+
+    /// `json.json` reads the fixtures in `data`. They are 4x4 rasters at the origin (0, 4) with
+    /// a pixel size of 1 in EPSG:32632, written with `write_geotiff`. `raster_u8.tif` holds the
+    /// pixels 0 to 15 and `raster_u16.tif` the pixels 1000 to 1015, row by row.
+    #[test]
+    fn runs_the_example_workflow() {
+        let bbox = parse_bbox("0,0,4,4").unwrap();
+        let u8s = read_window::<u8>(Path::new("data/raster_u8.tif"), &bbox).unwrap();
+        assert_eq!(u8s.data, (0..16).collect::<Vec<u8>>());
+        let u16s = read_window::<u16>(Path::new("data/raster_u16.tif"), &bbox).unwrap();
+        assert_eq!(u16s.data, (1000..1016).collect::<Vec<u16>>());
+
+        let directory = tempfile::tempdir().unwrap();
+        let output = directory.path().join("output.tif");
+        run(&args(Path::new("json.json"), &output)).unwrap();
+
+        // the lower left quarter of (u16 + 1) + u8
+        let raster = read_window::<u16>(&output, &parse_bbox("0,0,2,2").unwrap()).unwrap();
+        assert_eq!(raster.data, vec![1017, 1019, 1025, 1027]);
+    }
+
+    #[test]
+    fn runs_optimized_workflows() {
+        let directory = tempfile::tempdir().unwrap();
+        let input = write_input(directory.path());
+        let workflow = directory.path().join("workflow.json");
+        fs::write(
+            &workflow,
+            serde_json::json!({
+                "type": "MetaPlusOneOperator",
+                "sources": [{
+                    "type": "MetaNoopOperator",
+                    "sources": [{
+                        "type": "MetaPlusOneOperator",
+                        "sources": [{"type": "MetaGdalSource", "dataset": input}],
+                    }],
+                }],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let output = directory.path().join("output.tif");
+        let mut args = args(&workflow, &output);
+        args.explain = true;
+
+        run(&args).unwrap();
+
+        let bbox = parse_bbox("0,0,2,2").unwrap();
+        let raster = read_window::<u8>(&output, &bbox).unwrap();
+        assert_eq!(raster.data, vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn runs_vector_workflows() {
+        let directory = tempfile::tempdir().unwrap();
+        let input = write_input(directory.path());
+        let workflow = directory.path().join("workflow.json");
+        fs::write(
+            &workflow,
+            serde_json::json!({
+                "type": "MetaRasterVectorOperator",
+                "raster_sources": [{"type": "MetaGdalSource", "dataset": input}],
+                "vector_sources": [{"type": "MetaMyVectorSourceOperator"}],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let output = directory.path().join("output.csv");
+
+        run(&args(&workflow, &output)).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "\"wkt\",\"name\",\"raster_value\"\n\"MULTIPOINT ((12 13))\",\"a\",\n"
+        );
+    }
+
+    #[test]
+    fn runs_workflows_with_shared_nodes() {
+        let directory = tempfile::tempdir().unwrap();
+        let input = write_input(directory.path());
+        let workflow = directory.path().join("workflow.json");
+        fs::write(
+            &workflow,
+            serde_json::json!({
+                "nodes": {
+                    "input": {"type": "MetaGdalSource", "dataset": input},
+                    "squared": {
+                        "type": "MetaBinaryRasterOperator",
+                        "operation": "Multiply",
+                        "sources": [
+                            {"type": "MetaRasterNode", "node": "input"},
+                            {"type": "MetaRasterNode", "node": "input"},
+                        ],
+                    },
+                },
+                "output": "squared",
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let output = directory.path().join("output.tif");
+
+        run(&args(&workflow, &output)).unwrap();
+
+        let bbox = parse_bbox("0,0,2,2").unwrap();
+        let raster = read_window::<u8>(&output, &bbox).unwrap();
+        assert_eq!(raster.data, vec![1, 4, 9, 16]);
+    }
 
-    // --------------- this is the magic dynamic stuff ----------------
+    #[test]
+    fn parses_query_arguments() {
+        assert!(parse_bbox("0,0,1").is_err());
+        assert!(parse_bbox("1,0,0,1").is_err());
+        assert!(parse_bbox("0,0,NaN,1").is_err());
+        assert_eq!(parse_time("5").unwrap(), TimeInterval::new_instant(5));
+        assert_eq!(parse_time("1,2").unwrap(), TimeInterval::new(1, 2).unwrap());
+        assert!(parse_time("2,1").is_err());
+        assert_eq!(
+            parse_resolution("0.5,2").unwrap(),
+            SpatialResolution::new(0.5, 2.).unwrap()
+        );
+        assert!(parse_resolution("0").is_err());
+    }
 }
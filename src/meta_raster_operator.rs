@@ -1,11 +1,20 @@
 use crate::{
+    attributes::AttributeSchema,
+    error::{Error, Result},
+    feature_collection::{
+        MultiLineStringCollection, MultiPointCollection, MultiPolygonCollection,
+        VectorCollectionType,
+    },
     raster_type::RasterType,
-    source::{BoxedRasterOperatorInstance, RasterSource},
-    BoxedVectorOperatorInstance, Point, VectorSource,
+    result_descriptor::{RasterResultDescriptor, VectorResultDescriptor},
+    source::BoxedRasterOperatorInstance,
+    workflow::NodeReference,
+    BoxedVectorOperatorInstance, VectorSource,
 };
+use serde::Serialize;
 
 /// An Enum to indicate what a RasterOperator produces. TODO: find out what kind of combinations we need!
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RasterCreates {
     /// Upgrades a RasterType of a selected source: U8 -> U16
     UpgradesInput(usize),
@@ -13,12 +22,40 @@ pub enum RasterCreates {
     DowngradesInput(usize),
     // The same as a specified input
     SameAsInput(usize),
+    /// The smallest type that holds the values of two inputs: U8 + I16 -> I16
+    PromotedInputs(usize, usize),
     // A concrete Type
     SecificType(RasterType),
 }
 
+impl RasterCreates {
+    /// computes the created type from the types of the inputs
+    pub fn resolve<F>(self, input_type: F) -> Result<RasterType>
+    where
+        F: Fn(usize) -> Result<RasterType>,
+    {
+        match self {
+            RasterCreates::UpgradesInput(index) => {
+                let raster_type = input_type(index)?;
+                raster_type
+                    .upgraded()
+                    .ok_or(Error::CannotUpgrade(raster_type))
+            }
+            RasterCreates::DowngradesInput(index) => {
+                let raster_type = input_type(index)?;
+                raster_type
+                    .downgraded()
+                    .ok_or(Error::CannotDowngrade(raster_type))
+            }
+            RasterCreates::SameAsInput(index) => input_type(index),
+            RasterCreates::PromotedInputs(a, b) => Ok(input_type(a)?.promoted(input_type(b)?)),
+            RasterCreates::SecificType(raster_type) => Ok(raster_type),
+        }
+    }
+}
+
 /// An Enum to indicate what a RasterOperator requires at an input.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum RasterWants {
     /// accepts any input
     Any,
@@ -33,130 +70,330 @@ pub trait MetaOperator {
     fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>];
     //fn raster_sources(&self) -> &[&dyn MetaRasterOperator];
 
+    /// get what the Operator requires at each raster input
+    fn raster_wants(&self) -> &[RasterWants];
+
+    /// get what the Operator requires at any number of raster inputs after those of `raster_wants`
+    /// or `None` if it takes no further inputs
+    fn variadic_raster_wants(&self) -> Option<RasterWants> {
+        None
+    }
+
     /// get the sources of the Operator. TODO: extra trait?
     fn vector_sources(&self) -> &[Box<dyn MetaVectorOperator>] {
         &[]
     }
+
+    /// get a raster source or an error if the operator has too few sources
+    fn raster_source(&self, index: usize) -> Result<&dyn MetaRasterOperator> {
+        self.raster_sources()
+            .get(index)
+            .map(AsRef::as_ref)
+            .ok_or(Error::MissingRasterSource { index })
+    }
+
+    /// get a vector source or an error if the operator has too few sources
+    fn vector_source(&self, index: usize) -> Result<&dyn MetaVectorOperator> {
+        self.vector_sources()
+            .get(index)
+            .map(AsRef::as_ref)
+            .ok_or(Error::MissingVectorSource { index })
+    }
+
+    /// get the type a raster source creates
+    fn raster_source_type(&self, index: usize) -> Result<RasterType> {
+        let source = self.raster_source(index)?;
+        source
+            .creates_type()
+            .map_err(|error| error.in_operator(source.typetag_name()))
+    }
+
+    /// get the number of bands of the rasters a raster source creates
+    fn raster_source_bands(&self, index: usize) -> Result<usize> {
+        let source = self.raster_source(index)?;
+        source
+            .band_count()
+            .map_err(|error| error.in_operator(source.typetag_name()))
+    }
+
+    /// get the number of bands all raster sources share or 1 without raster sources.
+    /// Operators that combine their sources band by band need the same number of bands in all of them.
+    fn common_raster_source_bands(&self) -> Result<usize> {
+        let mut bands = None;
+        for index in 0..self.raster_sources().len() {
+            let source_bands = self.raster_source_bands(index)?;
+            match bands {
+                Some(first) if first != source_bands => {
+                    return Err(Error::BandCountMismatch {
+                        first,
+                        second: source_bands,
+                    })
+                }
+                _ => bands = Some(source_bands),
+            }
+        }
+        Ok(bands.unwrap_or(1))
+    }
+
+    /// get the result descriptor of a raster source
+    fn raster_source_descriptor(&self, index: usize) -> Result<RasterResultDescriptor> {
+        let source = self.raster_source(index)?;
+        source
+            .result_descriptor()
+            .map_err(|error| error.in_operator(source.typetag_name()))
+    }
+
+    /// get the result descriptor of the first raster source with the area all raster sources cover.
+    /// Operators that combine their sources pixel by pixel need the same resolution and spatial reference in all of them.
+    fn common_raster_source_descriptor(&self) -> Result<RasterResultDescriptor> {
+        let mut descriptor = self.raster_source_descriptor(0)?;
+        for index in 1..self.raster_sources().len() {
+            let source = self.raster_source_descriptor(index)?;
+            if source.resolution != descriptor.resolution {
+                return Err(Error::ResolutionMismatch {
+                    first: descriptor.resolution,
+                    second: source.resolution,
+                });
+            }
+            if source.spatial_reference != descriptor.spatial_reference {
+                return Err(Error::SpatialReferenceMismatch {
+                    first: descriptor.spatial_reference,
+                    second: source.spatial_reference,
+                });
+            }
+            descriptor.bbox = descriptor
+                .bbox
+                .intersection(&source.bbox)
+                .ok_or(Error::DisjointExtents)?;
+        }
+        Ok(descriptor)
+    }
+
+    /// get the result descriptor of a vector source
+    fn vector_source_descriptor(&self, index: usize) -> Result<VectorResultDescriptor> {
+        let source = self.vector_source(index)?;
+        source
+            .result_descriptor()
+            .map_err(|error| error.in_operator(source.typetag_name()))
+    }
+
+    /// get the type of the collections a vector source creates
+    fn vector_source_collection_type(&self, index: usize) -> Result<VectorCollectionType> {
+        let source = self.vector_source(index)?;
+        source
+            .creates_collection_type()
+            .map_err(|error| error.in_operator(source.typetag_name()))
+    }
+
+    /// get the attribute schema of the collections a vector source creates
+    fn vector_source_schema(&self, index: usize) -> Result<AttributeSchema> {
+        let source = self.vector_source(index)?;
+        source
+            .attribute_schema()
+            .map_err(|error| error.in_operator(source.typetag_name()))
+    }
+
+    /// create the processing operator of a raster source. The graph was validated before.
+    fn create_raster_source(&self, index: usize) -> Result<BoxedRasterOperatorInstance> {
+        self.raster_source(index)?.build_raster_op()
+    }
+
+    /// create the processing operator of a vector source. The graph was validated before.
+    fn create_vector_source(&self, index: usize) -> Result<BoxedVectorOperatorInstance> {
+        self.vector_source(index)?.build_vector_op()
+    }
+
+    /// get the references to shared workflow nodes in the Operator and its sources.
+    /// The sources of the referenced nodes are not visited.
+    fn node_references(&self) -> Vec<&dyn NodeReference> {
+        let raster_references = self
+            .raster_sources()
+            .iter()
+            .flat_map(|source| source.node_references());
+        let vector_references = self
+            .vector_sources()
+            .iter()
+            .flat_map(|source| source.node_references());
+        raster_references.chain(vector_references).collect()
+    }
 }
 
 #[typetag::serde(tag = "type")]
 pub trait MetaVectorOperator: MetaOperator {
-    fn creates_collection_type(&self) -> () {
-        ()
+    /// get the type of the collections the Operator creates.
+    fn creates_collection_type(&self) -> Result<VectorCollectionType>;
+
+    /// get the attribute columns of the collections the Operator creates, without running it.
+    fn attribute_schema(&self) -> Result<AttributeSchema>;
+
+    /// describes the collections the Operator creates, without running it.
+    /// By default the Operator keeps the extent, spatial reference and time of its first vector source.
+    fn result_descriptor(&self) -> Result<VectorResultDescriptor> {
+        let mut descriptor =
+            VectorResultDescriptor::new(self.creates_collection_type()?, self.attribute_schema()?);
+        if !self.vector_sources().is_empty() {
+            let source = self.vector_source_descriptor(0)?;
+            descriptor.bbox = source.bbox;
+            descriptor.spatial_reference = source.spatial_reference;
+            descriptor.time = source.time;
+        }
+        Ok(descriptor)
     }
 
-    fn create_vector_op(&self) -> BoxedVectorOperatorInstance {
+    /// validates the graph and creates its processing operators. All violations are reported as `Error::InvalidGraph`.
+    fn create_vector_op(&self) -> Result<BoxedVectorOperatorInstance> {
+        crate::validate_vector_graph(self)?;
+        self.build_vector_op()
+    }
+
+    /// creates the processing operator for the collection type without validating the graph first.
+    /// Errors are reported with the path to the failing operator.
+    fn build_vector_op(&self) -> Result<BoxedVectorOperatorInstance> {
         println!("MetaVectorOperator: create_vector_op");
-        match self.creates_collection_type() {
-            () => BoxedVectorOperatorInstance::Points(self.create_point_op()),
-        }
+        let instance = match self.creates_collection_type() {
+            Ok(VectorCollectionType::MultiPoint) => self
+                .create_multi_point_op()
+                .map(BoxedVectorOperatorInstance::MultiPoints),
+            Ok(VectorCollectionType::MultiLineString) => self
+                .create_multi_line_string_op()
+                .map(BoxedVectorOperatorInstance::MultiLineStrings),
+            Ok(VectorCollectionType::MultiPolygon) => self
+                .create_multi_polygon_op()
+                .map(BoxedVectorOperatorInstance::MultiPolygons),
+            Err(error) => Err(error),
+        };
+        instance.map_err(|error| error.in_operator(self.typetag_name()))
+    }
+
+    fn create_multi_point_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiPointCollection>>> {
+        Err(Error::UnsupportedCollectionType(
+            VectorCollectionType::MultiPoint,
+        ))
+    }
+
+    fn create_multi_line_string_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiLineStringCollection>>> {
+        Err(Error::UnsupportedCollectionType(
+            VectorCollectionType::MultiLineString,
+        ))
     }
 
-    fn create_point_op(&self) -> Box<dyn VectorSource<VectorType = Point>>;
+    fn create_multi_polygon_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiPolygonCollection>>> {
+        Err(Error::UnsupportedCollectionType(
+            VectorCollectionType::MultiPolygon,
+        ))
+    }
 }
 
 /// The MetaRasterOperator is a trait for MetaOperators creating RasterOperators for processing Raster data
 #[typetag::serde(tag = "type")]
 pub trait MetaRasterOperator: MetaOperator {
-    /// The magic method to handle the mapping of the create type to a concrete implementation. More work required! TODO: macro?
-    fn create_raster_op(&self) -> BoxedRasterOperatorInstance {
+    /// validates the graph and creates its processing operators. All violations are reported as `Error::InvalidGraph`.
+    fn create_raster_op(&self) -> Result<BoxedRasterOperatorInstance> {
+        crate::validate_raster_graph(self)?;
+        self.build_raster_op()
+    }
+
+    /// The magic method to handle the mapping of the create type to a concrete implementation.
+    /// It does not validate the graph first. Errors are reported with the path to the failing operator.
+    fn build_raster_op(&self) -> Result<BoxedRasterOperatorInstance> {
         println!("MetaRasterOperator: create_raster_op");
-        match self.creates_type() {
-            RasterType::U8 => BoxedRasterOperatorInstance::U8(self.create_u8_raster_op()),
-            RasterType::U16 => BoxedRasterOperatorInstance::U16(self.create_u16_raster_op()),
-            RasterType::U32 => BoxedRasterOperatorInstance::U32(self.create_u32_raster_op()),
-            RasterType::U64 => BoxedRasterOperatorInstance::U64(self.create_u64_raster_op()),
-            RasterType::I16 => BoxedRasterOperatorInstance::I16(self.create_i16_raster_op()),
-            RasterType::I32 => BoxedRasterOperatorInstance::I32(self.create_i32_raster_op()),
-            RasterType::I64 => BoxedRasterOperatorInstance::I64(self.create_i64_raster_op()),
-            RasterType::F32 => BoxedRasterOperatorInstance::F32(self.create_f32_raster_op()),
-            RasterType::F64 => BoxedRasterOperatorInstance::F64(self.create_f64_raster_op()),
-        }
+        let instance = crate::dispatch_raster_types!(dispatch_create_op: self);
+        instance.map_err(|error| error.in_operator(self.typetag_name()))
     }
 
-    // there is no way to use generics for the MetaRasterOperators in combination with serialisation -_-. We need to implement the create operator methods. TODO: Macro?
-    fn create_u8_raster_op(&self) -> Box<dyn RasterSource<RasterType = u8>>;
-    fn create_u16_raster_op(&self) -> Box<dyn RasterSource<RasterType = u16>>;
-    fn create_u32_raster_op(&self) -> Box<dyn RasterSource<RasterType = u32>>;
-    fn create_u64_raster_op(&self) -> Box<dyn RasterSource<RasterType = u64>>;
-    fn create_i16_raster_op(&self) -> Box<dyn RasterSource<RasterType = i16>>;
-    fn create_i32_raster_op(&self) -> Box<dyn RasterSource<RasterType = i32>>;
-    fn create_i64_raster_op(&self) -> Box<dyn RasterSource<RasterType = i64>>;
-    fn create_f32_raster_op(&self) -> Box<dyn RasterSource<RasterType = f32>>;
-    fn create_f64_raster_op(&self) -> Box<dyn RasterSource<RasterType = f64>>;
+    // there is no way to use generics for the MetaRasterOperators in combination with serialisation -_-.
+    // Implementations generate these methods from a generic method with `dispatch_raster_types!(create_ops: method)`.
+    crate::dispatch_raster_types!(declare_create_ops);
+
+    /// describes the type the Operator creates
+    fn raster_creates(&self) -> Result<RasterCreates>;
 
     /// get the type the Operator creates.
-    fn creates_type(&self) -> RasterType;
+    fn creates_type(&self) -> Result<RasterType> {
+        self.raster_creates()?
+            .resolve(|index| self.raster_source_type(index))
+    }
+
+    /// get the number of bands of the rasters the Operator creates.
+    /// By default the Operator processes band by band and keeps the bands of its sources.
+    fn band_count(&self) -> Result<usize> {
+        self.common_raster_source_bands()
+    }
+
+    /// describes the rasters the Operator creates, without running it.
+    /// By default the Operator covers the area all its sources share and marks missing and invalid results
+    /// with the no-data value of its first source or, if it has none or the created type cannot represent it,
+    /// with the one of the created type. The processing operators prefer the same value, see `RasterResultDescriptor::no_data_value`.
+    fn result_descriptor(&self) -> Result<RasterResultDescriptor> {
+        let source = self.common_raster_source_descriptor()?;
+        let data_type = self.creates_type()?;
+        let no_data_value = match source.no_data_value {
+            Some(no_data_value) if data_type.can_represent(no_data_value) => no_data_value,
+            _ => data_type.no_data_value(),
+        };
+        Ok(RasterResultDescriptor {
+            data_type,
+            bands: self.band_count()?,
+            no_data_value: Some(no_data_value),
+            ..source
+        })
+    }
 }
 
+/// Creates the processing operators of the meta operators with one or two sources of a common type.
+/// Operators that keep the types of their sources or convert them themselves, i.e. the
+/// `MetaExpressionOperator`, `MetaCastOperator` and `MetaBandSelectOperator`, validate their
+/// params before they create their processing operator instead.
 pub mod operator_creation {
 
     use crate::{
-        BoxedRasterOperatorInstance, CreateBoxedBinaryOperatorInplace, CreateBoxedUnaryOperator,
-        RasterSource,
+        error::Result, ArithmeticPixel, BoxedRasterOperatorInstance, BoxedRasterType,
+        CreateBoxedBinaryOperatorInplace, CreateBoxedUnaryOperator, OperatorParams, RasterSource,
     };
+    use num_traits::One;
+    use std::ops::{Add, AddAssign};
 
-    pub fn create_operator_unary_raster_u8<O>(
-        source: BoxedRasterOperatorInstance,
-    ) -> Box<dyn RasterSource<RasterType = u8> + 'static>
-    where
-        O: CreateBoxedUnaryOperator<String>,
-    {
-        println!("create_operator_unary_raster_u8");
-        let s = source.get_u8().expect("not u8");
-
-        O::create_unary_boxed(s, "params".to_string())
-    }
-
-    pub fn create_operator_unary_raster_u16<O>(
+    /// creates a unary operator whose source is converted to `T`, usually its own type
+    pub fn create_operator_unary_raster<O, T, P>(
         source: BoxedRasterOperatorInstance,
-    ) -> Box<dyn RasterSource<RasterType = u16> + 'static>
+        params: P,
+    ) -> Result<Box<dyn RasterSource<RasterType = T> + 'static>>
     where
-        O: CreateBoxedUnaryOperator<String>,
+        O: CreateBoxedUnaryOperator<P>,
+        T: BoxedRasterType + Add + AddAssign + One + ArithmeticPixel,
+        P: OperatorParams,
     {
-        println!("create_operator_unary_raster_u16");
-        let s = source.get_u16().expect("not u16");
+        println!("create_operator_unary_raster");
+        params.validate()?;
+        let s = source.convert::<T>();
 
-        O::create_unary_boxed(s, "params".to_string())
-    }
-
-    pub fn create_operator_binary_raster_u8_u8<O>(
-        source_a: BoxedRasterOperatorInstance,
-        source_b: BoxedRasterOperatorInstance,
-    ) -> Box<dyn RasterSource<RasterType = u8> + 'static>
-    where
-        O: CreateBoxedBinaryOperatorInplace<String> + 'static,
-    {
-        println!("create_operator_binary_raster_u8_u8");
-        match (source_a, source_b) {
-            (BoxedRasterOperatorInstance::U8(a), BoxedRasterOperatorInstance::U8(b)) => {
-                O::create_binary_boxed(a, b, "params".to_string())
-            }
-            _ => panic!(),
-        }
+        Ok(O::create_unary_boxed(s, params))
     }
 
-    pub fn create_operator_binary_raster_u16_x_commutativ<O>(
+    /// creates a binary operator whose sources are converted to `T`, usually their promoted type
+    pub fn create_operator_binary_raster<O, T, P>(
         source_a: BoxedRasterOperatorInstance,
         source_b: BoxedRasterOperatorInstance,
-    ) -> Box<dyn RasterSource<RasterType = u16> + 'static>
+        params: P,
+    ) -> Result<Box<dyn RasterSource<RasterType = T> + 'static>>
     where
-        O: CreateBoxedBinaryOperatorInplace<String> + 'static,
+        O: CreateBoxedBinaryOperatorInplace<P> + 'static,
+        T: BoxedRasterType + Add + AddAssign + One + ArithmeticPixel,
+        P: OperatorParams,
     {
-        println!("create_operator_binary_raster_u16_x_commutativ");
-
-        match (source_a, source_b) {
-            (BoxedRasterOperatorInstance::U16(a), BoxedRasterOperatorInstance::U8(b)) => {
-                O::create_binary_boxed(a, b, "params".to_string())
-            }
-            (BoxedRasterOperatorInstance::U8(a), BoxedRasterOperatorInstance::U16(b)) => {
-                O::create_binary_boxed(b, a, "params".to_string())
-            }
-            (BoxedRasterOperatorInstance::U16(a), BoxedRasterOperatorInstance::U16(b)) => {
-                O::create_binary_boxed(a, b, "params".to_string())
-            }
-            _ => panic!(),
-        }
+        println!("create_operator_binary_raster");
+        params.validate()?;
+        Ok(O::create_binary_boxed(
+            source_a.convert::<T>(),
+            source_b.convert::<T>(),
+            params,
+        ))
     }
 }
 
@@ -164,22 +401,31 @@ pub mod operator_creation {
 mod tests {
     use super::*;
     use crate::{
-        MetaAddRasterOperator, MetaGdalSource, MetaMyVectorSourceOperator, MetaNoopOperator,
-        MetaPlusOneOperator, MetaRasterVectorOperator, Query,
+        test_util::{gdal_source, query, write_test_geotiff, write_test_geotiff_with_no_data},
+        AddRasterParams, GdalSourceParams, GridShape, MetaAddRasterOperator, MetaGdalSource,
+        MetaMyVectorSourceOperator, MetaNoopOperator, MetaPlusOneOperator,
+        MetaRasterVectorOperator, PlusOneParams, RasterVectorParams, Sampling,
     };
 
     #[test]
     fn mixed_graph() {
         // create a MetaGdalSource
-        let meta_gdal_source = MetaGdalSource {
-            raster_type: RasterType::U16,
-        };
+        let meta_gdal_source = MetaGdalSource::new(write_test_geotiff(
+            "meta_mixed_graph_u16.tif",
+            GridShape::new(2, 2),
+            vec![1u16, 2, 3, 4],
+        ));
 
         let meta_vector_source = MetaMyVectorSourceOperator {};
 
         let meta_combining_operator = MetaRasterVectorOperator {
             raster_sources: vec![Box::new(meta_gdal_source)],
             vector_sources: vec![Box::new(meta_vector_source)],
+            params: RasterVectorParams {
+                column: "raster_value".to_string(),
+                sampling: Sampling::Nearest,
+                band: 0,
+            },
         };
 
         let boxed_meta_combining_operator =
@@ -193,27 +439,27 @@ mod tests {
         let deserial: Box<dyn MetaVectorOperator> = serde_json::from_str(&dynamic_serial).unwrap();
 
         // create the processing oeprator
-        let d_op = deserial.create_vector_op();
-        match d_op {
-            BoxedVectorOperatorInstance::Points(p) => {
-                let res = p.vector_query(Query);
-                dbg!(res);
-            }
-        }
+        let d_op = deserial.create_vector_op().unwrap();
+        let p = d_op.get::<MultiPointCollection>().unwrap();
+        let res = p.vector_query(query()).unwrap();
+        dbg!(res);
     }
 
     #[test]
     fn raster_graph() {
         // create a MetaGdalSource
-        let meta_gdal_source = MetaGdalSource {
-            raster_type: RasterType::U16,
-        };
+        let meta_gdal_source = MetaGdalSource::with_params(GdalSourceParams {
+            raster_type: Some(RasterType::U16),
+            ..GdalSourceParams::new(write_test_geotiff(
+                "meta_raster_graph_u16.tif",
+                GridShape::new(2, 2),
+                vec![1u16, 2, 3, 4],
+            ))
+        });
         // put it in a box
         let meta_gdal_sourcein_a_box = Box::new(meta_gdal_source) as Box<dyn MetaRasterOperator>;
 
-        let other_meta_gdal_source = Box::new(MetaGdalSource {
-            raster_type: RasterType::U8,
-        }) as Box<dyn MetaRasterOperator>;
+        let other_meta_gdal_source = gdal_source("meta_raster_graph_u8.tif", vec![5u8, 6, 7, 8]);
 
         // wrap it with a noop operator
         let meta_gdal_source_noop = MetaNoopOperator {
@@ -231,12 +477,14 @@ mod tests {
         };
 
         let meta_gdal_source_noop_noop_noop_noop_plusone = MetaPlusOneOperator {
+            params: PlusOneParams::default(),
             sources: Vec::from([
                 Box::new(meta_gdal_source_noop_noop_noop) as Box<dyn MetaRasterOperator>
             ]),
         };
 
         let meta_gdal_source_noop_noop_noop_noop_plusone_plusother = MetaAddRasterOperator {
+            params: AddRasterParams::default(),
             sources: vec![
                 Box::new(meta_gdal_source_noop_noop_noop_noop_plusone)
                     as Box<dyn MetaRasterOperator>,
@@ -249,13 +497,15 @@ mod tests {
             Box::new(meta_gdal_source_noop_noop_noop_noop_plusone_plusother)
                 as Box<dyn MetaRasterOperator>;
         // create a BoxedRasterOperatorInstance.
-        let operator_instance = meta_gdal_source_noop_noop_noop_box.create_raster_op();
+        let operator_instance = meta_gdal_source_noop_noop_noop_box
+            .create_raster_op()
+            .unwrap();
         println!("meh");
 
         // BoxedRasterOperatorInstance is an enum. Unpack it for access to the concrete type.
-        if let BoxedRasterOperatorInstance::U8(r) = operator_instance {
+        if let BoxedRasterOperatorInstance::U16(r) = operator_instance {
             // The query will produce a concrete type!
-            let meh = r.raster_query(Query);
+            let meh = r.raster_query(query()).unwrap();
             println!("{:?}", meh);
         }
 
@@ -267,11 +517,248 @@ mod tests {
         let deserial: Box<dyn MetaRasterOperator> = serde_json::from_str(&dynamic_serial).unwrap();
 
         // create the processing oeprator
-        let d_op = deserial.create_raster_op();
+        let d_op = deserial.create_raster_op().unwrap();
         // ....
         if let BoxedRasterOperatorInstance::U16(r) = d_op {
-            let meh = r.raster_query(Query);
+            let meh = r.raster_query(query()).unwrap();
             println!("{:?}", meh);
         }
     }
+
+    #[test]
+    fn errors_contain_the_operator_path() {
+        let operator = MetaPlusOneOperator {
+            params: PlusOneParams::default(),
+            sources: vec![Box::new(MetaNoopOperator {
+                sources: vec![Box::new(MetaGdalSource::new(
+                    "does/not/exist.tif".to_string(),
+                ))],
+            })],
+        };
+
+        // validating reports the path of the graph, building the operators the path of the operators
+        let error = operator.build_raster_op().err().unwrap();
+        match &error {
+            Error::Operator { path, source } => {
+                assert_eq!(
+                    path,
+                    &["MetaPlusOneOperator", "MetaNoopOperator", "MetaGdalSource"]
+                );
+                assert!(matches!(source.as_ref(), Error::Dataset { .. }));
+            }
+            _ => panic!("expected an operator error, got {:?}", error),
+        }
+        assert!(error
+            .to_string()
+            .starts_with("MetaPlusOneOperator -> MetaNoopOperator -> MetaGdalSource: cannot read dataset does/not/exist.tif"));
+    }
+
+    #[test]
+    fn missing_sources_are_reported() {
+        let operator = MetaNoopOperator { sources: vec![] };
+
+        let error = operator.create_raster_op().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "the operator graph is invalid: MetaNoopOperator: the raster source 0 is missing"
+        );
+    }
+
+    #[test]
+    fn operators_support_every_raster_type() {
+        fn plus_one_noop(dataset: String) -> Box<dyn MetaRasterOperator> {
+            Box::new(MetaPlusOneOperator {
+                params: PlusOneParams::default(),
+                sources: vec![Box::new(MetaNoopOperator {
+                    sources: vec![Box::new(MetaGdalSource::new(dataset))],
+                })],
+            })
+        }
+
+        let operator = plus_one_noop(write_test_geotiff(
+            "meta_every_type_f32.tif",
+            GridShape::new(2, 2),
+            vec![1.5f32, 2., 3., 4.],
+        ));
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<f32>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![2.5, 3., 4., 5.]);
+
+        let operator = plus_one_noop(write_test_geotiff(
+            "meta_every_type_i64.tif",
+            GridShape::new(2, 2),
+            vec![-1i64, 2, 3, 4],
+        ));
+        let instance = operator.create_raster_op().unwrap();
+        assert_eq!(instance.raster_type(), RasterType::I64);
+        let raster = instance
+            .get::<i64>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![0, 3, 4, 5]);
+    }
+
+    #[test]
+    fn adding_rasters_of_different_types_promotes_them() {
+        let operator = MetaAddRasterOperator {
+            params: AddRasterParams::default(),
+            sources: vec![
+                gdal_source("meta_add_types_u32.tif", vec![1u32, 2, 3, 4]),
+                gdal_source("meta_add_types_i16.tif", vec![-1i16, 2, -3, 4]),
+            ],
+        };
+
+        assert_eq!(operator.creates_type().unwrap(), RasterType::I64);
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<i64>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![0, 4, 0, 8]);
+    }
+
+    #[test]
+    fn overflows_follow_the_policy() {
+        let dataset = write_test_geotiff(
+            "meta_overflow_u8.tif",
+            GridShape::new(2, 2),
+            vec![1u8, 200, 254, 255],
+        );
+        let plus_one = |overflow: &str| -> Box<dyn MetaRasterOperator> {
+            serde_json::from_value(serde_json::json!({
+                "type": "MetaPlusOneOperator",
+                "overflow": overflow,
+                "sources": [{"type": "MetaGdalSource", "dataset": dataset}],
+            }))
+            .unwrap()
+        };
+
+        let query_u8 = |operator: Box<dyn MetaRasterOperator>| {
+            operator
+                .create_raster_op()
+                .unwrap()
+                .get::<u8>()
+                .unwrap()
+                .raster_query(query())
+                .unwrap()
+        };
+        // the results carry the preferred no-data value of the descriptor unless a valid pixel takes it
+        let raster = query_u8(plus_one("Saturate"));
+        assert_eq!(raster.data, vec![2, 201, 255, 255]);
+        assert_eq!(raster.no_data_value, Some(254));
+        let raster = query_u8(plus_one("Wrap"));
+        assert_eq!(raster.data, vec![2, 201, 255, 0]);
+        assert_eq!(raster.no_data_value, Some(254));
+        // 254 + 1 is valid although it equals the default no-data value of u8, so the overflow gets another one
+        let raster = query_u8(plus_one("NoData"));
+        assert_eq!(raster.data, vec![2, 201, 255, 254]);
+        assert_eq!(raster.no_data_value, Some(254));
+        assert_eq!(raster.validity_mask(), vec![true, true, true, false]);
+
+        // the missing pixels of the source stay missing, the valid 255 stays valid
+        let dataset = write_test_geotiff_with_no_data(
+            "meta_overflow_no_data_u8.tif",
+            GridShape::new(2, 2),
+            vec![1u8, 200, 254, 255],
+            255,
+        );
+        let raster = query_u8(
+            serde_json::from_value(serde_json::json!({
+                "type": "MetaPlusOneOperator",
+                "overflow": "Saturate",
+                "sources": [{"type": "MetaGdalSource", "dataset": dataset}],
+            }))
+            .unwrap(),
+        );
+        assert_eq!(raster.data, vec![2, 201, 255, 254]);
+        assert_eq!(raster.no_data_value, Some(254));
+        assert_eq!(raster.validity_mask(), vec![true, true, true, false]);
+
+        let operator = plus_one("Promote");
+        assert_eq!(operator.creates_type().unwrap(), RasterType::U16);
+        let raster = operator
+            .create_raster_op()
+            .unwrap()
+            .get::<u16>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![2, 201, 255, 256]);
+        assert_eq!(raster.no_data_value, Some(u16::MAX));
+    }
+
+    #[test]
+    fn params_round_trip_through_workflows() {
+        let workflow = serde_json::json!({
+            "type": "MetaBinaryRasterOperator",
+            "operation": "Power",
+            "overflow": "Saturate",
+            "sources": [],
+        });
+        let operator: Box<dyn MetaRasterOperator> =
+            serde_json::from_value(workflow.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&operator).unwrap(), workflow);
+
+        let operator: Box<dyn MetaRasterOperator> = serde_json::from_value(serde_json::json!({
+            "type": "MetaBandSelectOperator",
+            "bands": [],
+            "sources": [{
+                "type": "MetaGdalSource",
+                "dataset": write_test_geotiff(
+                    "meta_params_empty_bands.tif",
+                    GridShape::new(2, 2),
+                    vec![1u8, 2, 3, 4],
+                ),
+            }],
+        }))
+        .unwrap();
+        assert_eq!(
+            crate::validate_raster_graph(operator.as_ref())
+                .unwrap_err()
+                .to_string(),
+            "the operator graph is invalid: MetaBandSelectOperator: \
+             the parameter bands is invalid: at least one band must be selected"
+        );
+        assert_eq!(
+            operator.build_raster_op().err().unwrap().to_string(),
+            "MetaBandSelectOperator: \
+             the parameter bands is invalid: at least one band must be selected"
+        );
+    }
+
+    #[test]
+    fn raster_creates_resolves_input_types() {
+        let input_type = |index| match index {
+            0 => Ok(RasterType::U8),
+            1 => Ok(RasterType::F64),
+            index => Err(Error::MissingRasterSource { index }),
+        };
+
+        assert_eq!(
+            RasterCreates::UpgradesInput(0).resolve(input_type).unwrap(),
+            RasterType::U16
+        );
+        assert_eq!(
+            RasterCreates::DowngradesInput(1)
+                .resolve(input_type)
+                .unwrap(),
+            RasterType::F32
+        );
+        assert!(matches!(
+            RasterCreates::DowngradesInput(0).resolve(input_type),
+            Err(Error::CannotDowngrade(RasterType::U8))
+        ));
+        assert!(matches!(
+            RasterCreates::SameAsInput(2).resolve(input_type),
+            Err(Error::MissingRasterSource { index: 2 })
+        ));
+    }
 }
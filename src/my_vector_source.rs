@@ -1,8 +1,15 @@
 use crate::{
-    primitives::Point,
+    attributes::{AttributeColumn, AttributeSchema, AttributeType},
+    error::Result,
+    feature_collection::{
+        FeatureCollection, MultiPointCollection, MultiPoints, VectorCollectionType,
+    },
+    primitives::{BoundingBox2D, Coordinate2D},
+    result_descriptor::VectorResultDescriptor,
     source::{CreateSourceOperator, Query, Source},
     MetaOperator, MetaVectorOperator,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 
@@ -14,11 +21,14 @@ pub struct MyVectorSource<V> {
 }
 
 /// It is a Source producing Vector data -> its a VectorSource
-impl Source for MyVectorSource<Point> {
-    type Output = Point;
-    fn query(&self, _: Query) -> Self::Output {
+impl Source for MyVectorSource<MultiPointCollection> {
+    type Output = MultiPointCollection;
+    fn query(&self, _: Query) -> Result<Self::Output> {
         println!("MyVectorSource query");
-        Point { a: 12.0, b: 13.0 }
+        FeatureCollection::new(MultiPoints::from_features(vec![vec![Coordinate2D::new(
+            12.0, 13.0,
+        )]]))
+        .with_attribute("name", AttributeColumn::Text(vec![Some("a".to_string())]))
     }
 }
 
@@ -31,13 +41,17 @@ impl<T> CreateSourceOperator<String> for MyVectorSource<T> {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Produces a fixed point for testing.
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct MetaMyVectorSourceOperator {}
 
 impl MetaOperator for MetaMyVectorSourceOperator {
     fn raster_sources(&self) -> &[Box<dyn crate::MetaRasterOperator>] {
         &[]
     }
+    fn raster_wants(&self) -> &[crate::RasterWants] {
+        &[]
+    }
     fn vector_sources(&self) -> &[Box<dyn MetaVectorOperator>] {
         &[]
     }
@@ -45,13 +59,32 @@ impl MetaOperator for MetaMyVectorSourceOperator {
 
 #[typetag::serde]
 impl MetaVectorOperator for MetaMyVectorSourceOperator {
-    fn creates_collection_type(&self) -> () {
-        ()
+    fn creates_collection_type(&self) -> Result<VectorCollectionType> {
+        Ok(VectorCollectionType::MultiPoint)
+    }
+
+    fn attribute_schema(&self) -> Result<AttributeSchema> {
+        AttributeSchema::new().with_column("name", AttributeType::Text)
     }
-    fn create_point_op(&self) -> Box<dyn crate::VectorSource<VectorType = crate::Point>> {
-        Box::new(MyVectorSource {
+
+    fn result_descriptor(&self) -> Result<VectorResultDescriptor> {
+        let point = Coordinate2D::new(12.0, 13.0);
+        Ok(VectorResultDescriptor {
+            bbox: BoundingBox2D::from_coordinates([point]),
+            ..VectorResultDescriptor::new(self.creates_collection_type()?, self.attribute_schema()?)
+        })
+    }
+
+    fn create_multi_point_op(
+        &self,
+    ) -> Result<Box<dyn crate::VectorSource<VectorType = MultiPointCollection>>> {
+        Ok(Box::new(MyVectorSource {
             dataset: "dataset".to_string(),
             data: PhantomData,
-        })
+        }))
     }
 }
+
+crate::register_operator! {
+    MetaMyVectorSourceOperator: Vector
+}
@@ -1,10 +1,12 @@
 use crate::{
-    meta_raster_operator::{MetaRasterOperator, RasterWants},
+    error::Result,
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
+    result_descriptor::RasterResultDescriptor,
     source::{CreateUnaryOperator, Query, RasterSource, Source},
-    MetaOperator, RasterType,
+    BoxedRasterType, MetaOperator,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use typetag;
 
 /// The NoOp Operator does nothing. It wraps any Operator.
 #[derive(Debug, Clone)]
@@ -18,78 +20,64 @@ where
     S: Source<Output = D>,
 {
     type Output = D;
-    fn query(&self, query: Query) -> Self::Output {
+    fn query(&self, query: Query) -> Result<Self::Output> {
         println!("NoOpOperator query");
         self.source.query(query)
     }
 }
 
-impl<S> CreateUnaryOperator<S, String> for NoOpOperator<S> {
-    fn create<T1>(source: S, _params: String) -> Self {
+/// The NoOpOperator has no parameters.
+impl<S> CreateUnaryOperator<S, ()> for NoOpOperator<S> {
+    fn create<T1>(source: S, _params: ()) -> Self {
         NoOpOperator { source }
     }
 }
 
-/// The MetaNoopOperator
-#[derive(Serialize, Deserialize)]
+/// Passes its source through unchanged.
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct MetaNoopOperator {
+    #[schemars(skip)]
     pub sources: Vec<Box<dyn MetaRasterOperator>>,
 }
 
 // cant use constants in the crate bcause of typetag... -_-
 impl MetaNoopOperator {
     pub const REQUIRES_TYPES: [RasterWants; 1] = [RasterWants::Any];
+
+    fn create_typed_raster_op<T: BoxedRasterType>(
+        &self,
+    ) -> Result<Box<dyn RasterSource<RasterType = T>>> {
+        println!("MetaNoopOperator: create_typed_raster_op");
+        let source = self.create_raster_source(0)?.get::<T>()?;
+        Ok(Box::new(NoOpOperator::create::<T>(source, ())))
+    }
 }
 
 impl MetaOperator for MetaNoopOperator {
     fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
         self.sources.as_slice()
     }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::REQUIRES_TYPES
+    }
 }
 
 // impl MetaNoopOperator for MetaRasterOperator
 #[typetag::serde]
 impl MetaRasterOperator for MetaNoopOperator {
-    fn creates_type(&self) -> RasterType {
-        self.sources[0].creates_type() // this sould be same as input 1. need to handle this somewhere.
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        Ok(RasterCreates::SameAsInput(0))
     }
 
-    fn create_u8_raster_op(&self) -> Box<dyn RasterSource<RasterType = u8>> {
-        println!("MetaNoopOperator: create_u8_raster_op");
-        let source = self.raster_sources()[0]
-            .create_raster_op()
-            .get_u8()
-            .expect("not u8");
-        Box::new(NoOpOperator::create::<u8>(source, "noop".to_string()))
-    }
-    fn create_u16_raster_op(&self) -> Box<dyn RasterSource<RasterType = u16>> {
-        println!("MetaNoopOperator: create_u16_raster_op");
-        let source = self.raster_sources()[0]
-            .create_raster_op()
-            .get_u16()
-            .expect("not u8");
-        Box::new(NoOpOperator::create::<u16>(source, "noop".to_string()))
+    fn result_descriptor(&self) -> Result<RasterResultDescriptor> {
+        self.raster_source_descriptor(0)
     }
 
-    fn create_u32_raster_op(&self) -> Box<dyn RasterSource<RasterType = u32>> {
-        todo!()
-    }
-    fn create_u64_raster_op(&self) -> Box<dyn RasterSource<RasterType = u64>> {
-        todo!()
-    }
-    fn create_i16_raster_op(&self) -> Box<dyn RasterSource<RasterType = i16>> {
-        todo!()
-    }
-    fn create_i32_raster_op(&self) -> Box<dyn RasterSource<RasterType = i32>> {
-        todo!()
-    }
-    fn create_i64_raster_op(&self) -> Box<dyn RasterSource<RasterType = i64>> {
-        todo!()
-    }
-    fn create_f32_raster_op(&self) -> Box<dyn RasterSource<RasterType = f32>> {
-        todo!()
-    }
-    fn create_f64_raster_op(&self) -> Box<dyn RasterSource<RasterType = f64>> {
-        todo!()
-    }
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaNoopOperator: Raster
+        .with_raster_sources(&MetaNoopOperator::REQUIRES_TYPES)
 }
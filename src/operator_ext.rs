@@ -1,11 +1,16 @@
 use crate::{
-    add_raster_operator::AddRasterOperator,
+    band_select_operator::{BandSelectOperator, BandSelectParams},
+    band_stack_operator::BandStackOperator,
+    binary_raster_operator::{BinaryOperation, BinaryRasterOperator, BinaryRasterParams},
+    cast_operator::{CastOperator, CastParams},
+    feature_collection::{FeatureCollection, Geometries},
     noop_operator::NoOpOperator,
-    plus_one_operator::PlusOneOperator,
-    primitives::{Raster, VectorData},
-    raster_vector_operator::RasterVectorOperator,
+    plus_one_operator::{PlusOneOperator, PlusOneParams},
+    primitives::{Raster, Sampling},
+    raster_vector_operator::{RasterVectorOperator, RasterVectorParams},
     source::{RasterSource, Source, VectorSource},
 };
+use num_traits::AsPrimitive;
 
 /// A nice litte trait to add operations enable chaining all operators.
 pub trait VectorOperatorExt {
@@ -17,17 +22,27 @@ pub trait VectorOperatorExt {
         NoOpOperator { source: self }
     }
 
-    /// wraps any vector operator and adds raster data from any Operator producing Raster<T>
-    fn add_raster_values<R, V, T>(self, raster: R) -> RasterVectorOperator<R, Self>
+    /// wraps any vector operator and adds the values of any Operator producing Raster<T> as the attribute `column`
+    fn add_raster_values<R, G, T>(
+        self,
+        raster: R,
+        column: &str,
+        sampling: Sampling,
+    ) -> RasterVectorOperator<R, Self>
     where
         Self: Sized,
-        Self: Source<Output = V>,
-        V: VectorData,
+        Self: Source<Output = FeatureCollection<G>>,
+        G: Geometries,
         R: Source<Output = Raster<T>>,
-        T: Copy + Default,
+        T: AsPrimitive<f64> + PartialEq,
     {
         RasterVectorOperator {
             sources: (raster, self),
+            params: RasterVectorParams {
+                column: column.to_string(),
+                sampling,
+                band: 0,
+            },
         }
     }
 
@@ -56,19 +71,90 @@ pub trait RasterOperatorExt {
     where
         Self: RasterSource + Sized,
     {
-        PlusOneOperator { source: self }
+        PlusOneOperator {
+            source: self,
+            params: PlusOneParams::default(),
+        }
     }
 
-    fn plus_raster<R2, T2>(self, other: R2) -> AddRasterOperator<Self, R2>
+    fn plus_raster<R2, T2>(self, other: R2) -> BinaryRasterOperator<Self, R2>
     where
         R2: RasterSource<RasterType = T2>,
         Self: RasterSource + Sized,
     {
-        AddRasterOperator {
+        self.binary_raster(other, BinaryOperation::Add)
+    }
+
+    /// combines the pixels of both rasters with `operation`
+    fn binary_raster<R2, T2>(
+        self,
+        other: R2,
+        operation: BinaryOperation,
+    ) -> BinaryRasterOperator<Self, R2>
+    where
+        R2: RasterSource<RasterType = T2>,
+        Self: RasterSource + Sized,
+    {
+        BinaryRasterOperator {
             source: (self, other),
+            params: BinaryRasterParams::new(operation),
         }
     }
 
+    fn minus_raster<R2, T2>(self, other: R2) -> BinaryRasterOperator<Self, R2>
+    where
+        R2: RasterSource<RasterType = T2>,
+        Self: RasterSource + Sized,
+    {
+        self.binary_raster(other, BinaryOperation::Subtract)
+    }
+
+    fn times_raster<R2, T2>(self, other: R2) -> BinaryRasterOperator<Self, R2>
+    where
+        R2: RasterSource<RasterType = T2>,
+        Self: RasterSource + Sized,
+    {
+        self.binary_raster(other, BinaryOperation::Multiply)
+    }
+
+    fn divided_by_raster<R2, T2>(self, other: R2) -> BinaryRasterOperator<Self, R2>
+    where
+        R2: RasterSource<RasterType = T2>,
+        Self: RasterSource + Sized,
+    {
+        self.binary_raster(other, BinaryOperation::Divide)
+    }
+
+    /// keeps the given bands in the given order
+    fn select_bands(self, bands: Vec<usize>) -> BandSelectOperator<Self>
+    where
+        Self: RasterSource + Sized,
+    {
+        BandSelectOperator {
+            source: self,
+            params: BandSelectParams { bands },
+        }
+    }
+
+    /// appends the bands of `other`
+    fn stack_bands<R2, T>(self, other: R2) -> BandStackOperator<T>
+    where
+        Self: RasterSource<RasterType = T> + Sized + 'static,
+        R2: RasterSource<RasterType = T> + 'static,
+    {
+        BandStackOperator {
+            sources: vec![Box::new(self), Box::new(other)],
+        }
+    }
+
+    /// converts the pixels to `T`
+    fn cast<T>(self, params: CastParams) -> CastOperator<Self, T>
+    where
+        Self: RasterSource + Sized,
+    {
+        CastOperator::new(self, params)
+    }
+
     fn boxed_raster(self) -> Box<Self>
     where
         Self: Sized,
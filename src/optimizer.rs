@@ -0,0 +1,678 @@
+use crate::{
+    add_constant_operator::AddConstantParams,
+    expression::Expression,
+    expression_operator::ExpressionParams,
+    meta_raster_operator::{MetaRasterOperator, MetaVectorOperator},
+    raster_type::{OverflowPolicy, RasterType},
+    workflow::Workflow,
+};
+use num_traits::NumCast;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// the keys under which operators keep their sources
+const SOURCE_KEYS: [&str; 3] = ["sources", "raster_sources", "vector_sources"];
+
+/// operators that process every pixel on its own and keep the bands of their only source
+const PIXEL_WISE_UNARY: [&str; 3] = [
+    "MetaPlusOneOperator",
+    "MetaAddConstantOperator",
+    "MetaCastOperator",
+];
+
+/// An optimized operator graph together with the graph before and after the optimization.
+pub struct OptimizedGraph<O: ?Sized> {
+    pub operator: Box<O>,
+    pub before: Value,
+    pub after: Value,
+    /// a description of every rewrite in the order they were applied
+    pub rewrites: Vec<String>,
+}
+
+impl<O: ?Sized> OptimizedGraph<O> {
+    /// describes the rewrites and shows the graph before and after them
+    pub fn explain(&self) -> String {
+        let rewrites = if self.rewrites.is_empty() {
+            "  none\n".to_string()
+        } else {
+            self.rewrites
+                .iter()
+                .map(|rewrite| format!("  {}\n", rewrite))
+                .collect()
+        };
+        format!(
+            "before:\n{}\nrewrites:\n{}after:\n{}",
+            pretty(&self.before),
+            rewrites,
+            pretty(&self.after)
+        )
+    }
+}
+
+fn pretty(graph: &Value) -> String {
+    serde_json::to_string_pretty(graph).unwrap_or_else(|_| graph.to_string())
+}
+
+/// Optimizes a raster graph. The optimized graph produces the same rasters with fewer or cheaper operators:
+/// * `MetaNoopOperator`s are removed
+/// * chains of `MetaPlusOneOperator`s and `MetaAddConstantOperator`s over integer rasters become a single
+///   `MetaAddConstantOperator`
+/// * constant sub-expressions of `MetaExpressionOperator`s are folded
+/// * band selections are pushed down through pixel-wise operators into the `MetaGdalSource`s, which then
+///   only read the selected bands
+///
+/// Spatial and temporal restrictions need no rewrites: every operator passes the query on to its sources,
+/// so the sources only read the pixels and features in its rectangle and time interval.
+///
+/// Graphs that reference shared workflow nodes are returned unchanged, `optimize_workflow` optimizes them.
+pub fn optimize_raster_graph(
+    operator: Box<dyn MetaRasterOperator>,
+) -> serde_json::Result<OptimizedGraph<dyn MetaRasterOperator>> {
+    let before = serde_json::to_value(&operator)?;
+    if references_nodes(&before) {
+        return Ok(unchanged(operator, before));
+    }
+    let mut optimizer = Optimizer::default();
+    let after = optimizer.optimize(before.clone());
+    Ok(OptimizedGraph {
+        operator: serde_json::from_value(after.clone())?,
+        before,
+        after,
+        rewrites: optimizer.rewrites,
+    })
+}
+
+/// Optimizes the raster sources of a vector graph like `optimize_raster_graph`.
+pub fn optimize_vector_graph(
+    operator: Box<dyn MetaVectorOperator>,
+) -> serde_json::Result<OptimizedGraph<dyn MetaVectorOperator>> {
+    let before = serde_json::to_value(&operator)?;
+    if references_nodes(&before) {
+        return Ok(unchanged(operator, before));
+    }
+    let mut optimizer = Optimizer::default();
+    let after = optimizer.optimize(before.clone());
+    Ok(OptimizedGraph {
+        operator: serde_json::from_value(after.clone())?,
+        before,
+        after,
+        rewrites: optimizer.rewrites,
+    })
+}
+
+/// Optimizes every node of a workflow like `optimize_raster_graph`. References to other nodes are
+/// not followed, so rules that need to know a referenced node are not applied across references.
+pub fn optimize_workflow(workflow: Workflow) -> serde_json::Result<OptimizedGraph<Workflow>> {
+    let before = serde_json::to_value(&workflow)?;
+    let mut optimizer = Optimizer::default();
+    let mut after = before.clone();
+    if let Some(Value::Object(nodes)) = after.get_mut("nodes") {
+        for (id, operator) in nodes.iter_mut() {
+            let rewrites = optimizer.rewrites.len();
+            *operator = optimizer.optimize(operator.take());
+            for rewrite in &mut optimizer.rewrites[rewrites..] {
+                *rewrite = format!("node {}: {}", id, rewrite);
+            }
+        }
+    }
+    Ok(OptimizedGraph {
+        operator: Box::new(serde_json::from_value(after.clone())?),
+        before,
+        after,
+        rewrites: optimizer.rewrites,
+    })
+}
+
+fn unchanged<O: ?Sized>(operator: Box<O>, graph: Value) -> OptimizedGraph<O> {
+    OptimizedGraph {
+        operator,
+        before: graph.clone(),
+        after: graph,
+        rewrites: Vec::new(),
+    }
+}
+
+fn operator_type(operator: &Value) -> Option<&str> {
+    operator.get("type").and_then(Value::as_str)
+}
+
+fn sources(operator: &Value) -> impl Iterator<Item = &Value> {
+    SOURCE_KEYS
+        .iter()
+        .filter_map(move |key| operator.get(*key).and_then(Value::as_array))
+        .flatten()
+}
+
+/// the only source of an operator
+fn single_source(operator: &Value) -> Option<&Value> {
+    match operator.get("sources").and_then(Value::as_array) {
+        Some(sources) if sources.len() == 1 => sources.first(),
+        _ => None,
+    }
+}
+
+/// the nodes are linked after deserializing a whole workflow, so their graphs cannot be rebuilt from JSON
+fn references_nodes(operator: &Value) -> bool {
+    matches!(
+        operator_type(operator),
+        Some("MetaRasterNode") | Some("MetaVectorNode")
+    ) || sources(operator).any(references_nodes)
+}
+
+fn fits(raster_type: RasterType, value: f64) -> bool {
+    fn fits_in<T: NumCast>(value: f64) -> bool {
+        T::from(value).is_some()
+    }
+    match raster_type {
+        RasterType::U8 => fits_in::<u8>(value),
+        RasterType::U16 => fits_in::<u16>(value),
+        RasterType::U32 => fits_in::<u32>(value),
+        RasterType::U64 => fits_in::<u64>(value),
+        RasterType::I16 => fits_in::<i16>(value),
+        RasterType::I32 => fits_in::<i32>(value),
+        RasterType::I64 => fits_in::<i64>(value),
+        RasterType::F32 => fits_in::<f32>(value),
+        RasterType::F64 => fits_in::<f64>(value),
+    }
+}
+
+/// the constant and overflow policy of an operator that adds a constant
+fn addition(operator: &Value) -> Option<AddConstantParams> {
+    let overflow = match operator.get("overflow") {
+        Some(overflow) => serde_json::from_value(overflow.clone()).ok()?,
+        None => OverflowPolicy::default(),
+    };
+    let constant = match operator_type(operator)? {
+        "MetaPlusOneOperator" => 1.,
+        "MetaAddConstantOperator" => operator.get("constant")?.as_f64()?,
+        _ => return None,
+    };
+    Some(AddConstantParams { constant, overflow })
+}
+
+/// the operator with its only source replaced
+fn with_source(mut operator: Value, source: Value) -> Value {
+    operator["sources"] = json!([source]);
+    operator
+}
+
+/// The type and bands of a raster graph, `None` if they cannot be computed.
+#[derive(Clone, Copy)]
+struct GraphInfo {
+    raster_type: Option<RasterType>,
+    bands: Option<usize>,
+}
+
+#[derive(Default)]
+struct Optimizer {
+    rewrites: Vec<String>,
+    /// the info of the graphs looked up so far by their JSON, so every graph is read once
+    infos: HashMap<String, GraphInfo>,
+}
+
+impl Optimizer {
+    fn info(&mut self, operator: &Value) -> GraphInfo {
+        let key = operator.to_string();
+        if let Some(info) = self.infos.get(&key) {
+            return *info;
+        }
+        let operator = serde_json::from_value::<Box<dyn MetaRasterOperator>>(operator.clone()).ok();
+        let info = GraphInfo {
+            raster_type: operator.as_ref().and_then(|o| o.creates_type().ok()),
+            bands: operator.as_ref().and_then(|o| o.band_count().ok()),
+        };
+        self.infos.insert(key, info);
+        info
+    }
+
+    fn raster_type(&mut self, operator: &Value) -> Option<RasterType> {
+        self.info(operator).raster_type
+    }
+
+    fn raster_bands(&mut self, operator: &Value) -> Option<usize> {
+        self.info(operator).bands
+    }
+
+    /// optimizes the sources first, so every rule sees optimized sources
+    fn optimize(&mut self, mut operator: Value) -> Value {
+        for key in SOURCE_KEYS.iter() {
+            if let Some(Value::Array(sources)) = operator.get_mut(*key) {
+                for source in sources.iter_mut() {
+                    *source = self.optimize(source.take());
+                }
+            }
+        }
+        self.rewrite(operator)
+    }
+
+    fn rewrite(&mut self, operator: Value) -> Value {
+        match operator_type(&operator) {
+            Some("MetaNoopOperator") => self.remove_noop(operator),
+            Some("MetaPlusOneOperator") | Some("MetaAddConstantOperator") => {
+                self.fuse_additions(operator)
+            }
+            Some("MetaExpressionOperator") => self.fold_expression(operator),
+            Some("MetaBandSelectOperator") => self.push_down_band_selection(operator),
+            _ => operator,
+        }
+    }
+
+    fn remove_noop(&mut self, operator: Value) -> Value {
+        match single_source(&operator) {
+            Some(source) => {
+                self.rewrites.push("removed a MetaNoopOperator".to_string());
+                source.clone()
+            }
+            None => operator,
+        }
+    }
+
+    /// Fuses an addition with an addition as its source. Both add positive whole numbers to integers
+    /// and mark overflows the same way, so a pixel overflows in the chain iff it overflows in the sum.
+    /// No-data pixels of the source stay no-data pixels in both.
+    fn fuse_additions(&mut self, operator: Value) -> Value {
+        let fused = (|| {
+            let outer = addition(&operator)?;
+            let inner_operator = single_source(&operator)?;
+            let inner = addition(inner_operator)?;
+            let source = single_source(inner_operator)?;
+            let policies = [OverflowPolicy::NoData, OverflowPolicy::Saturate];
+            if outer.overflow != inner.overflow || !policies.contains(&outer.overflow) {
+                return None;
+            }
+            let constants = [inner.constant, outer.constant];
+            if constants.iter().any(|c| *c <= 0. || c.fract() != 0.) {
+                return None;
+            }
+            let constant = inner.constant + outer.constant;
+            let source_type = self.raster_type(source).filter(|t| !t.is_float())?;
+            if !fits(source_type, constant) {
+                return None;
+            }
+            Some(json!({
+                "type": "MetaAddConstantOperator",
+                "constant": constant,
+                "overflow": outer.overflow,
+                "sources": [source],
+            }))
+        })();
+        match fused {
+            Some(fused) => {
+                self.rewrites.push(format!(
+                    "fused {} and {} into a MetaAddConstantOperator adding {}",
+                    operator_type(&operator).unwrap_or_default(),
+                    single_source(&operator)
+                        .and_then(operator_type)
+                        .unwrap_or_default(),
+                    fused["constant"]
+                ));
+                fused
+            }
+            None => operator,
+        }
+    }
+
+    /// Folds the constant sub-expressions. The output type is kept if folding makes the expression integral.
+    fn fold_expression(&mut self, mut operator: Value) -> Value {
+        let params: ExpressionParams = match serde_json::from_value(operator.clone()) {
+            Ok(params) => params,
+            Err(_) => return operator,
+        };
+        let expression = match Expression::parse(&params.expression) {
+            Ok(expression) => expression,
+            Err(_) => return operator,
+        };
+        let folded = expression.folded();
+        if folded == expression {
+            return operator;
+        }
+        if params.output_type.is_none() && folded.is_integral() != expression.is_integral() {
+            match self.raster_type(&operator) {
+                Some(output_type) => operator["output_type"] = json!(output_type),
+                None => return operator,
+            }
+        }
+        self.rewrites.push(format!(
+            "folded the expression {} to {}",
+            params.expression, folded
+        ));
+        operator["expression"] = json!(folded.to_string());
+        operator
+    }
+
+    /// Moves a band selection below pixel-wise operators and merges it into band selections and
+    /// `MetaGdalSource`s, so less bands are read and processed.
+    fn push_down_band_selection(&mut self, operator: Value) -> Value {
+        let bands: Vec<usize> = match operator
+            .get("bands")
+            .and_then(|bands| serde_json::from_value(bands.clone()).ok())
+        {
+            Some(bands) => bands,
+            None => return operator,
+        };
+        let source = match single_source(&operator) {
+            Some(source) => source.clone(),
+            None => return operator,
+        };
+        let selected = |available: &[usize]| -> Option<Vec<usize>> {
+            bands
+                .iter()
+                .map(|&band| available.get(band).copied())
+                .collect()
+        };
+
+        match operator_type(&source) {
+            Some("MetaGdalSource") => {
+                let available = match source.get("bands") {
+                    Some(available) => serde_json::from_value(available.clone()).ok(),
+                    None => self
+                        .raster_bands(&source)
+                        .map(|bands| (0..bands).collect::<Vec<_>>()),
+                };
+                match available.as_deref().and_then(selected) {
+                    Some(bands) => {
+                        self.rewrites.push(format!(
+                            "pushed the band selection {:?} into the MetaGdalSource of {}",
+                            bands, source["dataset"]
+                        ));
+                        let mut source = source;
+                        source["bands"] = json!(bands);
+                        source
+                    }
+                    None => operator,
+                }
+            }
+            Some("MetaBandSelectOperator") => {
+                let available: Option<Vec<usize>> = source
+                    .get("bands")
+                    .and_then(|available| serde_json::from_value(available.clone()).ok());
+                match (
+                    available.as_deref().and_then(selected),
+                    single_source(&source),
+                ) {
+                    (Some(bands), Some(inner)) => {
+                        self.rewrites.push(format!(
+                            "merged two MetaBandSelectOperators into the selection {:?}",
+                            bands
+                        ));
+                        let merged = json!({
+                            "type": "MetaBandSelectOperator",
+                            "bands": bands,
+                            "sources": [inner],
+                        });
+                        self.rewrite(merged)
+                    }
+                    _ => operator,
+                }
+            }
+            Some(pixel_wise) if PIXEL_WISE_UNARY.contains(&pixel_wise) => {
+                let inner = match single_source(&source) {
+                    Some(inner) => inner.clone(),
+                    None => return operator,
+                };
+                self.rewrites.push(format!(
+                    "pushed the band selection {:?} below the {}",
+                    bands, pixel_wise
+                ));
+                let selection = self.rewrite(with_source(operator, inner));
+                with_source(source, selection)
+            }
+            _ => operator,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{
+            query, write_test_geotiff, write_test_geotiff_bands, write_test_geotiff_with_no_data,
+        },
+        GridShape,
+    };
+
+    fn optimize(graph: Value) -> OptimizedGraph<dyn MetaRasterOperator> {
+        optimize_raster_graph(serde_json::from_value(graph).unwrap()).unwrap()
+    }
+
+    fn query_u8(operator: &dyn MetaRasterOperator) -> Vec<u8> {
+        let instance = operator.create_raster_op().unwrap();
+        instance
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap()
+            .data
+    }
+
+    #[test]
+    fn removes_noops_and_fuses_additions() {
+        let dataset = write_test_geotiff(
+            "optimizer_fuse_u8.tif",
+            GridShape::new(2, 2),
+            vec![1u8, 2, 250, 253],
+        );
+        let graph = json!({
+            "type": "MetaAddConstantOperator",
+            "constant": 2.0,
+            "sources": [{
+                "type": "MetaNoopOperator",
+                "sources": [{
+                    "type": "MetaPlusOneOperator",
+                    "sources": [{
+                        "type": "MetaPlusOneOperator",
+                        "sources": [{"type": "MetaGdalSource", "dataset": dataset}]
+                    }]
+                }]
+            }]
+        });
+        let optimized = optimize(graph.clone());
+
+        assert_eq!(optimized.before["sources"][0]["type"], "MetaNoopOperator");
+        assert_eq!(
+            optimized.after,
+            json!({
+                "type": "MetaAddConstantOperator",
+                "constant": 4.0,
+                "overflow": "NoData",
+                "sources": [{"type": "MetaGdalSource", "dataset": dataset}]
+            })
+        );
+        assert_eq!(optimized.rewrites.len(), 3);
+        assert!(optimized.explain().contains("removed a MetaNoopOperator"));
+
+        let original: Box<dyn MetaRasterOperator> = serde_json::from_value(graph).unwrap();
+        assert_eq!(query_u8(optimized.operator.as_ref()), vec![5, 6, 254, 255]);
+        assert_eq!(
+            query_u8(original.as_ref()),
+            query_u8(optimized.operator.as_ref())
+        );
+    }
+
+    #[test]
+    fn fuses_additions_over_sources_with_no_data() {
+        let dataset = write_test_geotiff_with_no_data(
+            "optimizer_fuse_no_data_u8.tif",
+            GridShape::new(2, 2),
+            vec![1u8, 252, 253, 255],
+            255,
+        );
+        let graph = json!({
+            "type": "MetaPlusOneOperator",
+            "sources": [{
+                "type": "MetaPlusOneOperator",
+                "sources": [{"type": "MetaGdalSource", "dataset": dataset}]
+            }]
+        });
+        let original: Box<dyn MetaRasterOperator> = serde_json::from_value(graph.clone()).unwrap();
+        let optimized = optimize(graph);
+        assert_eq!(optimized.after["type"], "MetaAddConstantOperator");
+
+        let query = |operator: &dyn MetaRasterOperator| {
+            let raster = operator
+                .create_raster_op()
+                .unwrap()
+                .get::<u8>()
+                .unwrap()
+                .raster_query(query())
+                .unwrap();
+            let mask = raster.validity_mask();
+            let valid: Vec<Option<u8>> = raster
+                .data
+                .into_iter()
+                .zip(mask)
+                .map(|(value, valid)| Some(value).filter(|_| valid))
+                .collect();
+            valid
+        };
+        assert_eq!(
+            query(original.as_ref()),
+            vec![Some(3), Some(254), Some(255), None]
+        );
+        assert_eq!(query(original.as_ref()), query(optimized.operator.as_ref()));
+    }
+
+    #[test]
+    fn optimizes_workflow_nodes() {
+        let dataset = write_test_geotiff(
+            "optimizer_workflow_u8.tif",
+            GridShape::new(2, 2),
+            vec![1u8, 2, 3, 4],
+        );
+        let workflow: Workflow = serde_json::from_value(json!({
+            "nodes": {
+                "input": {
+                    "type": "MetaNoopOperator",
+                    "sources": [{"type": "MetaGdalSource", "dataset": dataset}],
+                },
+                "doubled": {
+                    "type": "MetaAddRasterOperator",
+                    "sources": [
+                        {"type": "MetaRasterNode", "node": "input"},
+                        {"type": "MetaRasterNode", "node": "input"},
+                    ],
+                },
+            },
+            "output": "doubled",
+        }))
+        .unwrap();
+        let optimized = optimize_workflow(workflow).unwrap();
+
+        assert_eq!(
+            optimized.after["nodes"]["input"],
+            json!({"type": "MetaGdalSource", "dataset": dataset})
+        );
+        assert_eq!(
+            optimized.rewrites,
+            vec!["node input: removed a MetaNoopOperator"]
+        );
+        let output = optimized.operator.raster_output().unwrap();
+        assert_eq!(query_u8(&output), vec![2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn keeps_additions_that_cannot_be_fused() {
+        let dataset = write_test_geotiff(
+            "optimizer_no_fuse_f32.tif",
+            GridShape::new(2, 2),
+            vec![0.1f32, 0.2, 0.3, 0.4],
+        );
+        // float additions are not associative
+        let graph = json!({
+            "type": "MetaPlusOneOperator",
+            "sources": [{
+                "type": "MetaPlusOneOperator",
+                "sources": [{"type": "MetaGdalSource", "dataset": dataset}]
+            }]
+        });
+        let optimized = optimize(graph.clone());
+        assert_eq!(optimized.after, optimized.before);
+        assert!(optimized.rewrites.is_empty());
+    }
+
+    #[test]
+    fn folds_expressions_and_keeps_the_output_type() {
+        let dataset = write_test_geotiff(
+            "optimizer_fold_u8.tif",
+            GridShape::new(2, 2),
+            vec![1u8, 2, 3, 4],
+        );
+        let graph = json!({
+            "type": "MetaExpressionOperator",
+            "expression": "A * (4 / 2)",
+            "sources": [{"type": "MetaGdalSource", "dataset": dataset}]
+        });
+        let original: Box<dyn MetaRasterOperator> = serde_json::from_value(graph.clone()).unwrap();
+        let optimized = optimize(graph);
+
+        assert_eq!(optimized.after["expression"], "(A * 2)");
+        assert_eq!(optimized.after["output_type"], "F32");
+        assert_eq!(optimized.operator.creates_type().unwrap(), RasterType::F32);
+        assert_eq!(
+            original.creates_type().unwrap(),
+            optimized.operator.creates_type().unwrap()
+        );
+    }
+
+    #[test]
+    fn folded_expressions_are_optimal() {
+        let graph = json!({
+            "type": "MetaExpressionOperator",
+            "expression": "A * (1 - 3) + -(2 * 0.5)",
+            "sources": [{
+                "type": "MetaGdalSource",
+                "dataset": write_test_geotiff(
+                    "optimizer_fold_negative_f32.tif",
+                    GridShape::new(2, 2),
+                    vec![1f32, 2., 3., 4.],
+                ),
+            }]
+        });
+        let optimized = optimize(graph);
+        assert_eq!(optimized.after["expression"], "((A * (-2)) + (-1))");
+
+        // optimizing again finds nothing to rewrite
+        let again = optimize(optimized.after.clone());
+        assert_eq!(again.after, optimized.after);
+        assert!(again.rewrites.is_empty());
+    }
+
+    #[test]
+    fn pushes_band_selections_into_sources() {
+        let dataset = write_test_geotiff_bands(
+            "optimizer_bands_u8.tif",
+            GridShape::new(2, 2),
+            3,
+            (0..12).collect::<Vec<u8>>(),
+        );
+        let graph = json!({
+            "type": "MetaBandSelectOperator",
+            "bands": [1],
+            "sources": [{
+                "type": "MetaPlusOneOperator",
+                "sources": [{
+                    "type": "MetaBandSelectOperator",
+                    "bands": [2, 0],
+                    "sources": [{"type": "MetaGdalSource", "dataset": dataset}]
+                }]
+            }]
+        });
+        let original: Box<dyn MetaRasterOperator> = serde_json::from_value(graph.clone()).unwrap();
+        let optimized = optimize(graph);
+
+        assert_eq!(
+            optimized.after,
+            json!({
+                "type": "MetaPlusOneOperator",
+                "overflow": "NoData",
+                "sources": [{"type": "MetaGdalSource", "dataset": dataset, "bands": [0]}]
+            })
+        );
+        assert_eq!(query_u8(optimized.operator.as_ref()), vec![1, 2, 3, 4]);
+        assert_eq!(
+            query_u8(original.as_ref()),
+            query_u8(optimized.operator.as_ref())
+        );
+    }
+}
@@ -1,58 +1,101 @@
 use crate::{
-    meta_raster_operator::{MetaRasterOperator, RasterWants},
+    error::Result,
+    meta_raster_operator::{MetaRasterOperator, RasterCreates, RasterWants},
     operator_creation,
     primitives::Raster,
-    source::{CreateUnaryOperator, Query, RasterSource, Source},
-    CreateBoxedUnaryOperator, MetaOperator, RasterType,
+    source::{CreateUnaryOperator, OperatorParams, Query, RasterSource, Source},
+    ArithmeticPixel, BoxedRasterType, CreateBoxedUnaryOperator, MetaOperator, OverflowPolicy,
 };
 use num_traits::One;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::ops::{Add, AddAssign};
-use typetag;
 
-/// The NoOp Operator does nothing. It wraps any Operator.
+/// The parameters of the `PlusOneOperator`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PlusOneParams {
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+}
+
+impl OperatorParams for PlusOneParams {}
+
+/// The PlusOneOperator adds one to every valid pixel.
 #[derive(Debug, Clone)]
 pub struct PlusOneOperator<S> {
     pub source: S,
+    pub params: PlusOneParams,
 }
 
-/// It works for anything
+/// It works for any arithmetic pixel type
 impl<T, S> Source for PlusOneOperator<S>
 where
     S: Source<Output = Raster<T>>,
-    T: AddAssign + Add<T> + One + Copy + Clone + Sized,
+    T: ArithmeticPixel,
 {
     type Output = Raster<T>;
-    fn query(&self, query: Query) -> Self::Output {
+    fn query(&self, query: Query) -> Result<Self::Output> {
         println!("PlusOneOperator query");
-        let mut r = self.source.query(query);
-        r.v.iter_mut().for_each(|p| p.add_assign(T::one()));
-        r
+        let r = self.source.query(query)?;
+        let results = r
+            .data
+            .iter()
+            .map(|&p| {
+                if r.is_no_data(p) {
+                    None
+                } else {
+                    self.params.overflow.add(p, T::one())
+                }
+            })
+            .collect();
+        // valid results may take the no-data value of the source, e.g. 254 + 1 in a u8 raster with no-data 255
+        let no_data_value = r.no_data_value.unwrap_or(T::NO_DATA);
+        Raster::from_results(
+            r.shape,
+            r.bands,
+            r.geo_transform,
+            results,
+            Some(no_data_value),
+        )
     }
 }
 
-impl<S> CreateUnaryOperator<S, String> for PlusOneOperator<S> {
-    fn create<T1>(source: S, _params: String) -> Self {
-        PlusOneOperator { source }
+impl<S> CreateUnaryOperator<S, PlusOneParams> for PlusOneOperator<S> {
+    fn create<T1>(source: S, params: PlusOneParams) -> Self {
+        PlusOneOperator { source, params }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Adds one to every valid pixel.
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct MetaPlusOneOperator {
+    #[serde(flatten)]
+    pub params: PlusOneParams,
+    #[schemars(skip)]
     pub sources: Vec<Box<dyn MetaRasterOperator>>,
 }
 
 impl MetaPlusOneOperator {
     pub const REQUIRES_TYPES: [RasterWants; 1] = [RasterWants::Any];
+
+    fn create_typed_raster_op<T>(&self) -> Result<Box<dyn RasterSource<RasterType = T>>>
+    where
+        T: BoxedRasterType + Add + AddAssign + One + ArithmeticPixel,
+    {
+        operator_creation::create_operator_unary_raster::<Self, T, _>(
+            self.create_raster_source(0)?,
+            self.params,
+        )
+    }
 }
 
-impl CreateBoxedUnaryOperator<String> for MetaPlusOneOperator {
+impl CreateBoxedUnaryOperator<PlusOneParams> for MetaPlusOneOperator {
     fn create_unary_boxed<T1>(
         source: Box<dyn RasterSource<RasterType = T1>>,
-        params: String,
+        params: PlusOneParams,
     ) -> Box<dyn RasterSource<RasterType = T1>>
     where
-        T1: Add + AddAssign + One + Copy + 'static,
+        T1: Add + AddAssign + One + ArithmeticPixel,
     {
         Box::new(PlusOneOperator::create::<T1>(source, params))
     }
@@ -62,44 +105,25 @@ impl MetaOperator for MetaPlusOneOperator {
     fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
         self.sources.as_slice()
     }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::REQUIRES_TYPES
+    }
 }
 
 #[typetag::serde]
 impl MetaRasterOperator for MetaPlusOneOperator {
-    fn creates_type(&self) -> RasterType {
-        self.sources[0].creates_type()
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        match self.params.overflow {
+            OverflowPolicy::Promote => Ok(RasterCreates::UpgradesInput(0)),
+            _ => Ok(RasterCreates::SameAsInput(0)),
+        }
     }
 
-    fn create_u8_raster_op(&self) -> Box<dyn RasterSource<RasterType = u8>> {
-        operator_creation::create_operator_unary_raster_u8::<Self>(
-            self.sources[0].create_raster_op(),
-        )
-    }
-    fn create_u16_raster_op(&self) -> Box<dyn RasterSource<RasterType = u16>> {
-        operator_creation::create_operator_unary_raster_u16::<Self>(
-            self.sources[0].create_raster_op(),
-        )
-    }
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
 
-    fn create_u32_raster_op(&self) -> Box<dyn RasterSource<RasterType = u32>> {
-        todo!()
-    }
-    fn create_u64_raster_op(&self) -> Box<dyn RasterSource<RasterType = u64>> {
-        todo!()
-    }
-    fn create_i16_raster_op(&self) -> Box<dyn RasterSource<RasterType = i16>> {
-        todo!()
-    }
-    fn create_i32_raster_op(&self) -> Box<dyn RasterSource<RasterType = i32>> {
-        todo!()
-    }
-    fn create_i64_raster_op(&self) -> Box<dyn RasterSource<RasterType = i64>> {
-        todo!()
-    }
-    fn create_f32_raster_op(&self) -> Box<dyn RasterSource<RasterType = f32>> {
-        todo!()
-    }
-    fn create_f64_raster_op(&self) -> Box<dyn RasterSource<RasterType = f64>> {
-        todo!()
-    }
+crate::register_operator! {
+    MetaPlusOneOperator: Raster
+        .with_raster_sources(&MetaPlusOneOperator::REQUIRES_TYPES)
 }
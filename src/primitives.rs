@@ -1,18 +1,1173 @@
-/// Simple mock implementation of a generic Raster
-#[derive(Debug, Clone)]
+use crate::{
+    error::{Error, Result},
+    raster_type::StaticRasterType,
+};
+use num_traits::{AsPrimitive, NumCast};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// The most pixels, summed over all bands, a resampled raster may have
+pub const MAX_RASTER_PIXELS: usize = 1 << 28;
+
+/// A georeferenced grid of pixels of type `T` with one or more bands.
+/// The bands are stored one after another, each row by row starting at the upper left pixel.
+/// Pixels equal to the `no_data_value` are missing and must not be used in computations.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Raster<T> {
-    pub v: Vec<T>,
+    pub shape: GridShape,
+    pub bands: usize,
+    pub geo_transform: GeoTransform,
+    pub data: Vec<T>,
+    pub no_data_value: Option<T>,
+}
+
+impl<T> Raster<T> {
+    /// Creates a new raster with a single band.
+    ///
+    /// # Panics
+    /// If the number of pixels does not match the grid shape.
+    pub fn new(
+        shape: GridShape,
+        geo_transform: GeoTransform,
+        data: Vec<T>,
+        no_data_value: Option<T>,
+    ) -> Self {
+        Self::new_with_bands(shape, 1, geo_transform, data, no_data_value)
+    }
+
+    /// Creates a new raster whose `data` holds the pixels of all bands one after another.
+    ///
+    /// # Panics
+    /// If the number of pixels does not match the grid shape and the number of bands.
+    pub fn new_with_bands(
+        shape: GridShape,
+        bands: usize,
+        geo_transform: GeoTransform,
+        data: Vec<T>,
+        no_data_value: Option<T>,
+    ) -> Self {
+        assert_eq!(
+            shape.number_of_pixels() * bands,
+            data.len(),
+            "the grid shape {:?} with {} bands does not match the number of pixels",
+            shape,
+            bands
+        );
+        Self {
+            shape,
+            bands,
+            geo_transform,
+            data,
+            no_data_value,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.shape.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.shape.height
+    }
+
+    /// the pixels of a band or `None` if the raster has no such band
+    pub fn band(&self, band: usize) -> Option<&[T]> {
+        let pixels = self.shape.number_of_pixels();
+        if band >= self.bands {
+            return None;
+        }
+        Some(&self.data[band * pixels..(band + 1) * pixels])
+    }
+
+    /// the pixel of the first band at `(column, row)` or `None` if it lies outside the grid
+    pub fn pixel(&self, column: usize, row: usize) -> Option<&T> {
+        self.band_pixel(0, column, row)
+    }
+
+    /// the pixel of a band at `(column, row)` or `None` if it lies outside the grid or the band does not exist
+    pub fn band_pixel(&self, band: usize, column: usize, row: usize) -> Option<&T> {
+        let index = self.shape.linear_index(column, row)?;
+        self.band(band)?.get(index)
+    }
+
+    /// the pixel of the first band covering a world coordinate or `None` if it lies outside the grid
+    pub fn pixel_at_coordinate(&self, coordinate: Coordinate2D) -> Option<&T> {
+        let (column, row) = self.geo_transform.coordinate_to_grid_index(coordinate)?;
+        if column < 0 || row < 0 {
+            return None;
+        }
+        self.pixel(column as usize, row as usize)
+    }
+
+    /// the area covered by the raster in world coordinates
+    pub fn bounding_box(&self) -> BoundingBox2D {
+        self.geo_transform.grid_bounding_box(self.shape)
+    }
+
+    /// the raster in another resolution covering the same area. Each pixel takes the value of the
+    /// pixel covering its center, i.e. it is resampled with nearest neighbour.
+    /// Fails if the resampled raster would have more than `MAX_RASTER_PIXELS` pixels.
+    pub fn resample_nearest(&self, resolution: SpatialResolution) -> Result<Raster<T>>
+    where
+        T: Copy,
+    {
+        let bbox = self.bounding_box();
+        let shape = if self.shape.number_of_pixels() == 0 {
+            GridShape::new(0, 0)
+        } else {
+            // float to integer casts saturate, so tiny resolutions end up above the limit
+            GridShape::new(
+                ((bbox.width() / resolution.x).round() as usize).max(1),
+                ((bbox.height() / resolution.y).round() as usize).max(1),
+            )
+        };
+        let pixels = shape
+            .width
+            .saturating_mul(shape.height)
+            .saturating_mul(self.bands);
+        if pixels > MAX_RASTER_PIXELS {
+            return Err(Error::RasterTooLarge {
+                width: shape.width,
+                height: shape.height,
+                bands: self.bands,
+            });
+        }
+        let geo_transform = GeoTransform::new_north_up(
+            Coordinate2D::new(bbox.lower_left().x, bbox.upper_right().y),
+            resolution,
+        );
+
+        let mut data = Vec::with_capacity(shape.number_of_pixels() * self.bands);
+        for band in 0..self.bands {
+            for row in 0..shape.height {
+                for column in 0..shape.width {
+                    let center = geo_transform.pixel_center_to_coordinate(column, row);
+                    let (source_column, source_row) = self
+                        .geo_transform
+                        .coordinate_to_grid_index(center)
+                        .unwrap_or((0, 0));
+                    let source_column = source_column.clamp(0, self.width() as isize - 1);
+                    let source_row = source_row.clamp(0, self.height() as isize - 1);
+                    data.push(
+                        *self
+                            .band_pixel(band, source_column as usize, source_row as usize)
+                            .expect("the index is clamped to the grid"),
+                    );
+                }
+            }
+        }
+        Ok(Raster::new_with_bands(
+            shape,
+            self.bands,
+            geo_transform,
+            data,
+            self.no_data_value,
+        ))
+    }
+
+    /// true if both rasters cover the same pixels, i.e. they can be combined pixel by pixel
+    pub fn is_grid_compatible<U>(&self, other: &Raster<U>) -> bool {
+        self.shape == other.shape && self.geo_transform.approx_eq(&other.geo_transform)
+    }
+
+    /// fails unless both rasters cover the same pixels
+    pub fn check_grid_compatible<U>(&self, other: &Raster<U>) -> Result<()> {
+        if self.is_grid_compatible(other) {
+            return Ok(());
+        }
+        Err(Error::IncompatibleGrids {
+            first: Box::new((self.shape, self.geo_transform)),
+            second: Box::new((other.shape, other.geo_transform)),
+        })
+    }
+
+    /// fails unless both rasters cover the same pixels with the same number of bands,
+    /// i.e. they can be combined pixel by pixel and band by band
+    pub fn check_compatible<U>(&self, other: &Raster<U>) -> Result<()> {
+        self.check_grid_compatible(other)?;
+        if self.bands != other.bands {
+            return Err(Error::BandCountMismatch {
+                first: self.bands,
+                second: other.bands,
+            });
+        }
+        Ok(())
+    }
+
+    /// a raster with the given bands in the given order. Bands may be repeated.
+    pub fn select_bands(&self, bands: &[usize]) -> Result<Raster<T>>
+    where
+        T: Copy,
+    {
+        let mut data = Vec::with_capacity(bands.len() * self.shape.number_of_pixels());
+        for &band in bands {
+            data.extend_from_slice(self.band(band).ok_or(Error::BandOutOfRange {
+                band,
+                bands: self.bands,
+            })?);
+        }
+        Ok(Raster {
+            shape: self.shape,
+            bands: bands.len(),
+            geo_transform: self.geo_transform,
+            data,
+            no_data_value: self.no_data_value,
+        })
+    }
+
+    /// Creates a raster from the results of a pixel-wise computation. `None` marks missing pixels.
+    /// They get the `preferred` no-data value unless a valid result takes it, otherwise a value that no valid result takes.
+    pub fn from_results(
+        shape: GridShape,
+        bands: usize,
+        geo_transform: GeoTransform,
+        results: Vec<Option<T>>,
+        preferred_no_data_value: Option<T>,
+    ) -> Result<Raster<T>>
+    where
+        T: StaticRasterType + PartialOrd + NumCast,
+    {
+        let has_no_data = results.iter().any(Option::is_none);
+        let valid = || results.iter().flatten().copied();
+        let no_data_value = match preferred_no_data_value {
+            // only search for another value if a valid result takes the preferred one
+            Some(preferred) if !valid().any(|value| is_no_data(value, preferred)) => {
+                Some(preferred)
+            }
+            Some(_) => free_no_data_value(valid(), preferred_no_data_value),
+            None if has_no_data => free_no_data_value(valid(), None),
+            None => None,
+        };
+        let data = match no_data_value {
+            Some(no_data_value) => results
+                .into_iter()
+                .map(|result| result.unwrap_or(no_data_value))
+                .collect(),
+            None if has_no_data => return Err(Error::NoFreeNoDataValue(T::TYPE)),
+            None => results.into_iter().flatten().collect(),
+        };
+        Ok(Self::new_with_bands(
+            shape,
+            bands,
+            geo_transform,
+            data,
+            no_data_value,
+        ))
+    }
+
+    /// converts every pixel and the no-data value. Floats are truncated towards zero.
+    /// Pixels that `U` cannot represent become no-data like the missing pixels of `self`.
+    /// They are marked with the converted no-data value unless a valid pixel takes it, see `from_results`.
+    pub fn convert<U>(self) -> Result<Raster<U>>
+    where
+        T: NumCast + PartialEq + Copy,
+        U: StaticRasterType + PartialOrd + NumCast,
+    {
+        let results = self
+            .data
+            .iter()
+            .map(|&value| {
+                if self.is_no_data(value) {
+                    None
+                } else {
+                    <U as NumCast>::from(value)
+                }
+            })
+            .collect();
+        Raster::from_results(
+            self.shape,
+            self.bands,
+            self.geo_transform,
+            results,
+            self.no_data_value.and_then(<U as NumCast>::from),
+        )
+    }
+}
+
+impl<T> Raster<T>
+where
+    T: PartialEq + Copy,
+{
+    /// true if `value` marks a missing pixel in this raster
+    pub fn is_no_data(&self, value: T) -> bool {
+        self.no_data_value
+            .is_some_and(|no_data_value| is_no_data(value, no_data_value))
+    }
+
+    /// a mask that is `true` for every pixel holding valid data
+    pub fn validity_mask(&self) -> Vec<bool> {
+        self.data
+            .iter()
+            .map(|&value| !self.is_no_data(value))
+            .collect()
+    }
+
+    /// appends the bands of `other`. The no-data pixels of both are marked with the no-data value of `self`,
+    /// or of `other`, unless a valid pixel takes it. Then they are marked with a value that no valid pixel takes.
+    pub fn stack(self, other: Raster<T>) -> Result<Raster<T>>
+    where
+        T: StaticRasterType + PartialOrd + NumCast,
+    {
+        self.check_grid_compatible(&other)?;
+        if self.no_data_value.is_none() && other.no_data_value.is_none() {
+            let mut data = self.data;
+            data.extend(other.data);
+            return Ok(Raster {
+                bands: self.bands + other.bands,
+                data,
+                ..self
+            });
+        }
+
+        let results = self
+            .data
+            .iter()
+            .map(|&value| Some(value).filter(|&value| !self.is_no_data(value)))
+            .chain(
+                other
+                    .data
+                    .iter()
+                    .map(|&value| Some(value).filter(|&value| !other.is_no_data(value))),
+            )
+            .collect();
+        Raster::from_results(
+            self.shape,
+            self.bands + other.bands,
+            self.geo_transform,
+            results,
+            self.no_data_value.or(other.no_data_value),
+        )
+    }
+
+    /// the value of a band at a world coordinate or `None` if it lies outside the grid, on a no-data pixel or the band does not exist.
+    /// Bilinear sampling interpolates between the centers of the surrounding pixels and ignores neighbors holding no data.
+    pub fn sample(&self, band: usize, coordinate: Coordinate2D, sampling: Sampling) -> Option<f64>
+    where
+        T: AsPrimitive<f64>,
+    {
+        let (column, row) = self.geo_transform.coordinate_to_grid_index(coordinate)?;
+        if column < 0 || row < 0 {
+            return None;
+        }
+        let value = *self.band_pixel(band, column as usize, row as usize)?;
+        if self.is_no_data(value) {
+            return None;
+        }
+        if sampling == Sampling::Nearest {
+            return Some(value.as_());
+        }
+
+        let (column, row) = self.geo_transform.coordinate_to_pixel(coordinate)?;
+        let (column, row) = (column - 0.5, row - 0.5);
+        let (left, top) = (column.floor(), row.floor());
+        let (x_weight, y_weight) = (column - left, row - top);
+
+        let mut sum = 0.;
+        let mut weights = 0.;
+        for (dx, dy, weight) in [
+            (0., 0., (1. - x_weight) * (1. - y_weight)),
+            (1., 0., x_weight * (1. - y_weight)),
+            (0., 1., (1. - x_weight) * y_weight),
+            (1., 1., x_weight * y_weight),
+        ] {
+            let clamp = |index: f64, size: usize| index.max(0.).min(size as f64 - 1.) as usize;
+            let neighbor = self.band_pixel(
+                band,
+                clamp(left + dx, self.width()),
+                clamp(top + dy, self.height()),
+            );
+            if let Some(&neighbor) = neighbor.filter(|&&neighbor| !self.is_no_data(neighbor)) {
+                sum += weight * neighbor.as_();
+                weights += weight;
+            }
+        }
+        Some(if weights > 0. {
+            sum / weights
+        } else {
+            value.as_()
+        })
+    }
+}
+
+/// How a raster is sampled at locations that do not coincide with pixel centers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum Sampling {
+    /// the value of the pixel covering the location
+    #[default]
+    Nearest,
+    /// the distance weighted mean of the four closest pixel centers
+    Bilinear,
+}
+
+/// A value to mark missing pixels that none of the valid `values` takes or `None` if they take all values of `T`.
+/// The candidates are the `preferred` value, the default no-data value of `T` and then the values next to it.
+pub fn free_no_data_value<T>(values: impl IntoIterator<Item = T>, preferred: Option<T>) -> Option<T>
+where
+    T: StaticRasterType + PartialOrd + NumCast,
+{
+    #[allow(clippy::eq_op)]
+    let (mut taken, nans): (Vec<T>, Vec<T>) = values.into_iter().partition(|value| value == value);
+    taken.sort_by(|a, b| a.partial_cmp(b).expect("NaNs are partitioned out"));
+    taken.dedup();
+    #[allow(clippy::eq_op)]
+    let is_free = |candidate: &T| {
+        if candidate != candidate {
+            nans.is_empty()
+        } else {
+            taken
+                .binary_search_by(|value| value.partial_cmp(candidate).expect("no NaNs"))
+                .is_err()
+        }
+    };
+
+    // integers: there are more candidates next to the default than valid values
+    let next_to_default = T::NO_DATA.to_i128().into_iter().flat_map(|start| {
+        let step = if start > 0 { -1 } else { 1 };
+        (1..=taken.len() as i128 + 1).map(move |distance| start + step * distance)
+    });
+    // floats whose NaNs are valid pixels
+    let infinite = [f64::NEG_INFINITY, f64::INFINITY];
+    preferred
+        .into_iter()
+        .chain(std::iter::once(T::NO_DATA))
+        .chain(next_to_default.filter_map(<T as NumCast>::from))
+        .chain(
+            infinite
+                .iter()
+                .filter_map(|&value| <T as NumCast>::from(value)),
+        )
+        .find(is_free)
+}
+
+/// Compares a value with a no-data value. Unlike `==` a `NaN` no-data value matches `NaN` pixels.
+#[allow(clippy::eq_op)]
+pub fn is_no_data<T: PartialEq>(value: T, no_data_value: T) -> bool {
+    value == no_data_value || (value != value && no_data_value != no_data_value)
+}
+
+/// The number of pixels of a grid in each dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GridShape {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl GridShape {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height }
+    }
+
+    pub fn number_of_pixels(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// the position of `(column, row)` in a row major pixel vector
+    pub fn linear_index(&self, column: usize, row: usize) -> Option<usize> {
+        if column < self.width && row < self.height {
+            Some(row * self.width + column)
+        } else {
+            None
+        }
+    }
+}
+
+/// An affine transformation from pixel space to world coordinates.
+///
+/// The coefficients follow the GDAL convention:
+/// `x = origin_x + column * x_pixel_size + row * x_rotation` and
+/// `y = origin_y + column * y_rotation + row * y_pixel_size`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoTransform {
+    pub origin_x: f64,
+    pub x_pixel_size: f64,
+    pub x_rotation: f64,
+    pub origin_y: f64,
+    pub y_rotation: f64,
+    pub y_pixel_size: f64,
+}
+
+impl GeoTransform {
+    /// the maximal difference of two coefficients that are considered equal
+    const EPSILON: f64 = 1e-9;
+
+    /// A north up transformation with `origin` as the upper left corner of the upper left pixel.
+    pub fn new_north_up(origin: Coordinate2D, resolution: SpatialResolution) -> Self {
+        Self {
+            origin_x: origin.x,
+            x_pixel_size: resolution.x,
+            x_rotation: 0.,
+            origin_y: origin.y,
+            y_rotation: 0.,
+            y_pixel_size: -resolution.y,
+        }
+    }
+
+    /// A transformation from the six coefficients in GDAL order.
+    pub fn from_gdal(coefficients: [f64; 6]) -> Self {
+        Self {
+            origin_x: coefficients[0],
+            x_pixel_size: coefficients[1],
+            x_rotation: coefficients[2],
+            origin_y: coefficients[3],
+            y_rotation: coefficients[4],
+            y_pixel_size: coefficients[5],
+        }
+    }
+
+    pub fn to_gdal(&self) -> [f64; 6] {
+        [
+            self.origin_x,
+            self.x_pixel_size,
+            self.x_rotation,
+            self.origin_y,
+            self.y_rotation,
+            self.y_pixel_size,
+        ]
+    }
+
+    /// the world coordinate of a (fractional) pixel position. `(0, 0)` is the upper left corner of the grid.
+    pub fn pixel_to_coordinate(&self, column: f64, row: f64) -> Coordinate2D {
+        Coordinate2D::new(
+            self.origin_x + column * self.x_pixel_size + row * self.x_rotation,
+            self.origin_y + column * self.y_rotation + row * self.y_pixel_size,
+        )
+    }
+
+    /// the world coordinate of the center of a pixel
+    pub fn pixel_center_to_coordinate(&self, column: usize, row: usize) -> Coordinate2D {
+        self.pixel_to_coordinate(column as f64 + 0.5, row as f64 + 0.5)
+    }
+
+    /// the (fractional) pixel position of a world coordinate or `None` if the transformation is not invertible
+    pub fn coordinate_to_pixel(&self, coordinate: Coordinate2D) -> Option<(f64, f64)> {
+        let determinant = self.x_pixel_size * self.y_pixel_size - self.x_rotation * self.y_rotation;
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let dx = coordinate.x - self.origin_x;
+        let dy = coordinate.y - self.origin_y;
+
+        let column = (dx * self.y_pixel_size - dy * self.x_rotation) / determinant;
+        let row = (dy * self.x_pixel_size - dx * self.y_rotation) / determinant;
+
+        Some((column, row))
+    }
+
+    /// the index of the pixel covering a world coordinate. The index may lie outside of any concrete grid.
+    pub fn coordinate_to_grid_index(&self, coordinate: Coordinate2D) -> Option<(isize, isize)> {
+        self.coordinate_to_pixel(coordinate)
+            .map(|(column, row)| (column.floor() as isize, row.floor() as isize))
+    }
+
+    /// the area covered by a grid of the given shape
+    pub fn grid_bounding_box(&self, shape: GridShape) -> BoundingBox2D {
+        let corners = [
+            self.pixel_to_coordinate(0., 0.),
+            self.pixel_to_coordinate(shape.width as f64, 0.),
+            self.pixel_to_coordinate(0., shape.height as f64),
+            self.pixel_to_coordinate(shape.width as f64, shape.height as f64),
+        ];
+
+        let (mut lower_left, mut upper_right) = (corners[0], corners[0]);
+        for corner in &corners[1..] {
+            lower_left = Coordinate2D::new(lower_left.x.min(corner.x), lower_left.y.min(corner.y));
+            upper_right =
+                Coordinate2D::new(upper_right.x.max(corner.x), upper_right.y.max(corner.y));
+        }
+
+        BoundingBox2D {
+            lower_left,
+            upper_right,
+        }
+    }
+
+    /// the size of a pixel along its columns and rows
+    pub fn resolution(&self) -> SpatialResolution {
+        SpatialResolution {
+            x: self.x_pixel_size.hypot(self.y_rotation),
+            y: self.x_rotation.hypot(self.y_pixel_size),
+        }
+    }
+
+    /// true if all coefficients are equal except for floating point inaccuracies
+    pub fn approx_eq(&self, other: &Self) -> bool {
+        self.to_gdal()
+            .iter()
+            .zip(other.to_gdal().iter())
+            .all(|(a, b)| (a - b).abs() <= Self::EPSILON)
+    }
+}
+
+/// A coordinate in a two dimensional world coordinate system.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinate2D {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Coordinate2D {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+}
+
+/// An axis aligned bounding box given by its lower left and upper right corner.
+/// Deserializing checks the corners like `BoundingBox2D::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "BoundingBox2DCorners")]
+pub struct BoundingBox2D {
+    lower_left: Coordinate2D,
+    upper_right: Coordinate2D,
+}
+
+#[derive(Deserialize)]
+struct BoundingBox2DCorners {
+    lower_left: Coordinate2D,
+    upper_right: Coordinate2D,
+}
+
+impl TryFrom<BoundingBox2DCorners> for BoundingBox2D {
+    type Error = Error;
+
+    fn try_from(corners: BoundingBox2DCorners) -> Result<Self> {
+        Self::new(corners.lower_left, corners.upper_right)
+    }
+}
+
+impl BoundingBox2D {
+    /// Creates a new bounding box. Fails if the lower left corner is not below and left of the upper right corner.
+    pub fn new(lower_left: Coordinate2D, upper_right: Coordinate2D) -> Result<Self> {
+        if lower_left.x <= upper_right.x && lower_left.y <= upper_right.y {
+            Ok(Self {
+                lower_left,
+                upper_right,
+            })
+        } else {
+            Err(Error::InvalidBoundingBox {
+                lower_left,
+                upper_right,
+            })
+        }
+    }
+
+    /// the smallest box containing all coordinates or `None` if there are none
+    pub fn from_coordinates(coordinates: impl IntoIterator<Item = Coordinate2D>) -> Option<Self> {
+        let mut coordinates = coordinates.into_iter();
+        let first = coordinates.next()?;
+        let (lower_left, upper_right) =
+            coordinates.fold((first, first), |(lower_left, upper_right), coordinate| {
+                (
+                    Coordinate2D::new(
+                        lower_left.x.min(coordinate.x),
+                        lower_left.y.min(coordinate.y),
+                    ),
+                    Coordinate2D::new(
+                        upper_right.x.max(coordinate.x),
+                        upper_right.y.max(coordinate.y),
+                    ),
+                )
+            });
+        Some(Self {
+            lower_left,
+            upper_right,
+        })
+    }
+
+    pub fn lower_left(&self) -> Coordinate2D {
+        self.lower_left
+    }
+
+    pub fn upper_right(&self) -> Coordinate2D {
+        self.upper_right
+    }
+
+    pub fn width(&self) -> f64 {
+        self.upper_right.x - self.lower_left.x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.upper_right.y - self.lower_left.y
+    }
+
+    /// true if the coordinate lies inside the box or on its border
+    pub fn contains_coordinate(&self, coordinate: &Coordinate2D) -> bool {
+        coordinate.x >= self.lower_left.x
+            && coordinate.x <= self.upper_right.x
+            && coordinate.y >= self.lower_left.y
+            && coordinate.y <= self.upper_right.y
+    }
+
+    /// the common area of two boxes or `None` if they do not overlap
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let lower_left = Coordinate2D::new(
+            self.lower_left.x.max(other.lower_left.x),
+            self.lower_left.y.max(other.lower_left.y),
+        );
+        let upper_right = Coordinate2D::new(
+            self.upper_right.x.min(other.upper_right.x),
+            self.upper_right.y.min(other.upper_right.y),
+        );
+
+        if lower_left.x <= upper_right.x && lower_left.y <= upper_right.y {
+            Some(Self {
+                lower_left,
+                upper_right,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// A half-open time interval `[start, end)` in milliseconds since the unix epoch.
+/// Deserializing checks the bounds like `TimeInterval::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "TimeIntervalBounds")]
+pub struct TimeInterval {
+    start: i64,
+    end: i64,
 }
 
-/// Simple mock implementation of a Point
-#[derive(Debug, Clone, Copy)]
-pub struct Point {
-    pub a: f32,
-    pub b: f32,
+#[derive(Deserialize)]
+struct TimeIntervalBounds {
+    start: i64,
+    end: i64,
 }
 
-/// A trait for Vector Data
-pub trait VectorData {}
+impl TryFrom<TimeIntervalBounds> for TimeInterval {
+    type Error = Error;
+
+    fn try_from(bounds: TimeIntervalBounds) -> Result<Self> {
+        Self::new(bounds.start, bounds.end)
+    }
+}
+
+impl TimeInterval {
+    /// Creates a new time interval. Fails if `start` is after `end`.
+    pub fn new(start: i64, end: i64) -> Result<Self> {
+        if start <= end {
+            Ok(Self { start, end })
+        } else {
+            Err(Error::InvalidTimeInterval { start, end })
+        }
+    }
+
+    /// a time interval of a single instant
+    pub fn new_instant(time: i64) -> Self {
+        Self {
+            start: time,
+            end: time,
+        }
+    }
 
-/// a Point is a VectorData format
-impl VectorData for Point {}
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    pub fn end(&self) -> i64 {
+        self.end
+    }
+
+    pub fn is_instant(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// true if the instant lies inside the interval
+    pub fn contains_instant(&self, time: i64) -> bool {
+        if self.is_instant() {
+            time == self.start
+        } else {
+            self.start <= time && time < self.end
+        }
+    }
+
+    /// true if both intervals share at least one instant
+    pub fn intersects(&self, other: &Self) -> bool {
+        if self.is_instant() {
+            other.contains_instant(self.start)
+        } else if other.is_instant() {
+            self.contains_instant(other.start)
+        } else {
+            self.start < other.end && other.start < self.end
+        }
+    }
+}
+
+/// A coordinate reference system identified by its EPSG code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct SpatialReference {
+    pub epsg_code: u32,
+}
+
+impl SpatialReference {
+    pub fn epsg(epsg_code: u32) -> Self {
+        Self { epsg_code }
+    }
+
+    /// WGS 84 longitude/latitude
+    pub fn wgs84() -> Self {
+        Self::epsg(4326)
+    }
+
+    /// true for geographic (longitude/latitude) systems. EPSG assigns the codes 4000 to 4999 to them.
+    pub fn is_geographic(&self) -> bool {
+        (4000..5000).contains(&self.epsg_code)
+    }
+}
+
+impl std::fmt::Display for SpatialReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EPSG:{}", self.epsg_code)
+    }
+}
+
+/// The size of a pixel in world coordinates. Deserializing checks the sizes like `SpatialResolution::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SpatialResolutionSizes")]
+pub struct SpatialResolution {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Deserialize)]
+struct SpatialResolutionSizes {
+    x: f64,
+    y: f64,
+}
+
+impl TryFrom<SpatialResolutionSizes> for SpatialResolution {
+    type Error = Error;
+
+    fn try_from(sizes: SpatialResolutionSizes) -> Result<Self> {
+        Self::new(sizes.x, sizes.y)
+    }
+}
+
+impl SpatialResolution {
+    /// Creates a new resolution. Fails if one of the pixel sizes is not strictly positive and finite.
+    pub fn new(x: f64, y: f64) -> Result<Self> {
+        if x > 0. && y > 0. && x.is_finite() && y.is_finite() {
+            Ok(Self { x, y })
+        } else {
+            Err(Error::InvalidResolution { x, y })
+        }
+    }
+
+    /// a resolution with the same pixel size in both directions
+    pub fn new_square(size: f64) -> Result<Self> {
+        Self::new(size, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_coordinate_conversion() {
+        let geo_transform = GeoTransform::new_north_up(
+            Coordinate2D::new(10., 50.),
+            SpatialResolution::new(0.5, 0.25).unwrap(),
+        );
+        let raster = Raster::new(
+            GridShape::new(4, 2),
+            geo_transform,
+            (0..8u8).collect(),
+            None,
+        );
+
+        assert_eq!(
+            geo_transform.pixel_to_coordinate(0., 0.),
+            Coordinate2D::new(10., 50.)
+        );
+        assert_eq!(
+            geo_transform.pixel_center_to_coordinate(1, 1),
+            Coordinate2D::new(10.75, 49.625)
+        );
+        assert_eq!(
+            geo_transform.coordinate_to_pixel(Coordinate2D::new(11., 49.5)),
+            Some((2., 2.))
+        );
+        assert_eq!(
+            raster.bounding_box(),
+            BoundingBox2D::new(Coordinate2D::new(10., 49.5), Coordinate2D::new(12., 50.)).unwrap()
+        );
+
+        assert_eq!(raster.pixel(3, 1), Some(&7));
+        assert_eq!(raster.pixel(4, 1), None);
+        assert_eq!(
+            raster.pixel_at_coordinate(Coordinate2D::new(10.6, 49.7)),
+            Some(&5)
+        );
+        assert_eq!(
+            raster.pixel_at_coordinate(Coordinate2D::new(9.9, 49.7)),
+            None
+        );
+    }
+
+    #[test]
+    fn rotated_geo_transform_roundtrip() {
+        let geo_transform = GeoTransform::from_gdal([100., 2., 0.5, 200., 0.25, -3.]);
+        let coordinate = geo_transform.pixel_to_coordinate(7.5, 3.25);
+        let (column, row) = geo_transform.coordinate_to_pixel(coordinate).unwrap();
+        assert!((column - 7.5).abs() < 1e-9);
+        assert!((row - 3.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_data_mask() {
+        let geo_transform = GeoTransform::new_north_up(
+            Coordinate2D::new(0., 2.),
+            SpatialResolution::new_square(1.).unwrap(),
+        );
+        let raster = Raster::new(
+            GridShape::new(2, 2),
+            geo_transform,
+            vec![1., f32::NAN, 3., 4.],
+            Some(f32::NAN),
+        );
+        assert_eq!(raster.validity_mask(), vec![true, false, true, true]);
+
+        let raster = Raster::new(GridShape::new(2, 1), geo_transform, vec![0u8, 7], Some(0));
+        assert_eq!(raster.validity_mask(), vec![false, true]);
+
+        let raster = Raster::new(GridShape::new(2, 1), geo_transform, vec![0u8, 7], None);
+        assert_eq!(raster.validity_mask(), vec![true, true]);
+    }
+
+    #[test]
+    fn conversions_mark_unrepresentable_pixels_as_no_data() {
+        let geo_transform = GeoTransform::new_north_up(
+            Coordinate2D::new(0., 1.),
+            SpatialResolution::new_square(1.).unwrap(),
+        );
+        let raster = Raster::new(
+            GridShape::new(3, 1),
+            geo_transform,
+            vec![1u64, u64::MAX, 7],
+            None,
+        );
+        let converted = raster.clone().convert::<i64>().unwrap();
+        assert_eq!(converted.data, vec![1, i64::NO_DATA, 7]);
+        assert_eq!(converted.validity_mask(), vec![true, false, true]);
+        let converted = raster.convert::<f64>().unwrap();
+        assert_eq!(converted.data, vec![1., u64::MAX as f64, 7.]);
+        assert_eq!(converted.no_data_value, None);
+
+        // the no-data value of the source is kept if it can be represented
+        let raster = Raster::new(
+            GridShape::new(3, 1),
+            geo_transform,
+            vec![i64::MIN, -1, 255],
+            Some(-1),
+        );
+        let converted = raster.clone().convert::<u8>().unwrap();
+        assert_eq!(converted.data, vec![254, 254, 255]);
+        assert_eq!(converted.validity_mask(), vec![false, false, true]);
+        let converted = raster.convert::<i32>().unwrap();
+        assert_eq!(converted.data, vec![-1, -1, 255]);
+        assert_eq!(converted.no_data_value, Some(-1));
+    }
+
+    #[test]
+    fn free_no_data_values() {
+        assert_eq!(free_no_data_value(vec![1u8, 2], Some(0)), Some(0));
+        assert_eq!(free_no_data_value(vec![0u8, 2], Some(0)), Some(255));
+        assert_eq!(free_no_data_value(vec![0u8, 255, 254], Some(0)), Some(253));
+        assert_eq!(free_no_data_value(vec![i16::MIN], None), Some(i16::MIN + 1));
+        assert_eq!(free_no_data_value(0..=255u8, None), None);
+        assert!(free_no_data_value(vec![1f32], None).unwrap().is_nan());
+        assert_eq!(
+            free_no_data_value(vec![f64::NAN, 1.], None),
+            Some(f64::NEG_INFINITY)
+        );
+
+        let geo_transform = GeoTransform::new_north_up(
+            Coordinate2D::new(0., 1.),
+            SpatialResolution::new_square(1.).unwrap(),
+        );
+        let raster = Raster::from_results(
+            GridShape::new(3, 1),
+            1,
+            geo_transform,
+            vec![Some(0u8), None, Some(255)],
+            Some(0),
+        )
+        .unwrap();
+        assert_eq!(raster.data, vec![0, 254, 255]);
+        assert_eq!(raster.validity_mask(), vec![true, false, true]);
+
+        let results = (0..=255u8).map(Some).chain(Some(None)).collect();
+        assert!(matches!(
+            Raster::from_results(GridShape::new(257, 1), 1, geo_transform, results, None),
+            Err(Error::NoFreeNoDataValue(crate::RasterType::U8))
+        ));
+    }
+
+    #[test]
+    fn sampling() {
+        let geo_transform = GeoTransform::new_north_up(
+            Coordinate2D::new(0., 2.),
+            SpatialResolution::new_square(1.).unwrap(),
+        );
+        let raster = Raster::new(
+            GridShape::new(2, 2),
+            geo_transform,
+            vec![0u8, 10, 20, 30],
+            Some(0),
+        );
+
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(1.5, 1.5), Sampling::Nearest),
+            Some(10.)
+        );
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(0.5, 0.5), Sampling::Nearest),
+            Some(20.)
+        );
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(0.5, 1.5), Sampling::Nearest),
+            None
+        );
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(0.5, 1.5), Sampling::Bilinear),
+            None
+        );
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(3., 1.), Sampling::Bilinear),
+            None
+        );
+
+        // the no-data neighbor is ignored
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(1., 1.), Sampling::Bilinear),
+            Some(20.)
+        );
+        // a quarter of the way from the center of 10 to the center of 30
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(1.5, 1.25), Sampling::Bilinear),
+            Some(15.)
+        );
+
+        let raster = Raster::new(GridShape::new(2, 1), geo_transform, vec![1., 3.], None);
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(1., 1.5), Sampling::Bilinear),
+            Some(2.)
+        );
+        // the edge pixels are extended beyond their centers
+        assert_eq!(
+            raster.sample(0, Coordinate2D::new(0.1, 1.5), Sampling::Bilinear),
+            Some(1.)
+        );
+    }
+
+    #[test]
+    fn grid_compatibility() {
+        let geo_transform = GeoTransform::new_north_up(
+            Coordinate2D::new(0., 2.),
+            SpatialResolution::new_square(1.).unwrap(),
+        );
+        let a = Raster::new(GridShape::new(2, 2), geo_transform, vec![0u8; 4], None);
+        let b = Raster::new(GridShape::new(2, 2), geo_transform, vec![0u16; 4], None);
+        let c = Raster::new(GridShape::new(4, 1), geo_transform, vec![0u16; 4], None);
+        let d = Raster::new(
+            GridShape::new(2, 2),
+            GeoTransform::new_north_up(
+                Coordinate2D::new(1., 2.),
+                SpatialResolution::new_square(1.).unwrap(),
+            ),
+            vec![0u16; 4],
+            None,
+        );
+
+        assert!(a.is_grid_compatible(&b));
+        assert!(!a.is_grid_compatible(&c));
+        assert!(!a.is_grid_compatible(&d));
+    }
+
+    #[test]
+    fn bounding_box_intersection() {
+        let a = BoundingBox2D::new(Coordinate2D::new(0., 0.), Coordinate2D::new(10., 10.)).unwrap();
+        let b = BoundingBox2D::new(Coordinate2D::new(5., -5.), Coordinate2D::new(15., 5.)).unwrap();
+        let c =
+            BoundingBox2D::new(Coordinate2D::new(20., 20.), Coordinate2D::new(30., 30.)).unwrap();
+
+        assert_eq!(
+            a.intersection(&b),
+            Some(
+                BoundingBox2D::new(Coordinate2D::new(5., 0.), Coordinate2D::new(10., 5.)).unwrap()
+            )
+        );
+        assert_eq!(a.intersection(&c), None);
+        assert!(a.contains_coordinate(&Coordinate2D::new(10., 0.)));
+        assert!(!a.contains_coordinate(&Coordinate2D::new(10.5, 0.)));
+    }
+
+    #[test]
+    fn time_interval_intersection() {
+        let a = TimeInterval::new(0, 10).unwrap();
+        assert!(a.intersects(&TimeInterval::new(5, 15).unwrap()));
+        assert!(a.intersects(&TimeInterval::new_instant(0)));
+        assert!(!a.intersects(&TimeInterval::new(10, 20).unwrap()));
+        assert!(!a.intersects(&TimeInterval::new_instant(10)));
+        assert!(TimeInterval::new_instant(3).intersects(&TimeInterval::new_instant(3)));
+    }
+
+    #[test]
+    fn rejects_invalid_bounds() {
+        assert!(BoundingBox2D::new(Coordinate2D::new(1., 0.), Coordinate2D::new(0., 1.)).is_err());
+        assert!(TimeInterval::new(2, 1).is_err());
+
+        let bbox: Result<BoundingBox2D, _> = serde_json::from_str(
+            r#"{"lower_left": {"x": 0, "y": 1}, "upper_right": {"x": 1, "y": 0}}"#,
+        );
+        assert!(bbox.is_err());
+        let time: Result<TimeInterval, _> = serde_json::from_str(r#"{"start": 2, "end": 1}"#);
+        assert!(time.is_err());
+        let time: TimeInterval = serde_json::from_str(r#"{"start": 1, "end": 2}"#).unwrap();
+        assert_eq!(time, TimeInterval::new(1, 2).unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_resolutions() {
+        assert!(SpatialResolution::new(0., 1.).is_err());
+        assert!(SpatialResolution::new(1., -1.).is_err());
+        assert!(SpatialResolution::new_square(f64::NAN).is_err());
+        assert!(SpatialResolution::new_square(f64::INFINITY).is_err());
+
+        let resolution: Result<SpatialResolution, _> = serde_json::from_str(r#"{"x": 0, "y": 1}"#);
+        assert!(resolution.is_err());
+        let resolution: SpatialResolution = serde_json::from_str(r#"{"x": 2, "y": 1}"#).unwrap();
+        assert_eq!(resolution, SpatialResolution::new(2., 1.).unwrap());
+    }
+
+    #[test]
+    fn resampling_is_limited_in_size() {
+        let raster = Raster::new(
+            GridShape::new(2, 2),
+            GeoTransform::new_north_up(
+                Coordinate2D::new(0., 2.),
+                SpatialResolution::new_square(1.).unwrap(),
+            ),
+            vec![1u8, 2, 3, 4],
+            None,
+        );
+        assert!(matches!(
+            raster.resample_nearest(SpatialResolution::new_square(1e-9).unwrap()),
+            Err(Error::RasterTooLarge { .. })
+        ));
+        assert!(matches!(
+            raster.resample_nearest(SpatialResolution { x: 0., y: 0. }),
+            Err(Error::RasterTooLarge { .. })
+        ));
+        assert_eq!(
+            raster
+                .resample_nearest(SpatialResolution::new_square(2.).unwrap())
+                .unwrap()
+                .data,
+            vec![4]
+        );
+    }
+}
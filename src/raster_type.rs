@@ -1,7 +1,11 @@
+use crate::error::{Error, Result};
+use num_traits::{NumCast, One, Zero};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 /// An enum for the Raster types.
-#[derive(Debug, Clone, Serialize, Deserialize, Copy)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Copy, PartialEq, Eq, Hash)]
 pub enum RasterType {
     U8,
     U16,
@@ -14,43 +18,557 @@ pub enum RasterType {
     F64,
 }
 
+impl RasterType {
+    /// the next larger type of the same kind, e.g. U8 -> U16
+    pub fn upgraded(self) -> Option<RasterType> {
+        match self {
+            RasterType::U8 => Some(RasterType::U16),
+            RasterType::U16 => Some(RasterType::U32),
+            RasterType::U32 => Some(RasterType::U64),
+            RasterType::I16 => Some(RasterType::I32),
+            RasterType::I32 => Some(RasterType::I64),
+            RasterType::F32 => Some(RasterType::F64),
+            RasterType::U64 | RasterType::I64 | RasterType::F64 => None,
+        }
+    }
+
+    /// the smallest type that can hold all values of both types, e.g. U8 + I16 -> I16, I16 + U32 -> I64.
+    /// Floats take precedence over integers. U64 + I64 and 64 bit integers + floats become F64.
+    pub fn promoted(self, other: RasterType) -> RasterType {
+        const BY_SIZE: [RasterType; 9] = [
+            RasterType::U8,
+            RasterType::U16,
+            RasterType::I16,
+            RasterType::U32,
+            RasterType::I32,
+            RasterType::F32,
+            RasterType::U64,
+            RasterType::I64,
+            RasterType::F64,
+        ];
+
+        BY_SIZE
+            .iter()
+            .copied()
+            .find(|candidate| candidate.can_hold(self) && candidate.can_hold(other))
+            .unwrap_or(RasterType::F64)
+    }
+
+    /// true if all values of `other` can be represented exactly
+    pub fn can_hold(self, other: RasterType) -> bool {
+        if (other.is_float() && !self.is_float()) || (other.is_signed() && !self.is_signed()) {
+            return false;
+        }
+        self.magnitude_bits() >= other.magnitude_bits()
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, RasterType::F32 | RasterType::F64)
+    }
+
+    pub fn is_signed(self) -> bool {
+        !matches!(
+            self,
+            RasterType::U8 | RasterType::U16 | RasterType::U32 | RasterType::U64
+        )
+    }
+
+    /// the number of bits for the magnitude of integers, i.e. without the sign bit or the exponent
+    fn magnitude_bits(self) -> u32 {
+        match self {
+            RasterType::U8 => 8,
+            RasterType::U16 => 16,
+            RasterType::U32 => 32,
+            RasterType::U64 => 64,
+            RasterType::I16 => 15,
+            RasterType::I32 => 31,
+            RasterType::I64 => 63,
+            RasterType::F32 => 24,
+            RasterType::F64 => 53,
+        }
+    }
+
+    /// the value that marks missing pixels of this type if a raster does not specify one
+    pub fn no_data_value(self) -> f64 {
+        match self {
+            RasterType::U8 => u8::NO_DATA.into(),
+            RasterType::U16 => u16::NO_DATA.into(),
+            RasterType::U32 => u32::NO_DATA.into(),
+            RasterType::U64 => u64::NO_DATA as f64,
+            RasterType::I16 => i16::NO_DATA.into(),
+            RasterType::I32 => i32::NO_DATA.into(),
+            RasterType::I64 => i64::NO_DATA as f64,
+            RasterType::F32 => f32::NO_DATA.into(),
+            RasterType::F64 => f64::NO_DATA,
+        }
+    }
+
+    /// converts `value` to this type with `as`, i.e. saturating and truncating towards zero
+    pub fn convert_value(self, value: f64) -> f64 {
+        match self {
+            RasterType::U8 => (value as u8).into(),
+            RasterType::U16 => (value as u16).into(),
+            RasterType::U32 => (value as u32).into(),
+            RasterType::U64 => value as u64 as f64,
+            RasterType::I16 => (value as i16).into(),
+            RasterType::I32 => (value as i32).into(),
+            RasterType::I64 => value as i64 as f64,
+            RasterType::F32 => (value as f32).into(),
+            RasterType::F64 => value,
+        }
+    }
+
+    /// true if converting `value` to this type keeps it, `NaN` only fits floats
+    pub fn can_represent(self, value: f64) -> bool {
+        let converted = self.convert_value(value);
+        converted == value || (converted.is_nan() && value.is_nan())
+    }
+
+    /// the next smaller type of the same kind, e.g. U16 -> U8
+    pub fn downgraded(self) -> Option<RasterType> {
+        match self {
+            RasterType::U16 => Some(RasterType::U8),
+            RasterType::U32 => Some(RasterType::U16),
+            RasterType::U64 => Some(RasterType::U32),
+            RasterType::I32 => Some(RasterType::I16),
+            RasterType::I64 => Some(RasterType::I32),
+            RasterType::F64 => Some(RasterType::F32),
+            RasterType::U8 | RasterType::I16 | RasterType::F32 => None,
+        }
+    }
+}
+
 /// A trait to get the RasterType from primitive types.
-pub trait StaticRasterType: Copy + Default + 'static {
+pub trait StaticRasterType: Copy + Default + PartialEq + 'static {
     const TYPE: RasterType;
+    /// The value used to mark missing data if a raster does not specify one.
+    const NO_DATA: Self;
 }
 
 impl StaticRasterType for u8 {
     const TYPE: RasterType = RasterType::U8;
+    const NO_DATA: Self = u8::MAX;
 }
 
 impl StaticRasterType for u16 {
     const TYPE: RasterType = RasterType::U16;
+    const NO_DATA: Self = u16::MAX;
 }
 
 impl StaticRasterType for u32 {
     const TYPE: RasterType = RasterType::U32;
+    const NO_DATA: Self = u32::MAX;
 }
 
 impl StaticRasterType for u64 {
     const TYPE: RasterType = RasterType::U64;
+    const NO_DATA: Self = u64::MAX;
 }
 
 impl StaticRasterType for i16 {
     const TYPE: RasterType = RasterType::I16;
+    const NO_DATA: Self = i16::MIN;
 }
 
 impl StaticRasterType for i32 {
     const TYPE: RasterType = RasterType::I32;
+    const NO_DATA: Self = i32::MIN;
 }
 
 impl StaticRasterType for i64 {
     const TYPE: RasterType = RasterType::I64;
+    const NO_DATA: Self = i64::MIN;
 }
 
 impl StaticRasterType for f32 {
     const TYPE: RasterType = RasterType::F32;
+    const NO_DATA: Self = f32::NAN;
 }
 
 impl StaticRasterType for f64 {
     const TYPE: RasterType = RasterType::F64;
+    const NO_DATA: Self = f64::NAN;
+}
+
+/// Pixel-wise arithmetic of the raster types. `None` marks results that cannot be represented,
+/// e.g. integer overflows, divisions by zero or `NaN`s, which become no-data.
+pub trait ArithmeticPixel: StaticRasterType + PartialOrd + Zero + One + NumCast {
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    /// integer divisions truncate towards zero
+    fn checked_div(self, other: Self) -> Option<Self>;
+    /// integers can only be raised to non-negative integer powers
+    fn checked_pow(self, exponent: Self) -> Option<Self>;
+    /// floats do not saturate, they become infinite
+    fn saturating_add(self, other: Self) -> Self;
+    fn saturating_sub(self, other: Self) -> Self;
+    fn saturating_mul(self, other: Self) -> Self;
+    fn saturating_pow(self, exponent: Self) -> Option<Self>;
+    /// floats do not wrap, they become infinite
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+    fn wrapping_pow(self, exponent: Self) -> Option<Self>;
+}
+
+/// What arithmetic operators do with integer results that do not fit into the pixel type.
+/// The behavior is the same in every build profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum OverflowPolicy {
+    /// clamps to the smallest or largest value of the type
+    Saturate,
+    /// wraps around like two's complement arithmetic
+    Wrap,
+    /// yields a no-data pixel
+    #[default]
+    NoData,
+    /// computes in the next larger type of the same kind, e.g. U8 -> U16.
+    /// Results that overflow even the larger type yield no-data pixels.
+    /// Only the meta operators convert their sources to the larger type,
+    /// the processing operators treat `Promote` like `NoData`.
+    Promote,
+}
+
+impl OverflowPolicy {
+    /// the type an operator produces for pixels of type `input_type`
+    pub fn output_type(self, input_type: RasterType) -> Result<RasterType> {
+        match self {
+            OverflowPolicy::Promote => input_type
+                .upgraded()
+                .ok_or(Error::CannotUpgrade(input_type)),
+            _ => Ok(input_type),
+        }
+    }
+
+    pub fn add<T: ArithmeticPixel>(self, a: T, b: T) -> Option<T> {
+        match self {
+            OverflowPolicy::Saturate => Some(a.saturating_add(b)),
+            OverflowPolicy::Wrap => Some(a.wrapping_add(b)),
+            OverflowPolicy::NoData | OverflowPolicy::Promote => a.checked_add(b),
+        }
+    }
+
+    pub fn sub<T: ArithmeticPixel>(self, a: T, b: T) -> Option<T> {
+        match self {
+            OverflowPolicy::Saturate => Some(a.saturating_sub(b)),
+            OverflowPolicy::Wrap => Some(a.wrapping_sub(b)),
+            OverflowPolicy::NoData | OverflowPolicy::Promote => a.checked_sub(b),
+        }
+    }
+
+    pub fn mul<T: ArithmeticPixel>(self, a: T, b: T) -> Option<T> {
+        match self {
+            OverflowPolicy::Saturate => Some(a.saturating_mul(b)),
+            OverflowPolicy::Wrap => Some(a.wrapping_mul(b)),
+            OverflowPolicy::NoData | OverflowPolicy::Promote => a.checked_mul(b),
+        }
+    }
+
+    pub fn pow<T: ArithmeticPixel>(self, base: T, exponent: T) -> Option<T> {
+        match self {
+            OverflowPolicy::Saturate => base.saturating_pow(exponent),
+            OverflowPolicy::Wrap => base.wrapping_pow(exponent),
+            OverflowPolicy::NoData | OverflowPolicy::Promote => base.checked_pow(exponent),
+        }
+    }
+}
+
+macro_rules! impl_integer_arithmetic {
+    ($($t:ty),*) => {
+        $(
+            impl ArithmeticPixel for $t {
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$t>::checked_add(self, other)
+                }
+
+                fn checked_sub(self, other: Self) -> Option<Self> {
+                    <$t>::checked_sub(self, other)
+                }
+
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, other)
+                }
+
+                fn checked_div(self, other: Self) -> Option<Self> {
+                    <$t>::checked_div(self, other)
+                }
+
+                fn checked_pow(self, exponent: Self) -> Option<Self> {
+                    u32::try_from(exponent)
+                        .ok()
+                        .and_then(|exponent| <$t>::checked_pow(self, exponent))
+                }
+
+                fn saturating_add(self, other: Self) -> Self {
+                    <$t>::saturating_add(self, other)
+                }
+
+                fn saturating_sub(self, other: Self) -> Self {
+                    <$t>::saturating_sub(self, other)
+                }
+
+                fn saturating_mul(self, other: Self) -> Self {
+                    <$t>::saturating_mul(self, other)
+                }
+
+                fn saturating_pow(self, exponent: Self) -> Option<Self> {
+                    u32::try_from(exponent)
+                        .ok()
+                        .map(|exponent| <$t>::saturating_pow(self, exponent))
+                }
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$t>::wrapping_add(self, other)
+                }
+
+                fn wrapping_sub(self, other: Self) -> Self {
+                    <$t>::wrapping_sub(self, other)
+                }
+
+                fn wrapping_mul(self, other: Self) -> Self {
+                    <$t>::wrapping_mul(self, other)
+                }
+
+                fn wrapping_pow(self, exponent: Self) -> Option<Self> {
+                    u32::try_from(exponent)
+                        .ok()
+                        .map(|exponent| <$t>::wrapping_pow(self, exponent))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_float_arithmetic {
+    ($($t:ty),*) => {
+        $(
+            impl ArithmeticPixel for $t {
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    Some(self + other).filter(|result| !result.is_nan())
+                }
+
+                fn checked_sub(self, other: Self) -> Option<Self> {
+                    Some(self - other).filter(|result| !result.is_nan())
+                }
+
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    Some(self * other).filter(|result| !result.is_nan())
+                }
+
+                fn checked_div(self, other: Self) -> Option<Self> {
+                    if other == 0. {
+                        return None;
+                    }
+                    Some(self / other).filter(|result| !result.is_nan())
+                }
+
+                fn checked_pow(self, exponent: Self) -> Option<Self> {
+                    Some(self.powf(exponent)).filter(|result| !result.is_nan())
+                }
+
+                fn saturating_add(self, other: Self) -> Self {
+                    self + other
+                }
+
+                fn saturating_sub(self, other: Self) -> Self {
+                    self - other
+                }
+
+                fn saturating_mul(self, other: Self) -> Self {
+                    self * other
+                }
+
+                fn saturating_pow(self, exponent: Self) -> Option<Self> {
+                    ArithmeticPixel::checked_pow(self, exponent)
+                }
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    self + other
+                }
+
+                fn wrapping_sub(self, other: Self) -> Self {
+                    self - other
+                }
+
+                fn wrapping_mul(self, other: Self) -> Self {
+                    self * other
+                }
+
+                fn wrapping_pow(self, exponent: Self) -> Option<Self> {
+                    ArithmeticPixel::checked_pow(self, exponent)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_arithmetic!(u8, u16, u32, u64, i16, i32, i64);
+impl_float_arithmetic!(f32, f64);
+
+/// Generates the code that dispatches between the `RasterType`s and their primitive types.
+///
+/// This is the only place that lists all raster types. The modes are
+/// * `boxed_instance`: the `BoxedRasterOperatorInstance` enum and its conversions
+/// * `declare_create_ops`: the `create_<type>_raster_op` methods of `MetaRasterOperator`
+/// * `dispatch_create_op: self`: calls the `create_<type>_raster_op` method for `self.creates_type()`
+/// * `create_ops: method`: implements all `create_<type>_raster_op` methods by calling the generic `self.method::<T>()`
+/// * `match_instance: instance, source => expression`: evaluates the expression for the typed operator in a `BoxedRasterOperatorInstance`
+/// * `match_type: raster_type, T => expression`: evaluates the expression with `T` as the primitive type of a `RasterType`
+#[macro_export]
+macro_rules! dispatch_raster_types {
+    ($mode:ident $(: $($args:tt)*)?) => {
+        $crate::dispatch_raster_types! {
+            @$mode [$($($args)*)?]
+            U8: u8 => create_u8_raster_op,
+            U16: u16 => create_u16_raster_op,
+            U32: u32 => create_u32_raster_op,
+            U64: u64 => create_u64_raster_op,
+            I16: i16 => create_i16_raster_op,
+            I32: i32 => create_i32_raster_op,
+            I64: i64 => create_i64_raster_op,
+            F32: f32 => create_f32_raster_op,
+            F64: f64 => create_f64_raster_op
+        }
+    };
+
+    (@boxed_instance [] $($variant:ident: $t:ty => $create:ident),*) => {
+        /// A typed raster operator in a box. It is the result of creating a `MetaRasterOperator`.
+        pub enum BoxedRasterOperatorInstance {
+            $($variant(Box<dyn $crate::RasterSource<RasterType = $t>>),)*
+        }
+
+        impl BoxedRasterOperatorInstance {
+            /// the type of the rasters the operator produces
+            pub fn raster_type(&self) -> $crate::RasterType {
+                match self {
+                    $(BoxedRasterOperatorInstance::$variant(_) => $crate::RasterType::$variant,)*
+                }
+            }
+        }
+
+        $(
+            impl $crate::BoxedRasterType for $t {
+                fn into_boxed_instance(
+                    source: Box<dyn $crate::RasterSource<RasterType = Self>>,
+                ) -> BoxedRasterOperatorInstance {
+                    BoxedRasterOperatorInstance::$variant(source)
+                }
+
+                fn convert_boxed_instance(
+                    instance: BoxedRasterOperatorInstance,
+                ) -> Box<dyn $crate::RasterSource<RasterType = Self>> {
+                    match instance {
+                        BoxedRasterOperatorInstance::$variant(source) => source,
+                        other => $crate::dispatch_raster_types!(match_instance: other, source => {
+                            Box::new($crate::ConvertRasterOperator::<_, $t>::new(source))
+                        }),
+                    }
+                }
+
+                fn from_boxed_instance(
+                    instance: BoxedRasterOperatorInstance,
+                ) -> $crate::Result<Box<dyn $crate::RasterSource<RasterType = Self>>> {
+                    match instance {
+                        BoxedRasterOperatorInstance::$variant(source) => Ok(source),
+                        other => Err($crate::Error::RasterTypeMismatch {
+                            expected: $crate::RasterType::$variant,
+                            found: other.raster_type(),
+                        }),
+                    }
+                }
+            }
+        )*
+    };
+
+    (@declare_create_ops [] $($variant:ident: $t:ty => $create:ident),*) => {
+        $(
+            fn $create(&self) -> $crate::Result<Box<dyn $crate::RasterSource<RasterType = $t>>>;
+        )*
+    };
+
+    (@dispatch_create_op [$this:ident] $($variant:ident: $t:ty => $create:ident),*) => {
+        match $this.creates_type() {
+            $(Ok($crate::RasterType::$variant) => $this
+                .$create()
+                .map($crate::BoxedRasterOperatorInstance::$variant),)*
+            Err(error) => Err(error),
+        }
+    };
+
+    (@create_ops [$method:ident] $($variant:ident: $t:ty => $create:ident),*) => {
+        $(
+            fn $create(&self) -> $crate::Result<Box<dyn $crate::RasterSource<RasterType = $t>>> {
+                self.$method::<$t>()
+            }
+        )*
+    };
+
+    (@match_instance [$instance:expr, $source:ident => $body:expr] $($variant:ident: $t:ty => $create:ident),*) => {
+        match $instance {
+            $($crate::BoxedRasterOperatorInstance::$variant($source) => $body,)*
+        }
+    };
+
+    (@match_type [$raster_type:expr, $alias:ident => $body:expr] $($variant:ident: $t:ty => $create:ident),*) => {
+        match $raster_type {
+            $($crate::RasterType::$variant => {
+                type $alias = $t;
+                $body
+            })*
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn promotion() {
+        use RasterType::*;
+
+        assert_eq!(U8.promoted(U8), U8);
+        assert_eq!(U8.promoted(U16), U16);
+        assert_eq!(U16.promoted(U8), U16);
+        assert_eq!(U8.promoted(I16), I16);
+        assert_eq!(U16.promoted(I16), I32);
+        assert_eq!(I16.promoted(U32), I64);
+        assert_eq!(U8.promoted(F32), F32);
+        assert_eq!(I16.promoted(F32), F32);
+        assert_eq!(U32.promoted(F32), F64);
+        assert_eq!(F32.promoted(F64), F64);
+        assert_eq!(U64.promoted(I64), F64);
+    }
+
+    #[test]
+    fn overflow_policies() {
+        assert_eq!(OverflowPolicy::Saturate.add(255u8, 1), Some(255));
+        assert_eq!(OverflowPolicy::Wrap.add(255u8, 1), Some(0));
+        assert_eq!(OverflowPolicy::NoData.add(255u8, 1), None);
+        assert_eq!(
+            OverflowPolicy::Saturate.sub(-30000i16, 10000),
+            Some(i16::MIN)
+        );
+        assert_eq!(OverflowPolicy::Wrap.mul(16u8, 16), Some(0));
+        assert_eq!(OverflowPolicy::Saturate.pow(2u16, 20), Some(u16::MAX));
+        assert_eq!(OverflowPolicy::Wrap.pow(2i32, -1), None);
+        assert_eq!(
+            OverflowPolicy::Saturate.add(f32::MAX, f32::MAX),
+            Some(f32::INFINITY)
+        );
+
+        assert_eq!(
+            OverflowPolicy::Promote.output_type(RasterType::U8).unwrap(),
+            RasterType::U16
+        );
+        assert_eq!(
+            OverflowPolicy::Wrap.output_type(RasterType::U8).unwrap(),
+            RasterType::U8
+        );
+        assert!(matches!(
+            OverflowPolicy::Promote.output_type(RasterType::I64),
+            Err(Error::CannotUpgrade(RasterType::I64))
+        ));
+    }
 }
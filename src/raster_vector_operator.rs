@@ -1,42 +1,152 @@
 use crate::{
-    primitives::{Raster, VectorData},
-    source::{Query, Source},
-    MetaOperator, MetaRasterOperator, MetaVectorOperator,
+    attributes::{AttributeColumn, AttributeSchema, AttributeType},
+    error::{Error, Result},
+    feature_collection::{
+        FeatureCollection, Geometries, MultiLineStringCollection, MultiPointCollection,
+        MultiPolygonCollection, VectorCollectionType,
+    },
+    primitives::{Raster, Sampling},
+    source::{BoxedVectorType, OperatorParams, Query, Source, VectorSource},
+    MetaOperator, MetaRasterOperator, MetaVectorOperator, RasterWants,
 };
-
+use num_traits::AsPrimitive;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// An Operator consuming a Raster and a Vector!
+/// It samples a band of the raster at the center of each feature and appends the values as a `Float` attribute `column`.
+/// Features outside of the raster or on no-data pixels get a null value.
 #[derive(Debug, Clone)]
 pub struct RasterVectorOperator<R, V> {
     pub sources: (R, V),
+    pub params: RasterVectorParams,
+}
+
+/// The parameters of the `RasterVectorOperator`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct RasterVectorParams {
+    /// the name of the attribute holding the raster values
+    #[serde(default = "RasterVectorParams::default_column")]
+    pub column: String,
+    #[serde(default)]
+    pub sampling: Sampling,
+    /// the band of the raster that is sampled
+    #[serde(default)]
+    pub band: usize,
+}
+
+impl RasterVectorParams {
+    fn default_column() -> String {
+        "raster_value".to_string()
+    }
+}
+
+impl Default for RasterVectorParams {
+    fn default() -> Self {
+        RasterVectorParams {
+            column: Self::default_column(),
+            sampling: Sampling::default(),
+            band: 0,
+        }
+    }
+}
+
+impl OperatorParams for RasterVectorParams {
+    fn validate(&self) -> Result<()> {
+        if self.column.is_empty() {
+            return Err(Error::InvalidParameter {
+                name: "column".to_string(),
+                reason: "the attribute name must not be empty".to_string(),
+            });
+        }
+        Ok(())
+    }
 }
 
 // It is a Source producing Vector data. So it is a VectorSource
-impl<RD, VD, R, V> Source for RasterVectorOperator<R, V>
+impl<RD, G, R, V> Source for RasterVectorOperator<R, V>
 where
     R: Source<Output = Raster<RD>>,
-    V: Source<Output = VD>,
-    VD: VectorData,
+    V: Source<Output = FeatureCollection<G>>,
+    RD: AsPrimitive<f64> + PartialEq,
+    G: Geometries,
 {
-    type Output = VD;
-    fn query(&self, query: Query) -> Self::Output {
+    type Output = FeatureCollection<G>;
+    fn query(&self, query: Query) -> Result<Self::Output> {
         println!("RasterVectorOperator query");
-        self.sources.0.query(query.clone());
-        self.sources.1.query(query)
+        let raster = self.sources.0.query(query)?;
+        let collection = self.sources.1.query(query)?;
+
+        let values = (0..collection.geometries.len())
+            .map(|feature| {
+                collection
+                    .geometries
+                    .feature_center(feature)
+                    .and_then(|center| {
+                        raster.sample(self.params.band, center, self.params.sampling)
+                    })
+            })
+            .collect();
+        collection.with_attribute(&self.params.column, AttributeColumn::Float(values))
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Adds the raster value at each feature as an attribute column.
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct MetaRasterVectorOperator {
+    #[schemars(skip)]
     pub raster_sources: Vec<Box<dyn MetaRasterOperator>>,
+    #[schemars(skip)]
     pub vector_sources: Vec<Box<dyn MetaVectorOperator>>,
+    #[serde(flatten)]
+    pub params: RasterVectorParams,
+}
+
+impl MetaRasterVectorOperator {
+    pub const REQUIRES_TYPES: [RasterWants; 1] = [RasterWants::Any];
+
+    /// fails unless the raster source has the sampled band
+    fn check_band(&self) -> Result<()> {
+        let bands = self.raster_source_bands(0)?;
+        if self.params.band >= bands {
+            return Err(Error::InvalidParameter {
+                name: "band".to_string(),
+                reason: format!("the raster source has no band {}", self.params.band),
+            });
+        }
+        Ok(())
+    }
+
+    fn create_typed_vector_op<G>(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = FeatureCollection<G>>>>
+    where
+        G: Geometries + 'static,
+        FeatureCollection<G>: BoxedVectorType,
+    {
+        self.params.validate()?;
+        self.check_band()?;
+        let raster_source = self.create_raster_source(0)?;
+        let vector_source = self
+            .create_vector_source(0)?
+            .get::<FeatureCollection<G>>()?;
+        let operator: Box<dyn VectorSource<VectorType = FeatureCollection<G>>> = crate::dispatch_raster_types!(match_instance: raster_source, raster => {
+            Box::new(RasterVectorOperator {
+                sources: (raster, vector_source),
+                params: self.params.clone(),
+            })
+        });
+        Ok(operator)
+    }
 }
 
 impl MetaOperator for MetaRasterVectorOperator {
     fn raster_sources(&self) -> &[Box<dyn crate::MetaRasterOperator>] {
         &self.raster_sources
     }
+    fn raster_wants(&self) -> &[RasterWants] {
+        &Self::REQUIRES_TYPES
+    }
     fn vector_sources(&self) -> &[Box<dyn MetaVectorOperator>] {
         &self.vector_sources
     }
@@ -44,22 +154,131 @@ impl MetaOperator for MetaRasterVectorOperator {
 
 #[typetag::serde]
 impl MetaVectorOperator for MetaRasterVectorOperator {
-    fn creates_collection_type(&self) -> () {
-        ()
-    }
-    fn create_point_op(&self) -> Box<dyn crate::VectorSource<VectorType = crate::Point>> {
-        let raster_source = self.raster_sources()[0].create_raster_op();
-        let vector_source = self.vector_sources()[0].create_vector_op();
-        match (raster_source, vector_source) {
-            (
-                crate::BoxedRasterOperatorInstance::U8(r),
-                crate::BoxedVectorOperatorInstance::Points(p),
-            ) => Box::new(RasterVectorOperator { sources: (r, p) }),
-            (
-                crate::BoxedRasterOperatorInstance::U16(r),
-                crate::BoxedVectorOperatorInstance::Points(p),
-            ) => Box::new(RasterVectorOperator { sources: (r, p) }),
-            _ => panic!(),
+    fn creates_collection_type(&self) -> Result<VectorCollectionType> {
+        self.vector_source_collection_type(0)
+    }
+
+    fn attribute_schema(&self) -> Result<AttributeSchema> {
+        self.params.validate()?;
+        self.check_band()?;
+        self.vector_source_schema(0)?
+            .with_column(&self.params.column, AttributeType::Float)
+    }
+
+    fn create_multi_point_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiPointCollection>>> {
+        self.create_typed_vector_op()
+    }
+
+    fn create_multi_line_string_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiLineStringCollection>>> {
+        self.create_typed_vector_op()
+    }
+
+    fn create_multi_polygon_op(
+        &self,
+    ) -> Result<Box<dyn VectorSource<VectorType = MultiPolygonCollection>>> {
+        self.create_typed_vector_op()
+    }
+}
+
+crate::register_operator! {
+    MetaRasterVectorOperator: Vector
+        .with_raster_sources(&MetaRasterVectorOperator::REQUIRES_TYPES)
+        .with_vector_sources(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        attributes::AttributeValue,
+        test_util::{query_area, write_test_geotiff},
+        validate_vector_graph, GridShape, MetaGdalSource, MetaMyVectorSourceOperator,
+    };
+
+    fn operator(column: &str, raster_name: &str) -> MetaRasterVectorOperator {
+        MetaRasterVectorOperator {
+            raster_sources: vec![Box::new(MetaGdalSource::new(write_test_geotiff(
+                raster_name,
+                GridShape::new(20, 20),
+                (0..400i32).collect(),
+            )))],
+            vector_sources: vec![Box::new(MetaMyVectorSourceOperator {})],
+            params: RasterVectorParams {
+                column: column.to_string(),
+                sampling: Sampling::Nearest,
+                band: 0,
+            },
         }
     }
+
+    #[test]
+    fn attaches_raster_values() {
+        let operator = operator("elevation", "raster_vector_attaches_values_i32.tif");
+        assert_eq!(
+            operator
+                .attribute_schema()
+                .unwrap()
+                .attribute_type("elevation"),
+            Some(AttributeType::Float)
+        );
+
+        let source = operator
+            .create_vector_op()
+            .unwrap()
+            .get::<MultiPointCollection>()
+            .unwrap();
+        let collection = source.vector_query(query_area(20., 20.)).unwrap();
+
+        // the point (12, 13) lies in column 12 and row 20 - 13 = 7
+        assert_eq!(
+            collection.attribute_value("elevation", 0),
+            Some(AttributeValue::Float(7. * 20. + 12.))
+        );
+    }
+
+    #[test]
+    fn samples_the_selected_band() {
+        let mut operator = operator("value", "raster_vector_band_i32.tif");
+        operator.raster_sources = vec![Box::new(MetaGdalSource::new(
+            crate::test_util::write_test_geotiff_bands(
+                "raster_vector_bands_i32.tif",
+                GridShape::new(20, 20),
+                2,
+                (0..800i32).collect(),
+            ),
+        ))];
+        operator.params.band = 1;
+        let collection = operator
+            .create_vector_op()
+            .unwrap()
+            .get::<MultiPointCollection>()
+            .unwrap()
+            .vector_query(query_area(20., 20.))
+            .unwrap();
+        assert_eq!(
+            collection.attribute_value("value", 0),
+            Some(AttributeValue::Float(400. + 7. * 20. + 12.))
+        );
+
+        operator.params.band = 2;
+        assert_eq!(
+            validate_vector_graph(&operator).unwrap_err().to_string(),
+            "the operator graph is invalid: MetaRasterVectorOperator: \
+             the parameter band is invalid: the raster source has no band 2"
+        );
+    }
+
+    #[test]
+    fn rejects_existing_columns() {
+        let operator = operator("name", "raster_vector_rejects_existing_columns.tif");
+        let error = validate_vector_graph(&operator).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the operator graph is invalid: MetaRasterVectorOperator: the attribute name already exists"
+        );
+    }
 }
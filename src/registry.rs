@@ -0,0 +1,250 @@
+use crate::{meta_raster_operator::RasterWants, workflow::WorkflowNodeKind};
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
+use serde::Serialize;
+
+/// The registration of a `MetaRasterOperator` or `MetaVectorOperator`.
+/// Every operator submits one with `register_operator!` next to its `#[typetag::serde]` implementation.
+pub struct OperatorRegistration {
+    /// the `type` of the operator in JSON workflows
+    pub name: &'static str,
+    pub output: WorkflowNodeKind,
+    /// what the operator requires at each raster input
+    pub raster_sources: &'static [RasterWants],
+    /// the number of raster inputs that must be connected. Further inputs are optional.
+    pub required_raster_sources: usize,
+    /// what the operator requires at any number of raster inputs after `raster_sources` or `None` if it takes no more
+    pub variadic_raster_sources: Option<RasterWants>,
+    pub vector_sources: usize,
+    params_schema: fn() -> RootSchema,
+}
+
+inventory::collect!(OperatorRegistration);
+
+impl OperatorRegistration {
+    /// a registration without sources. The parameters are the fields of `O` that are not skipped by serde or schemars.
+    pub const fn new<O: JsonSchema>(name: &'static str, output: WorkflowNodeKind) -> Self {
+        OperatorRegistration {
+            name,
+            output,
+            raster_sources: &[],
+            required_raster_sources: 0,
+            variadic_raster_sources: None,
+            vector_sources: 0,
+            params_schema: params_schema::<O>,
+        }
+    }
+
+    /// all of the raster inputs are required
+    pub const fn with_raster_sources(self, raster_sources: &'static [RasterWants]) -> Self {
+        self.with_optional_raster_sources(raster_sources, raster_sources.len())
+    }
+
+    /// only the first `required` raster inputs are required, e.g. for operators that take a list of sources
+    pub const fn with_optional_raster_sources(
+        self,
+        raster_sources: &'static [RasterWants],
+        required: usize,
+    ) -> Self {
+        OperatorRegistration {
+            raster_sources,
+            required_raster_sources: required,
+            ..self
+        }
+    }
+
+    /// the raster inputs are required and followed by any number of inputs that require `variadic`,
+    /// e.g. for operators that stack their sources
+    pub const fn with_variadic_raster_sources(
+        self,
+        raster_sources: &'static [RasterWants],
+        variadic: RasterWants,
+    ) -> Self {
+        OperatorRegistration {
+            variadic_raster_sources: Some(variadic),
+            ..self.with_raster_sources(raster_sources)
+        }
+    }
+
+    pub const fn with_vector_sources(self, vector_sources: usize) -> Self {
+        OperatorRegistration {
+            vector_sources,
+            ..self
+        }
+    }
+
+    /// the description is the first paragraph of the doc comment of the operator
+    pub fn describe(&self) -> OperatorDescription {
+        let params = (self.params_schema)();
+        let description = params
+            .schema
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.description.as_deref())
+            .and_then(|description| description.split("\n\n").next())
+            .unwrap_or_default()
+            .to_string();
+        OperatorDescription {
+            name: self.name,
+            description,
+            output: self.output,
+            raster_sources: self.raster_sources.to_vec(),
+            required_raster_sources: self.required_raster_sources,
+            variadic_raster_sources: self.variadic_raster_sources,
+            vector_sources: self.vector_sources,
+            params,
+        }
+    }
+}
+
+/// Registers an operator under its type name, which is also its `type` in JSON workflows.
+/// The sources are added with the builder methods of `OperatorRegistration`:
+///
+/// ```ignore
+/// crate::register_operator! {
+///     MetaCastOperator: Raster
+///         .with_raster_sources(&MetaCastOperator::REQUIRES_TYPES)
+/// }
+/// ```
+#[macro_export]
+macro_rules! register_operator {
+    ($operator:ident: $output:ident $($sources:tt)*) => {
+        inventory::submit! {
+            $crate::OperatorRegistration::new::<$operator>(
+                stringify!($operator),
+                $crate::WorkflowNodeKind::$output,
+            )
+            $($sources)*
+        }
+    };
+}
+
+fn params_schema<O: JsonSchema>() -> RootSchema {
+    schema_for!(O)
+}
+
+/// A registered operator as a client sees it, e.g. to build a workflow editor.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorDescription {
+    pub name: &'static str,
+    pub description: String,
+    pub output: WorkflowNodeKind,
+    pub raster_sources: Vec<RasterWants>,
+    pub required_raster_sources: usize,
+    pub variadic_raster_sources: Option<RasterWants>,
+    pub vector_sources: usize,
+    /// the JSON schema of the operator without its `type` and sources
+    pub params: RootSchema,
+}
+
+/// all registered operators sorted by name
+pub fn registered_operators() -> Vec<OperatorDescription> {
+    let mut operators: Vec<OperatorDescription> = inventory::iter::<OperatorRegistration>
+        .into_iter()
+        .map(OperatorRegistration::describe)
+        .collect();
+    operators.sort_by_key(|operator| operator.name);
+    operators
+}
+
+/// the registered operator with this name
+pub fn registered_operator(name: &str) -> Option<OperatorDescription> {
+    inventory::iter::<OperatorRegistration>
+        .into_iter()
+        .find(|registration| registration.name == name)
+        .map(OperatorRegistration::describe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MetaRasterOperator, MetaVectorOperator};
+
+    #[test]
+    fn registers_all_operators() {
+        let operators = registered_operators();
+        let names: Vec<&str> = operators.iter().map(|operator| operator.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "MetaAddConstantOperator",
+                "MetaAddRasterOperator",
+                "MetaBandSelectOperator",
+                "MetaBandStackOperator",
+                "MetaBinaryRasterOperator",
+                "MetaCastOperator",
+                "MetaCsvPointSource",
+                "MetaExpressionOperator",
+                "MetaGdalSource",
+                "MetaGeoJsonSource",
+                "MetaMyVectorSourceOperator",
+                "MetaNoopOperator",
+                "MetaPlusOneOperator",
+                "MetaRasterNode",
+                "MetaRasterVectorOperator",
+                "MetaVectorNode",
+            ]
+        );
+
+        // the names are the tags typetag deserializes
+        for operator in &operators {
+            assert!(
+                !operator.description.is_empty(),
+                "{} has no doc comment",
+                operator.name
+            );
+            let json = serde_json::json!({ "type": operator.name });
+            let error = match operator.output {
+                WorkflowNodeKind::Raster => {
+                    serde_json::from_value::<Box<dyn MetaRasterOperator>>(json).err()
+                }
+                WorkflowNodeKind::Vector => {
+                    serde_json::from_value::<Box<dyn MetaVectorOperator>>(json).err()
+                }
+            };
+            if let Some(error) = error {
+                assert!(
+                    !error.to_string().contains("unknown variant"),
+                    "{}: {}",
+                    operator.name,
+                    error
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn describes_parameters() {
+        let cast = registered_operator("MetaCastOperator").unwrap();
+        assert_eq!(cast.output, WorkflowNodeKind::Raster);
+        assert_eq!(cast.raster_sources, vec![RasterWants::Any]);
+        assert_eq!(cast.required_raster_sources, 1);
+        assert_eq!(
+            cast.description,
+            "Converts a raster to another type with optional scaling, rounding and clamping."
+        );
+
+        let schema = serde_json::to_value(&cast.params).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        let mut names: Vec<&String> = properties.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["clamp", "output_type", "rounding", "scale"]);
+        assert_eq!(schema["required"], serde_json::json!(["output_type"]));
+        assert_eq!(properties["clamp"]["default"], true);
+
+        let expression = registered_operator("MetaExpressionOperator").unwrap();
+        assert_eq!(expression.raster_sources.len(), 26);
+        assert_eq!(expression.required_raster_sources, 1);
+        assert_eq!(expression.variadic_raster_sources, None);
+
+        let stack = registered_operator("MetaBandStackOperator").unwrap();
+        assert_eq!(stack.raster_sources, vec![RasterWants::Any]);
+        assert_eq!(stack.required_raster_sources, 1);
+        assert_eq!(stack.variadic_raster_sources, Some(RasterWants::Any));
+
+        let raster_vector = registered_operator("MetaRasterVectorOperator").unwrap();
+        assert_eq!(raster_vector.output, WorkflowNodeKind::Vector);
+        assert_eq!(raster_vector.vector_sources, 1);
+
+        assert!(registered_operator("MetaUnknownOperator").is_none());
+    }
+}
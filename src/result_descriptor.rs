@@ -0,0 +1,320 @@
+use crate::{
+    attributes::AttributeSchema,
+    feature_collection::VectorCollectionType,
+    primitives::{BoundingBox2D, SpatialReference, SpatialResolution, TimeInterval},
+    raster_type::RasterType,
+};
+use serde::{Deserialize, Serialize};
+
+/// Describes the rasters a `MetaRasterOperator` creates. It is derived from the sources without running the graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RasterResultDescriptor {
+    pub data_type: RasterType,
+    pub bands: usize,
+    /// the area covered by the rasters
+    pub bbox: BoundingBox2D,
+    /// the native pixel size. Sources resample the pixels to the query resolution with nearest neighbour.
+    pub resolution: SpatialResolution,
+    pub spatial_reference: Option<SpatialReference>,
+    /// the time range of the data or `None` if it does not change over time
+    pub time: Option<TimeInterval>,
+    /// the preferred value to mark missing pixels or `None` if all pixels are valid.
+    /// A result marks them with another value if a valid pixel takes this one, so use the `no_data_value` of each `Raster`.
+    #[serde(with = "no_data_value")]
+    pub no_data_value: Option<f64>,
+}
+
+/// Describes the collections a `MetaVectorOperator` creates. It is derived from the sources without running the graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorResultDescriptor {
+    pub collection_type: VectorCollectionType,
+    pub schema: AttributeSchema,
+    /// the area covered by the features or `None` if it is only known after reading them
+    pub bbox: Option<BoundingBox2D>,
+    pub spatial_reference: Option<SpatialReference>,
+    /// the time range of the features or `None` if they do not change over time or it is only known after reading them
+    pub time: Option<TimeInterval>,
+}
+
+impl VectorResultDescriptor {
+    /// a descriptor without extent, spatial reference and time
+    pub fn new(collection_type: VectorCollectionType, schema: AttributeSchema) -> Self {
+        VectorResultDescriptor {
+            collection_type,
+            schema,
+            bbox: None,
+            spatial_reference: None,
+            time: None,
+        }
+    }
+}
+
+/// `NaN` is no JSON number, so it is written as the string `"NaN"`.
+mod no_data_value {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NoDataValue {
+        Number(f64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) if value.is_nan() => serializer.serialize_some("NaN"),
+            _ => value.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<f64>, D::Error> {
+        match Option::<NoDataValue>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(NoDataValue::Number(value)) => Ok(Some(value)),
+            Some(NoDataValue::Text(text)) if text == "NaN" => Ok(Some(f64::NAN)),
+            Some(NoDataValue::Text(text)) => Err(D::Error::custom(format!(
+                "{} is not a valid no-data value",
+                text
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{temp_path, write_test_geotiff, write_test_geotiff_bands},
+        write_geotiff, AddRasterParams, AttributeType, Coordinate2D, GeoTransform, GridShape,
+        MetaAddRasterOperator, MetaBandSelectOperator, MetaBandStackOperator, MetaGdalSource,
+        MetaMyVectorSourceOperator, MetaNoopOperator, MetaRasterOperator, MetaRasterVectorOperator,
+        MetaVectorOperator, Raster, RasterVectorParams, StaticRasterType,
+    };
+
+    #[test]
+    fn describes_raster_graphs() {
+        let dataset = write_test_geotiff_bands(
+            "descriptor_bands_u8.tif",
+            GridShape::new(2, 2),
+            3,
+            (0..12).collect::<Vec<u8>>(),
+        );
+        let source = MetaGdalSource::new(dataset.clone())
+            .result_descriptor()
+            .unwrap();
+        assert_eq!(
+            source,
+            RasterResultDescriptor {
+                data_type: RasterType::U8,
+                bands: 3,
+                bbox: BoundingBox2D::new(Coordinate2D::new(0., 0.), Coordinate2D::new(2., 2.))
+                    .unwrap(),
+                resolution: SpatialResolution::new_square(1.).unwrap(),
+                spatial_reference: Some(SpatialReference::epsg(32632)),
+                time: None,
+                no_data_value: None,
+            }
+        );
+
+        // selecting bands keeps the grid and the no-data value
+        let selection: Box<dyn MetaRasterOperator> = serde_json::from_value(serde_json::json!({
+            "type": "MetaBandSelectOperator",
+            "bands": [2],
+            "sources": [{"type": "MetaNoopOperator", "sources": [{"type": "MetaGdalSource", "dataset": dataset}]}]
+        }))
+        .unwrap();
+        assert_eq!(
+            selection.result_descriptor().unwrap(),
+            RasterResultDescriptor {
+                bands: 1,
+                ..source.clone()
+            }
+        );
+
+        // arithmetic marks overflows with the no-data value of the created type
+        let plus_one: Box<dyn MetaRasterOperator> = serde_json::from_value(serde_json::json!({
+            "type": "MetaPlusOneOperator",
+            "overflow": "Promote",
+            "sources": [{"type": "MetaGdalSource", "dataset": dataset}]
+        }))
+        .unwrap();
+        assert_eq!(
+            plus_one.result_descriptor().unwrap(),
+            RasterResultDescriptor {
+                data_type: RasterType::U16,
+                no_data_value: Some(u16::NO_DATA.into()),
+                ..source
+            }
+        );
+    }
+
+    #[test]
+    fn describes_stacks_of_sources_with_no_data() {
+        let first = write_test_geotiff(
+            "descriptor_stack_i16.tif",
+            GridShape::new(2, 2),
+            vec![1i16, 2, 3, 4],
+        );
+        let second = temp_path("descriptor_stack_f32.tif");
+        let raster = Raster::new(
+            GridShape::new(2, 2),
+            GeoTransform::new_north_up(
+                Coordinate2D::new(0., 2.),
+                SpatialResolution::new_square(1.).unwrap(),
+            ),
+            vec![1f32, f32::NAN, 3., 4.],
+            Some(f32::NAN),
+        );
+        write_geotiff(&second, &raster, Some(SpatialReference::epsg(32632))).unwrap();
+        let second = second.to_string_lossy().into_owned();
+        let stack = MetaBandStackOperator {
+            sources: vec![
+                Box::new(MetaGdalSource::new(first)),
+                Box::new(MetaGdalSource::new(second)),
+            ],
+        };
+        let descriptor = stack.result_descriptor().unwrap();
+        assert_eq!(descriptor.data_type, RasterType::F32);
+        assert_eq!(descriptor.bands, 2);
+        assert!(descriptor.no_data_value.unwrap().is_nan());
+
+        let json = serde_json::to_value(&descriptor).unwrap();
+        assert_eq!(json["no_data_value"], "NaN");
+        let deserialized: RasterResultDescriptor = serde_json::from_value(json).unwrap();
+        assert!(deserialized.no_data_value.unwrap().is_nan());
+
+        let noop = MetaNoopOperator {
+            sources: vec![Box::new(MetaBandSelectOperator {
+                params: crate::BandSelectParams { bands: vec![0] },
+                sources: vec![Box::new(stack)],
+            })],
+        };
+        assert_eq!(noop.result_descriptor().unwrap().bands, 1);
+    }
+
+    #[test]
+    fn combines_the_area_all_sources_cover() {
+        let write = |name: &str, origin: Coordinate2D, resolution: f64, epsg_code: u32| {
+            let path = temp_path(name);
+            let raster = Raster::new(
+                GridShape::new(2, 2),
+                GeoTransform::new_north_up(
+                    origin,
+                    SpatialResolution::new_square(resolution).unwrap(),
+                ),
+                vec![1u8, 2, 3, 4],
+                None,
+            );
+            write_geotiff(&path, &raster, Some(SpatialReference::epsg(epsg_code))).unwrap();
+            let source: Box<dyn MetaRasterOperator> =
+                Box::new(MetaGdalSource::new(path.to_string_lossy().into_owned()));
+            source
+        };
+        let add = |sources: Vec<Box<dyn MetaRasterOperator>>| MetaAddRasterOperator {
+            params: AddRasterParams::default(),
+            sources,
+        };
+
+        let operator = add(vec![
+            write(
+                "descriptor_area_a.tif",
+                Coordinate2D::new(0., 2.),
+                1.,
+                32632,
+            ),
+            write(
+                "descriptor_area_b.tif",
+                Coordinate2D::new(1., 3.),
+                1.,
+                32632,
+            ),
+        ]);
+        assert_eq!(
+            operator.result_descriptor().unwrap().bbox,
+            BoundingBox2D::new(Coordinate2D::new(1., 1.), Coordinate2D::new(2., 2.)).unwrap()
+        );
+
+        let operator = add(vec![
+            write(
+                "descriptor_area_c.tif",
+                Coordinate2D::new(0., 2.),
+                1.,
+                32632,
+            ),
+            write(
+                "descriptor_area_d.tif",
+                Coordinate2D::new(0., 4.),
+                2.,
+                32632,
+            ),
+        ]);
+        assert_eq!(
+            operator.result_descriptor().unwrap_err().to_string(),
+            "cannot combine a raster with 1x1 pixels and a raster with 2x2 pixels"
+        );
+
+        let operator = add(vec![
+            write(
+                "descriptor_area_e.tif",
+                Coordinate2D::new(0., 2.),
+                1.,
+                32632,
+            ),
+            write("descriptor_area_f.tif", Coordinate2D::new(0., 2.), 1., 4326),
+        ]);
+        assert_eq!(
+            operator.result_descriptor().unwrap_err().to_string(),
+            "cannot combine data in EPSG:32632 and data in EPSG:4326"
+        );
+
+        let operator = add(vec![
+            write(
+                "descriptor_area_g.tif",
+                Coordinate2D::new(0., 2.),
+                1.,
+                32632,
+            ),
+            write(
+                "descriptor_area_h.tif",
+                Coordinate2D::new(5., 2.),
+                1.,
+                32632,
+            ),
+        ]);
+        assert_eq!(
+            operator.result_descriptor().unwrap_err().to_string(),
+            "the rasters do not overlap"
+        );
+    }
+
+    #[test]
+    fn describes_vector_graphs() {
+        let dataset = write_test_geotiff(
+            "descriptor_vector_u8.tif",
+            GridShape::new(2, 2),
+            vec![1u8, 2, 3, 4],
+        );
+        let operator = MetaRasterVectorOperator {
+            raster_sources: vec![Box::new(MetaGdalSource::new(dataset))],
+            vector_sources: vec![Box::new(MetaMyVectorSourceOperator {})],
+            params: RasterVectorParams::default(),
+        };
+        let descriptor = operator.result_descriptor().unwrap();
+        assert_eq!(descriptor.collection_type, VectorCollectionType::MultiPoint);
+        assert_eq!(
+            descriptor.schema,
+            AttributeSchema::new()
+                .with_column("name", AttributeType::Text)
+                .unwrap()
+                .with_column("raster_value", AttributeType::Float)
+                .unwrap()
+        );
+        let point = Coordinate2D::new(12., 13.);
+        assert_eq!(
+            descriptor.bbox,
+            Some(BoundingBox2D::new(point, point).unwrap())
+        );
+    }
+}
@@ -1,21 +1,50 @@
-use crate::{primitives::Raster, Point};
+use crate::{
+    error::{Error, Result},
+    feature_collection::{
+        MultiLineStringCollection, MultiPointCollection, MultiPolygonCollection,
+        VectorCollectionType, VectorData,
+    },
+    primitives::{BoundingBox2D, Raster, SpatialResolution, TimeInterval},
+    ArithmeticPixel, StaticRasterType,
+};
 use num_traits::One;
+use serde::{de::DeserializeOwned, Serialize};
 use std::ops::{Add, AddAssign};
 
-/// The Query...
-#[derive(Debug, Clone, Copy)]
-pub struct Query;
+/// The Query describes the spatio-temporal window and the resolution a consumer is interested in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Query {
+    pub bbox: BoundingBox2D,
+    pub time_interval: TimeInterval,
+    pub resolution: SpatialResolution,
+}
+
+impl Query {
+    /// Fails if the resolution is not strictly positive and finite. Its fields are public,
+    /// so it may not have been checked by `SpatialResolution::new`.
+    pub fn new(
+        bbox: BoundingBox2D,
+        time_interval: TimeInterval,
+        resolution: SpatialResolution,
+    ) -> Result<Self> {
+        Ok(Self {
+            bbox,
+            time_interval,
+            resolution: SpatialResolution::new(resolution.x, resolution.y)?,
+        })
+    }
+}
 
 /// a the most generic Source
 pub trait Source {
     type Output;
-    fn query(&self, query: Query) -> Self::Output;
+    fn query(&self, query: Query) -> Result<Self::Output>;
 }
 
 /// a RasterSource is similar to a Source but it returns Raster<T>
 pub trait RasterSource {
     type RasterType;
-    fn raster_query(&self, query: Query) -> Raster<Self::RasterType>;
+    fn raster_query(&self, query: Query) -> Result<Raster<Self::RasterType>>;
 }
 
 /// A Source is a RasterSource if it returns Rasters...
@@ -24,7 +53,7 @@ where
     S: Source<Output = Raster<T>>,
 {
     type RasterType = T;
-    fn raster_query(&self, query: Query) -> Raster<Self::RasterType> {
+    fn raster_query(&self, query: Query) -> Result<Raster<Self::RasterType>> {
         self.query(query)
     }
 }
@@ -32,7 +61,7 @@ where
 /// A VectorSource Returns some kind of Vector data
 pub trait VectorSource {
     type VectorType;
-    fn vector_query(&self, query: Query) -> Self::VectorType;
+    fn vector_query(&self, query: Query) -> Result<Self::VectorType>;
 }
 
 /// A Source is a VectorSource if it returns Vector data...
@@ -42,14 +71,14 @@ where
 {
     type VectorType = VD;
 
-    fn vector_query(&self, query: Query) -> Self::VectorType {
+    fn vector_query(&self, query: Query) -> Result<Self::VectorType> {
         self.query(query)
     }
 }
 
 impl<T> Source for Box<dyn Source<Output = T>> {
     type Output = T;
-    fn query(&self, query: Query) -> Self::Output {
+    fn query(&self, query: Query) -> Result<Self::Output> {
         self.as_ref().query(query)
     }
 }
@@ -60,7 +89,7 @@ where
     T: 'static,
 {
     type Output = Raster<T>;
-    fn query(&self, query: Query) -> Self::Output {
+    fn query(&self, query: Query) -> Result<Self::Output> {
         self.as_ref().raster_query(query)
     }
 }
@@ -70,11 +99,22 @@ where
     V: 'static,
 {
     type Output = V;
-    fn query(&self, query: Query) -> Self::Output {
+    fn query(&self, query: Query) -> Result<Self::Output> {
         self.as_ref().vector_query(query)
     }
 }
 
+/// The parameters that configure an operator. The Meta operators read them from workflows.
+pub trait OperatorParams: Serialize + DeserializeOwned + Clone {
+    /// fails if no operator can be created with these parameters
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// operators without parameters
+impl OperatorParams for () {}
+
 pub trait CreateSourceOperator<P> {
     fn create(params: P) -> Self;
 }
@@ -93,7 +133,7 @@ pub trait CreateBoxedUnaryOperator<P> {
         params: P,
     ) -> Box<dyn RasterSource<RasterType = T1>>
     where
-        T1: Add + AddAssign + One + Copy + 'static;
+        T1: Add + AddAssign + One + ArithmeticPixel;
 }
 
 pub trait CreateBoxedBinaryOperatorInplace<P> {
@@ -103,137 +143,337 @@ pub trait CreateBoxedBinaryOperatorInplace<P> {
         params: P,
     ) -> Box<dyn RasterSource<RasterType = T1>>
     where
-        T1: Add + AddAssign + One + Copy + 'static,
-        T2: Add + AddAssign + One + Into<T1> + Copy + 'static;
+        T1: Add + AddAssign + One + ArithmeticPixel,
+        T2: Add + AddAssign + One + Into<T1> + ArithmeticPixel;
 }
 
-pub enum BoxedRasterOperatorInstance {
-    U8(Box<dyn RasterSource<RasterType = u8>>),
-    U16(Box<dyn RasterSource<RasterType = u16>>),
-    U32(Box<dyn RasterSource<RasterType = u32>>),
-    U64(Box<dyn RasterSource<RasterType = u64>>),
-    I16(Box<dyn RasterSource<RasterType = i16>>),
-    I32(Box<dyn RasterSource<RasterType = i32>>),
-    I64(Box<dyn RasterSource<RasterType = i64>>),
-    F32(Box<dyn RasterSource<RasterType = f32>>),
-    F64(Box<dyn RasterSource<RasterType = f64>>),
+/// A primitive raster type that can be moved in and out of a `BoxedRasterOperatorInstance`.
+pub trait BoxedRasterType: StaticRasterType {
+    fn into_boxed_instance(
+        source: Box<dyn RasterSource<RasterType = Self>>,
+    ) -> BoxedRasterOperatorInstance;
+
+    fn from_boxed_instance(
+        instance: BoxedRasterOperatorInstance,
+    ) -> Result<Box<dyn RasterSource<RasterType = Self>>>;
+
+    /// wraps the operator with a conversion to `Self` unless it already produces `Self`
+    fn convert_boxed_instance(
+        instance: BoxedRasterOperatorInstance,
+    ) -> Box<dyn RasterSource<RasterType = Self>>;
 }
 
+crate::dispatch_raster_types!(boxed_instance);
+
 impl BoxedRasterOperatorInstance {
-    pub fn get_u8(self) -> Option<Box<dyn RasterSource<RasterType = u8>>> {
-        match self {
-            BoxedRasterOperatorInstance::U8(r) => Some(r),
-            _ => None,
-        }
-    }
-    pub fn get_u16(self) -> Option<Box<dyn RasterSource<RasterType = u16>>> {
-        match self {
-            BoxedRasterOperatorInstance::U16(r) => Some(r),
-            _ => None,
-        }
-    }
-    pub fn get_u32(self) -> Option<Box<dyn RasterSource<RasterType = u32>>> {
-        match self {
-            BoxedRasterOperatorInstance::U32(r) => Some(r),
-            _ => None,
-        }
-    }
-    pub fn get_u64(self) -> Option<Box<dyn RasterSource<RasterType = u64>>> {
-        match self {
-            BoxedRasterOperatorInstance::U64(r) => Some(r),
-            _ => None,
-        }
-    }
-    pub fn get_i16(self) -> Option<Box<dyn RasterSource<RasterType = i16>>> {
-        match self {
-            BoxedRasterOperatorInstance::I16(r) => Some(r),
-            _ => None,
-        }
+    /// wraps a typed raster operator
+    pub fn new<T: BoxedRasterType>(source: Box<dyn RasterSource<RasterType = T>>) -> Self {
+        T::into_boxed_instance(source)
     }
-    pub fn get_i32(self) -> Option<Box<dyn RasterSource<RasterType = i32>>> {
-        match self {
-            BoxedRasterOperatorInstance::I32(r) => Some(r),
-            _ => None,
-        }
+
+    /// unwraps the typed raster operator or fails if it produces another type than `T`
+    pub fn get<T: BoxedRasterType>(self) -> Result<Box<dyn RasterSource<RasterType = T>>> {
+        T::from_boxed_instance(self)
     }
-    pub fn get_i64(self) -> Option<Box<dyn RasterSource<RasterType = i64>>> {
-        match self {
-            BoxedRasterOperatorInstance::I64(r) => Some(r),
-            _ => None,
-        }
+
+    /// converts the output of the operator to `T`. Use it with a promoted type to convert losslessly.
+    pub fn convert<T: BoxedRasterType>(self) -> Box<dyn RasterSource<RasterType = T>> {
+        T::convert_boxed_instance(self)
     }
-    pub fn get_f32(self) -> Option<Box<dyn RasterSource<RasterType = f32>>> {
+}
+
+pub enum BoxedVectorOperatorInstance {
+    MultiPoints(Box<dyn VectorSource<VectorType = MultiPointCollection>>),
+    MultiLineStrings(Box<dyn VectorSource<VectorType = MultiLineStringCollection>>),
+    MultiPolygons(Box<dyn VectorSource<VectorType = MultiPolygonCollection>>),
+}
+
+impl BoxedVectorOperatorInstance {
+    /// the type of the collections the operator produces
+    pub fn collection_type(&self) -> VectorCollectionType {
         match self {
-            BoxedRasterOperatorInstance::F32(r) => Some(r),
-            _ => None,
+            BoxedVectorOperatorInstance::MultiPoints(_) => VectorCollectionType::MultiPoint,
+            BoxedVectorOperatorInstance::MultiLineStrings(_) => {
+                VectorCollectionType::MultiLineString
+            }
+            BoxedVectorOperatorInstance::MultiPolygons(_) => VectorCollectionType::MultiPolygon,
         }
     }
-    pub fn get_f64(self) -> Option<Box<dyn RasterSource<RasterType = f64>>> {
-        match self {
-            BoxedRasterOperatorInstance::F64(r) => Some(r),
-            _ => None,
-        }
+
+    /// unwraps the typed vector operator or fails if it produces another collection than `V`
+    pub fn get<V: BoxedVectorType>(self) -> Result<Box<dyn VectorSource<VectorType = V>>> {
+        V::from_boxed_instance(self)
     }
 }
 
-pub enum BoxedVectorOperatorInstance {
-    Points(Box<dyn VectorSource<VectorType = Point>>),
+/// A feature collection that can be moved in and out of a `BoxedVectorOperatorInstance`.
+pub trait BoxedVectorType: VectorData + Sized {
+    fn into_boxed_instance(
+        source: Box<dyn VectorSource<VectorType = Self>>,
+    ) -> BoxedVectorOperatorInstance;
+
+    fn from_boxed_instance(
+        instance: BoxedVectorOperatorInstance,
+    ) -> Result<Box<dyn VectorSource<VectorType = Self>>>;
+}
+
+macro_rules! impl_boxed_vector_type {
+    ($($collection:ty => $variant:ident),*) => {
+        $(
+            impl BoxedVectorType for $collection {
+                fn into_boxed_instance(
+                    source: Box<dyn VectorSource<VectorType = Self>>,
+                ) -> BoxedVectorOperatorInstance {
+                    BoxedVectorOperatorInstance::$variant(source)
+                }
+
+                fn from_boxed_instance(
+                    instance: BoxedVectorOperatorInstance,
+                ) -> Result<Box<dyn VectorSource<VectorType = Self>>> {
+                    match instance {
+                        BoxedVectorOperatorInstance::$variant(source) => Ok(source),
+                        other => Err(Error::CollectionTypeMismatch {
+                            expected: Self::TYPE,
+                            found: other.collection_type(),
+                        }),
+                    }
+                }
+            }
+        )*
+    };
 }
 
+impl_boxed_vector_type!(
+    MultiPointCollection => MultiPoints,
+    MultiLineStringCollection => MultiLineStrings,
+    MultiPolygonCollection => MultiPolygons
+);
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        GdalSource, MyVectorSource, Point, Query, RasterOperatorExt, Source, VectorOperatorExt,
-        VectorSource,
+        error::{Error, Result},
+        test_util::{query, query_area, write_test_geotiff},
+        BoundingBox2D, Coordinate2D, GdalSource, GeoTransform, GridShape, MultiPointCollection,
+        MyVectorSource, Query, Raster, RasterOperatorExt, Sampling, Source, SpatialResolution,
+        TimeInterval, VectorOperatorExt, VectorSource,
     };
-    use std::marker::PhantomData;
+    use std::{cell::RefCell, marker::PhantomData, rc::Rc};
 
     #[test]
     fn complex() {
         // a gdal source
         let gdal_source: GdalSource<u16> = GdalSource {
-            dataset: "meh".to_owned(),
+            dataset: write_test_geotiff(
+                "source_complex_u16.tif",
+                GridShape::new(2, 2),
+                vec![1u16, 2, 3, 4],
+            ),
+            bands: None,
+            time: None,
             data: PhantomData,
         };
 
         // concrete raster!
-        let r = gdal_source.query(Query);
+        let r = gdal_source.query(query()).unwrap();
         println!("{:?}", r);
 
         let raster_plus_one = gdal_source.plus_one();
-        let r = raster_plus_one.query(Query);
+        let r = raster_plus_one.query(query()).unwrap();
         println!("{:?}", r);
 
         let other_gdal_source: GdalSource<u8> = GdalSource {
-            dataset: "meh".to_owned(),
+            dataset: write_test_geotiff(
+                "source_complex_u8.tif",
+                GridShape::new(2, 2),
+                vec![5u8, 6, 7, 8],
+            ),
+            bands: None,
+            time: None,
             data: PhantomData,
         };
 
         let raster_plusone_plus_other = raster_plus_one.plus_raster(other_gdal_source);
-        let r = raster_plusone_plus_other.query(Query);
+        let r = raster_plusone_plus_other.query(query()).unwrap();
         println!("{:?}", r);
 
         // a vector source
-        let vector_source: MyVectorSource<Point> = MyVectorSource {
+        let vector_source: MyVectorSource<MultiPointCollection> = MyVectorSource {
             dataset: "vec".to_owned(),
             data: PhantomData,
         };
 
         // concrete vector!
-        let v = vector_source.query(Query);
+        let v = vector_source.query(query()).unwrap();
         println!("{:?}", v);
 
         // take the vector_source, add a noop, combine the result with the raster_source wrapped in a noop
-        let vector_noop_raster_noop_combine = vector_source
-            .noop()
-            .add_raster_values(RasterOperatorExt::noop(raster_plusone_plus_other));
+        let vector_noop_raster_noop_combine = vector_source.noop().add_raster_values(
+            RasterOperatorExt::noop(raster_plusone_plus_other),
+            "raster_value",
+            Sampling::Nearest,
+        );
         // add more noops
         let vector_noop_raster_noop_combine_noop_noop =
             vector_noop_raster_noop_combine.noop().noop();
         // will produce the concrete vector type! (all known at compile time)
         println!(
             "{:?}",
-            vector_noop_raster_noop_combine_noop_noop.vector_query(Query)
+            vector_noop_raster_noop_combine_noop_noop
+                .vector_query(query())
+                .unwrap()
         );
     }
+
+    /// A source that remembers every query it receives
+    struct RecordingSource {
+        queries: Rc<RefCell<Vec<Query>>>,
+    }
+
+    impl Source for RecordingSource {
+        type Output = Raster<u8>;
+        fn query(&self, query: Query) -> Result<Self::Output> {
+            self.queries.borrow_mut().push(query);
+            Ok(Raster::new(
+                GridShape::new(2, 2),
+                GeoTransform::new_north_up(
+                    Coordinate2D::new(query.bbox.lower_left().x, query.bbox.upper_right().y),
+                    query.resolution,
+                ),
+                vec![0; 4],
+                None,
+            ))
+        }
+    }
+
+    #[test]
+    fn query_is_propagated_to_sources() {
+        let queries = Rc::new(RefCell::new(Vec::new()));
+        let recording_source = || RecordingSource {
+            queries: queries.clone(),
+        };
+
+        let vector_source: MyVectorSource<MultiPointCollection> = MyVectorSource {
+            dataset: "vec".to_owned(),
+            data: PhantomData,
+        };
+
+        let operator = VectorOperatorExt::noop(vector_source).add_raster_values(
+            RasterOperatorExt::noop(recording_source())
+                .plus_one()
+                .plus_raster(recording_source()),
+            "raster_value",
+            Sampling::Nearest,
+        );
+
+        let query = Query::new(
+            BoundingBox2D::new(Coordinate2D::new(-10., 20.), Coordinate2D::new(5., 30.)).unwrap(),
+            TimeInterval::new(1_000, 2_000).unwrap(),
+            SpatialResolution::new(0.5, 0.25).unwrap(),
+        )
+        .unwrap();
+        operator.query(query).unwrap();
+
+        assert_eq!(*queries.borrow(), vec![query, query]);
+    }
+
+    #[test]
+    fn add_raster_rejects_incompatible_grids() {
+        let a: GdalSource<u16> = GdalSource {
+            dataset: write_test_geotiff(
+                "source_add_raster_rejects_incompatible_grids.tif",
+                GridShape::new(4, 4),
+                vec![0u16; 16],
+            ),
+            bands: None,
+            time: None,
+            data: PhantomData,
+        };
+        let b = RecordingSource {
+            queries: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        // the recording source always returns a 2x2 grid, the gdal source a 4x4 grid for this query
+        let query = query_area(4., 4.);
+        assert!(matches!(
+            a.plus_raster(b).query(query),
+            Err(Error::IncompatibleGrids { .. })
+        ));
+    }
+
+    /// A source that always returns the same raster
+    struct MockRasterSource<T> {
+        raster: Raster<T>,
+    }
+
+    impl<T: Clone> Source for MockRasterSource<T> {
+        type Output = Raster<T>;
+        fn query(&self, _query: Query) -> Result<Self::Output> {
+            Ok(self.raster.clone())
+        }
+    }
+
+    fn mock_raster_source<T>(data: Vec<T>, no_data_value: Option<T>) -> MockRasterSource<T> {
+        MockRasterSource {
+            raster: Raster::new(
+                GridShape::new(2, 2),
+                GeoTransform::new_north_up(
+                    Coordinate2D::new(0., 2.),
+                    SpatialResolution::new_square(1.).unwrap(),
+                ),
+                data,
+                no_data_value,
+            ),
+        }
+    }
+
+    #[test]
+    fn no_data_is_propagated() {
+        let a = mock_raster_source(vec![1u16, 2, 0, 4], Some(0));
+        let b = mock_raster_source(vec![10u8, 255, 30, 40], Some(255));
+
+        let r = a.plus_one().plus_raster(b).query(query()).unwrap();
+
+        assert_eq!(r.data, vec![12, 0, 0, 45]);
+        assert_eq!(r.no_data_value, Some(0));
+    }
+
+    #[test]
+    fn sums_equal_to_the_no_data_value_stay_valid() {
+        let a = mock_raster_source(vec![254u8, 1, 2, 255], Some(255));
+        let b = mock_raster_source(vec![1u8, 1, 255, 1], None);
+
+        // 254 + 1 is valid, 2 + 255 overflows and the last pixel is missing in `a`
+        let r = a.plus_raster(b).query(query()).unwrap();
+        assert_eq!(r.data, vec![255, 2, 254, 254]);
+        assert_eq!(r.no_data_value, Some(254));
+        assert_eq!(r.validity_mask(), vec![true, true, false, false]);
+    }
+
+    #[test]
+    fn no_data_defaults_to_type_no_data_value() {
+        let a = mock_raster_source(vec![1i16, 2, 3, 4], None);
+        let b = mock_raster_source(vec![1.5f32, 2., f32::NAN, 4.], Some(f32::NAN));
+
+        let r = b.plus_raster(a).query(query()).unwrap();
+        assert_eq!(r.data[..2], [2.5, 4.]);
+        assert!(r.data[2].is_nan());
+        assert_eq!(r.validity_mask(), vec![true, true, false, true]);
+
+        let a = mock_raster_source(vec![1u16, 2, 3, 4], None);
+        let b = mock_raster_source(vec![1u8, 2, 3, 4], Some(3));
+
+        let r = a.plus_raster(b).query(query()).unwrap();
+        assert_eq!(r.data, vec![2, 4, u16::MAX, 8]);
+        assert_eq!(r.no_data_value, Some(u16::MAX));
+    }
+
+    #[test]
+    fn rejects_unchecked_resolutions() {
+        let unchecked = SpatialResolution { x: 0., y: 1. };
+        assert!(matches!(
+            Query::new(query().bbox, query().time_interval, unchecked),
+            Err(Error::InvalidResolution { .. })
+        ));
+    }
 }
@@ -39,7 +39,7 @@ where
 }
 
 // impl Subgraph for NoOpOperator. TODO: find out if this is needed.
-impl<S1, S2> Subgraph for AddRasterOperator<S1, S2>
+impl<S1, S2> Subgraph for BinaryRasterOperator<S1, S2>
 where
     S1: Source,
     S2: Source,
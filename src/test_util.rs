@@ -0,0 +1,116 @@
+//! Fixtures shared by the tests of the crate
+
+use crate::{
+    geotiff::GeoTiffPixel, write_geotiff, BoundingBox2D, Coordinate2D, GeoTransform, GridShape,
+    MetaGdalSource, MetaRasterOperator, Query, Raster, SpatialReference, SpatialResolution,
+    TimeInterval,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// a path for a test file in a temporary directory of this test process.
+/// Concurrent test runs write to different directories.
+pub fn temp_path(name: &str) -> PathBuf {
+    static DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+    DIRECTORY
+        .get_or_init(|| {
+            tempfile::Builder::new()
+                .prefix("engine_x_tests")
+                .tempdir()
+                .unwrap()
+                .keep()
+        })
+        .join(name)
+}
+
+/// writes a GeoTIFF covering `(0, 0)` to `(width, height)` with a resolution of one
+pub fn write_test_geotiff<T>(name: &str, shape: GridShape, data: Vec<T>) -> String
+where
+    T: GeoTiffPixel,
+    [T]: tiff::encoder::TiffValue,
+{
+    write_test_geotiff_bands(name, shape, 1, data)
+}
+
+/// like `write_test_geotiff` for a file with several bands. `data` holds the bands one after another.
+pub fn write_test_geotiff_bands<T>(
+    name: &str,
+    shape: GridShape,
+    bands: usize,
+    data: Vec<T>,
+) -> String
+where
+    T: GeoTiffPixel,
+    [T]: tiff::encoder::TiffValue,
+{
+    write_test_raster(&temp_path(name), shape, bands, data, None)
+}
+
+/// like `write_test_geotiff` for a file whose pixels equal to `no_data_value` are missing
+pub fn write_test_geotiff_with_no_data<T>(
+    name: &str,
+    shape: GridShape,
+    data: Vec<T>,
+    no_data_value: T,
+) -> String
+where
+    T: GeoTiffPixel,
+    [T]: tiff::encoder::TiffValue,
+{
+    write_test_raster(&temp_path(name), shape, 1, data, Some(no_data_value))
+}
+
+fn write_test_raster<T>(
+    path: &Path,
+    shape: GridShape,
+    bands: usize,
+    data: Vec<T>,
+    no_data_value: Option<T>,
+) -> String
+where
+    T: GeoTiffPixel,
+    [T]: tiff::encoder::TiffValue,
+{
+    let raster = Raster::new_with_bands(
+        shape,
+        bands,
+        GeoTransform::new_north_up(
+            Coordinate2D::new(0., shape.height as f64),
+            SpatialResolution::new_square(1.).unwrap(),
+        ),
+        data,
+        no_data_value,
+    );
+    write_geotiff(path, &raster, Some(SpatialReference::epsg(32632))).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// a GDAL source reading a two by two GeoTIFF written by `write_test_geotiff`
+pub fn gdal_source<T>(name: &str, data: Vec<T>) -> Box<dyn MetaRasterOperator>
+where
+    T: GeoTiffPixel,
+    [T]: tiff::encoder::TiffValue,
+{
+    Box::new(MetaGdalSource::new(write_test_geotiff(
+        name,
+        GridShape::new(2, 2),
+        data,
+    )))
+}
+
+/// a query for the two by two pixels at the origin of the test GeoTIFFs
+pub fn query() -> Query {
+    query_area(2., 2.)
+}
+
+/// a query for `(0, 0)` to `(x, y)` in `[0, 1)` with a resolution of one
+pub fn query_area(x: f64, y: f64) -> Query {
+    Query::new(
+        BoundingBox2D::new(Coordinate2D::new(0., 0.), Coordinate2D::new(x, y)).unwrap(),
+        TimeInterval::new(0, 1).unwrap(),
+        SpatialResolution::new_square(1.).unwrap(),
+    )
+    .unwrap()
+}
@@ -0,0 +1,234 @@
+use crate::{
+    error::{Error, Result},
+    meta_raster_operator::{MetaOperator, MetaRasterOperator, MetaVectorOperator, RasterWants},
+    raster_type::RasterType,
+};
+
+/// Checks the input requirements of all operators in a raster graph and computes its output type.
+/// All violations are reported at once as `Error::InvalidGraph`. No processing operator is created.
+pub fn validate_raster_graph<O>(operator: &O) -> Result<RasterType>
+where
+    O: MetaRasterOperator + ?Sized,
+{
+    let mut errors = Vec::new();
+    match validate_raster_operator(operator, &mut errors) {
+        Some(raster_type) if errors.is_empty() => Ok(raster_type),
+        _ => Err(Error::InvalidGraph(errors)),
+    }
+}
+
+/// Checks the input requirements of all raster operators in a vector graph and
+/// that the attribute schema of every vector operator can be computed.
+pub fn validate_vector_graph<O>(operator: &O) -> Result<()>
+where
+    O: MetaVectorOperator + ?Sized,
+{
+    let mut errors = Vec::new();
+    validate_vector_operator(operator, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidGraph(errors))
+    }
+}
+
+/// validates a raster operator and its sources. Returns the output type if it can be computed.
+fn validate_raster_operator<O>(operator: &O, errors: &mut Vec<Error>) -> Option<RasterType>
+where
+    O: MetaRasterOperator + ?Sized,
+{
+    let mut operator_errors = Vec::new();
+    let input_types = validate_inputs(operator, &mut operator_errors);
+
+    let mut raster_type = None;
+    if operator_errors.is_empty() {
+        if let Some(input_types) = input_types {
+            match operator.raster_creates().and_then(|creates| {
+                creates.resolve(|index| {
+                    input_types
+                        .get(index)
+                        .copied()
+                        .ok_or(Error::MissingRasterSource { index })
+                })
+            }) {
+                Ok(output_type) => match operator.band_count() {
+                    Ok(_) => raster_type = Some(output_type),
+                    Err(error) => push_error(&mut operator_errors, error),
+                },
+                Err(error) => push_error(&mut operator_errors, error),
+            }
+        }
+    }
+
+    errors.extend(
+        operator_errors
+            .into_iter()
+            .map(|error| error.in_operator(operator.typetag_name())),
+    );
+    raster_type
+}
+
+fn validate_vector_operator<O>(operator: &O, errors: &mut Vec<Error>)
+where
+    O: MetaVectorOperator + ?Sized,
+{
+    let mut operator_errors = Vec::new();
+    validate_inputs(operator, &mut operator_errors);
+
+    if operator_errors.is_empty() {
+        if let Err(error) = operator.attribute_schema() {
+            push_error(&mut operator_errors, error);
+        }
+    }
+
+    errors.extend(
+        operator_errors
+            .into_iter()
+            .map(|error| error.in_operator(operator.typetag_name())),
+    );
+}
+
+/// adds an error. The violations in a referenced workflow node are added one by one.
+fn push_error(errors: &mut Vec<Error>, error: Error) {
+    match error {
+        Error::InvalidGraph(violations) => errors.extend(violations),
+        error => errors.push(error),
+    }
+}
+
+/// validates the sources of an operator and checks them against its `raster_wants` and `variadic_raster_wants`.
+/// Returns the types of the raster sources if all of them are known.
+fn validate_inputs<O>(operator: &O, errors: &mut Vec<Error>) -> Option<Vec<RasterType>>
+where
+    O: MetaOperator + ?Sized,
+{
+    let wants = operator.raster_wants();
+    let variadic_wants = operator.variadic_raster_wants();
+
+    let input_types: Vec<Option<RasterType>> = operator
+        .raster_sources()
+        .iter()
+        .enumerate()
+        .map(|(index, source)| {
+            let mut source_errors = Vec::new();
+            let raster_type = validate_raster_operator(source.as_ref(), &mut source_errors);
+            errors.extend(
+                source_errors
+                    .into_iter()
+                    .map(|error| error.in_operator(&format!("raster source {}", index))),
+            );
+            raster_type
+        })
+        .collect();
+
+    for (index, source) in operator.vector_sources().iter().enumerate() {
+        let mut source_errors = Vec::new();
+        validate_vector_operator(source.as_ref(), &mut source_errors);
+        errors.extend(
+            source_errors
+                .into_iter()
+                .map(|error| error.in_operator(&format!("vector source {}", index))),
+        );
+    }
+
+    for index in 0..wants.len().max(input_types.len()) {
+        let wants = match (wants.get(index), input_types.get(index)) {
+            (None, Some(_)) => variadic_wants.as_ref(),
+            (wants, _) => wants,
+        };
+        match (wants, input_types.get(index)) {
+            (Some(RasterWants::None), None) | (None, None) => {}
+            (Some(RasterWants::None), Some(_)) | (None, Some(_)) => {
+                errors.push(Error::UnexpectedRasterSource { index })
+            }
+            (Some(_), None) => errors.push(Error::MissingRasterSource { index }),
+            (Some(RasterWants::ConceteType(expected)), Some(Some(found))) if expected != found => {
+                errors.push(Error::InputTypeMismatch {
+                    index,
+                    expected: *expected,
+                    found: *found,
+                })
+            }
+            (Some(_), Some(_)) => {}
+        }
+    }
+
+    input_types.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::gdal_source, AddRasterParams, MetaAddRasterOperator, MetaGdalSource,
+        MetaMyVectorSourceOperator, MetaNoopOperator, MetaPlusOneOperator,
+        MetaRasterVectorOperator, PlusOneParams, RasterVectorParams, Sampling,
+    };
+
+    #[test]
+    fn valid_graph_has_output_type() {
+        let operator = MetaAddRasterOperator {
+            params: AddRasterParams::default(),
+            sources: vec![
+                Box::new(MetaPlusOneOperator {
+                    params: PlusOneParams::default(),
+                    sources: vec![gdal_source("validation_valid_a.tif", vec![1u16, 2, 3, 4])],
+                }),
+                gdal_source("validation_valid_b.tif", vec![1u16, 2, 3, 4]),
+            ],
+        };
+
+        assert_eq!(validate_raster_graph(&operator).unwrap(), RasterType::U16);
+    }
+
+    #[test]
+    fn reports_all_violations() {
+        let operator = MetaAddRasterOperator {
+            params: AddRasterParams::default(),
+            sources: vec![
+                Box::new(MetaNoopOperator { sources: vec![] }),
+                Box::new(MetaPlusOneOperator {
+                    params: PlusOneParams::default(),
+                    sources: vec![
+                        gdal_source("validation_violations_a.tif", vec![1u16, 2, 3, 4]),
+                        gdal_source("validation_violations_b.tif", vec![1u16, 2, 3, 4]),
+                    ],
+                }),
+            ],
+        };
+
+        match validate_raster_graph(&operator) {
+            Err(Error::InvalidGraph(errors)) => {
+                let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                assert_eq!(
+                    messages,
+                    vec![
+                        "MetaAddRasterOperator -> raster source 0 -> MetaNoopOperator: the raster source 0 is missing",
+                        "MetaAddRasterOperator -> raster source 1 -> MetaPlusOneOperator: the raster source 1 is not expected",
+                    ]
+                );
+            }
+            result => panic!("expected an invalid graph, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn checks_vector_graphs() {
+        let operator = MetaRasterVectorOperator {
+            raster_sources: vec![Box::new(MetaGdalSource::new(
+                "does/not/exist.tif".to_string(),
+            ))],
+            vector_sources: vec![Box::new(MetaMyVectorSourceOperator {})],
+            params: RasterVectorParams {
+                column: "raster_value".to_string(),
+                sampling: Sampling::Nearest,
+                band: 0,
+            },
+        };
+
+        let error = validate_vector_graph(&operator).unwrap_err();
+        assert!(error.to_string().starts_with(
+            "the operator graph is invalid: MetaRasterVectorOperator -> raster source 0 -> MetaGdalSource: cannot read dataset"
+        ));
+    }
+}
@@ -0,0 +1,843 @@
+use crate::{
+    attributes::AttributeSchema,
+    error::{Error, Result},
+    feature_collection::VectorCollectionType,
+    meta_raster_operator::{
+        MetaOperator, MetaRasterOperator, MetaVectorOperator, RasterCreates, RasterWants,
+    },
+    raster_type::RasterType,
+    result_descriptor::{RasterResultDescriptor, VectorResultDescriptor},
+    source::{
+        BoxedRasterOperatorInstance, BoxedRasterType, BoxedVectorOperatorInstance, Query,
+        RasterSource, Source,
+    },
+    validation::{validate_raster_graph, validate_vector_graph},
+};
+use schemars::JsonSchema;
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{BTreeMap, HashSet},
+    convert::TryFrom,
+    rc::Rc,
+};
+
+/// The SharedSource lets several consumers query one source.
+/// It keeps the result of the last query and hands out copies of it while the query does not change.
+/// Every consumer gets its own copy, so sharing a raster costs a copy of its pixels per consumer
+/// instead of computing it again.
+pub struct SharedSource<S: Source> {
+    state: Rc<SharedSourceState<S>>,
+}
+
+struct SharedSourceState<S: Source> {
+    source: S,
+    last: RefCell<Option<(Query, S::Output)>>,
+}
+
+impl<S: Source> SharedSource<S> {
+    pub fn new(source: S) -> Self {
+        SharedSource {
+            state: Rc::new(SharedSourceState {
+                source,
+                last: RefCell::new(None),
+            }),
+        }
+    }
+}
+
+impl<S: Source> Clone for SharedSource<S> {
+    fn clone(&self) -> Self {
+        SharedSource {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S> Source for SharedSource<S>
+where
+    S: Source,
+    S::Output: Clone,
+{
+    type Output = S::Output;
+    fn query(&self, query: Query) -> Result<Self::Output> {
+        if let Some((last_query, output)) = self.state.last.borrow().as_ref() {
+            if *last_query == query {
+                return Ok(output.clone());
+            }
+        }
+        let output = self.state.source.query(query)?;
+        *self.state.last.borrow_mut() = Some((query, output.clone()));
+        Ok(output)
+    }
+}
+
+/// The kind of data a workflow node produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum WorkflowNodeKind {
+    Raster,
+    Vector,
+}
+
+/// The operator of a workflow node. Its kind is determined by the type of the operator.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged, try_from = "serde_json::Value")]
+pub enum WorkflowOperator {
+    Raster(Box<dyn MetaRasterOperator>),
+    Vector(Box<dyn MetaVectorOperator>),
+}
+
+impl TryFrom<serde_json::Value> for WorkflowOperator {
+    type Error = String;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let raster_error = match serde_json::from_value(value.clone()) {
+            Ok(operator) => return Ok(WorkflowOperator::Raster(operator)),
+            Err(error) => error,
+        };
+        serde_json::from_value(value)
+            .map(WorkflowOperator::Vector)
+            .map_err(|vector_error| {
+                format!(
+                    "neither a raster operator ({}) nor a vector operator ({})",
+                    raster_error, vector_error
+                )
+            })
+    }
+}
+
+impl WorkflowOperator {
+    pub fn kind(&self) -> WorkflowNodeKind {
+        match self {
+            WorkflowOperator::Raster(_) => WorkflowNodeKind::Raster,
+            WorkflowOperator::Vector(_) => WorkflowNodeKind::Vector,
+        }
+    }
+
+    /// get the references to other nodes in the operator
+    fn node_references(&self) -> Vec<&dyn NodeReference> {
+        match self {
+            WorkflowOperator::Raster(operator) => operator.node_references(),
+            WorkflowOperator::Vector(operator) => operator.node_references(),
+        }
+    }
+}
+
+/// A node of a workflow. Its processing operator is created once and shared by all references to the node.
+/// It is validated and described once as well, so references do not walk the graph of the node again.
+pub struct WorkflowNode {
+    pub id: String,
+    pub operator: WorkflowOperator,
+    raster_instance: RefCell<Option<Box<dyn Fn() -> BoxedRasterOperatorInstance>>>,
+    vector_instance: RefCell<Option<Box<dyn Fn() -> BoxedVectorOperatorInstance>>>,
+    valid: OnceCell<bool>,
+    raster_type: OnceCell<RasterType>,
+    bands: OnceCell<usize>,
+    raster_descriptor: OnceCell<RasterResultDescriptor>,
+    collection_type: OnceCell<VectorCollectionType>,
+    schema: OnceCell<AttributeSchema>,
+    vector_descriptor: OnceCell<VectorResultDescriptor>,
+}
+
+/// the cached value or the computed one, which is cached if it could be computed
+fn cached<T: Clone>(cell: &OnceCell<T>, compute: impl FnOnce() -> Result<T>) -> Result<T> {
+    if let Some(value) = cell.get() {
+        return Ok(value.clone());
+    }
+    let value = compute()?;
+    Ok(cell.get_or_init(|| value).clone())
+}
+
+impl WorkflowNode {
+    fn new(id: String, operator: WorkflowOperator) -> Self {
+        WorkflowNode {
+            id,
+            operator,
+            raster_instance: RefCell::new(None),
+            vector_instance: RefCell::new(None),
+            valid: OnceCell::new(),
+            raster_type: OnceCell::new(),
+            bands: OnceCell::new(),
+            raster_descriptor: OnceCell::new(),
+            collection_type: OnceCell::new(),
+            schema: OnceCell::new(),
+            vector_descriptor: OnceCell::new(),
+        }
+    }
+
+    /// validates the graph of the node on the first call. Later calls only tell if it is valid,
+    /// so the violations in a node are reported once, however often it is referenced.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(&valid) = self.valid.get() {
+            return if valid {
+                Ok(())
+            } else {
+                Err(Error::InvalidNode(self.id.clone()))
+            };
+        }
+        let result = match &self.operator {
+            WorkflowOperator::Raster(operator) => {
+                validate_raster_graph(operator.as_ref()).map(|_| ())
+            }
+            WorkflowOperator::Vector(operator) => validate_vector_graph(operator.as_ref()),
+        };
+        let _ = self.valid.set(result.is_ok());
+        result.map_err(|error| match error {
+            Error::InvalidGraph(errors) => Error::InvalidGraph(
+                errors
+                    .into_iter()
+                    .map(|error| error.in_operator(&format!("node {}", self.id)))
+                    .collect(),
+            ),
+            error => error.in_operator(&format!("node {}", self.id)),
+        })
+    }
+
+    /// get the type the raster node creates
+    pub fn creates_type(&self) -> Result<RasterType> {
+        let operator = self.raster_operator()?;
+        cached(&self.raster_type, || {
+            operator
+                .creates_type()
+                .map_err(|error| error.in_operator(operator.typetag_name()))
+        })
+    }
+
+    /// get the number of bands of the rasters the raster node creates
+    pub fn band_count(&self) -> Result<usize> {
+        let operator = self.raster_operator()?;
+        cached(&self.bands, || {
+            operator
+                .band_count()
+                .map_err(|error| error.in_operator(operator.typetag_name()))
+        })
+    }
+
+    /// describes the rasters the raster node creates
+    pub fn raster_descriptor(&self) -> Result<RasterResultDescriptor> {
+        let operator = self.raster_operator()?;
+        cached(&self.raster_descriptor, || {
+            operator
+                .result_descriptor()
+                .map_err(|error| error.in_operator(operator.typetag_name()))
+        })
+    }
+
+    /// get the type of the collections the vector node creates
+    pub fn collection_type(&self) -> Result<VectorCollectionType> {
+        let operator = self.vector_operator()?;
+        cached(&self.collection_type, || {
+            operator
+                .creates_collection_type()
+                .map_err(|error| error.in_operator(operator.typetag_name()))
+        })
+    }
+
+    /// get the attribute schema of the collections the vector node creates
+    pub fn attribute_schema(&self) -> Result<AttributeSchema> {
+        let operator = self.vector_operator()?;
+        cached(&self.schema, || {
+            operator
+                .attribute_schema()
+                .map_err(|error| error.in_operator(operator.typetag_name()))
+        })
+    }
+
+    /// describes the collections the vector node creates
+    pub fn vector_descriptor(&self) -> Result<VectorResultDescriptor> {
+        let operator = self.vector_operator()?;
+        cached(&self.vector_descriptor, || {
+            operator
+                .result_descriptor()
+                .map_err(|error| error.in_operator(operator.typetag_name()))
+        })
+    }
+
+    fn kind_mismatch(&self, expected: WorkflowNodeKind) -> Error {
+        Error::NodeKindMismatch {
+            node: self.id.clone(),
+            expected,
+        }
+    }
+
+    /// get the operator of a raster node
+    pub fn raster_operator(&self) -> Result<&dyn MetaRasterOperator> {
+        match &self.operator {
+            WorkflowOperator::Raster(operator) => Ok(operator.as_ref()),
+            WorkflowOperator::Vector(_) => Err(self.kind_mismatch(WorkflowNodeKind::Raster)),
+        }
+    }
+
+    /// get the operator of a vector node
+    pub fn vector_operator(&self) -> Result<&dyn MetaVectorOperator> {
+        match &self.operator {
+            WorkflowOperator::Vector(operator) => Ok(operator.as_ref()),
+            WorkflowOperator::Raster(_) => Err(self.kind_mismatch(WorkflowNodeKind::Vector)),
+        }
+    }
+
+    /// creates the processing operator of a raster node on the first call.
+    /// Every call returns a handle to the same operator.
+    pub fn create_raster_instance(&self) -> Result<BoxedRasterOperatorInstance> {
+        let mut fan_out = self.raster_instance.borrow_mut();
+        if fan_out.is_none() {
+            self.validate()?;
+            let instance = self.raster_operator()?.build_raster_op()?;
+            *fan_out = Some(
+                crate::dispatch_raster_types!(match_instance: instance, source => {
+                    let shared = SharedSource::new(source);
+                    Box::new(move || BoxedRasterOperatorInstance::new(Box::new(shared.clone())))
+                }),
+            );
+        }
+        Ok(fan_out.as_ref().map(|instance| instance()).unwrap())
+    }
+
+    /// creates the processing operator of a vector node on the first call.
+    /// Every call returns a handle to the same operator.
+    pub fn create_vector_instance(&self) -> Result<BoxedVectorOperatorInstance> {
+        let mut fan_out = self.vector_instance.borrow_mut();
+        if fan_out.is_none() {
+            self.validate()?;
+            *fan_out = Some(match self.vector_operator()?.build_vector_op()? {
+                BoxedVectorOperatorInstance::MultiPoints(source) => {
+                    let shared = SharedSource::new(source);
+                    Box::new(move || {
+                        BoxedVectorOperatorInstance::MultiPoints(Box::new(shared.clone()))
+                    })
+                }
+                BoxedVectorOperatorInstance::MultiLineStrings(source) => {
+                    let shared = SharedSource::new(source);
+                    Box::new(move || {
+                        BoxedVectorOperatorInstance::MultiLineStrings(Box::new(shared.clone()))
+                    })
+                }
+                BoxedVectorOperatorInstance::MultiPolygons(source) => {
+                    let shared = SharedSource::new(source);
+                    Box::new(move || {
+                        BoxedVectorOperatorInstance::MultiPolygons(Box::new(shared.clone()))
+                    })
+                }
+            });
+        }
+        Ok(fan_out.as_ref().map(|instance| instance()).unwrap())
+    }
+}
+
+/// A reference to a node of the surrounding workflow.
+pub trait NodeReference {
+    /// the id of the referenced node
+    fn node(&self) -> &str;
+
+    /// connects the reference to the node. Fails if the node is of another kind than the reference.
+    fn link(&self, node: &Rc<WorkflowNode>) -> Result<()>;
+}
+
+/// References a raster node of the surrounding workflow.
+///
+/// The MetaRasterNode references the raster node `node` of the surrounding workflow.
+/// It behaves like the operator of the node but shares its processing operator with all other references.
+/// The node is no source of the reference, so walking a graph stops at references.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MetaRasterNode {
+    pub node: String,
+    #[serde(skip)]
+    target: OnceCell<Rc<WorkflowNode>>,
+}
+
+impl MetaRasterNode {
+    pub fn new(node: &str) -> Self {
+        MetaRasterNode {
+            node: node.to_string(),
+            target: OnceCell::new(),
+        }
+    }
+
+    /// get the referenced node or an error if the reference is not part of a workflow
+    fn linked(&self) -> Result<&WorkflowNode> {
+        self.target
+            .get()
+            .map(AsRef::as_ref)
+            .ok_or_else(|| Error::UnknownNode(self.node.clone()))
+    }
+
+    fn create_typed_raster_op<T: BoxedRasterType>(
+        &self,
+    ) -> Result<Box<dyn RasterSource<RasterType = T>>> {
+        self.linked()?.create_raster_instance()?.get::<T>()
+    }
+}
+
+impl NodeReference for MetaRasterNode {
+    fn node(&self) -> &str {
+        &self.node
+    }
+
+    fn link(&self, node: &Rc<WorkflowNode>) -> Result<()> {
+        node.raster_operator()?;
+        // a reference is only linked once, by the workflow that contains it
+        let _ = self.target.set(node.clone());
+        Ok(())
+    }
+}
+
+impl MetaOperator for MetaRasterNode {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        &[]
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &[]
+    }
+
+    fn node_references(&self) -> Vec<&dyn NodeReference> {
+        vec![self]
+    }
+}
+
+#[typetag::serde]
+impl MetaRasterOperator for MetaRasterNode {
+    /// the type of the linked node, which must be valid
+    fn raster_creates(&self) -> Result<RasterCreates> {
+        let node = self.linked()?;
+        node.validate()?;
+        node.creates_type().map(RasterCreates::SecificType)
+    }
+
+    fn band_count(&self) -> Result<usize> {
+        self.linked()?.band_count()
+    }
+
+    fn result_descriptor(&self) -> Result<RasterResultDescriptor> {
+        self.linked()?.raster_descriptor()
+    }
+
+    crate::dispatch_raster_types!(create_ops: create_typed_raster_op);
+}
+
+crate::register_operator! {
+    MetaRasterNode: Raster
+}
+
+/// References a vector node of the surrounding workflow.
+///
+/// The MetaVectorNode references the vector node `node` of the surrounding workflow.
+/// It behaves like the operator of the node but shares its processing operator with all other references.
+/// The node is no source of the reference, so walking a graph stops at references.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MetaVectorNode {
+    pub node: String,
+    #[serde(skip)]
+    target: OnceCell<Rc<WorkflowNode>>,
+}
+
+impl MetaVectorNode {
+    pub fn new(node: &str) -> Self {
+        MetaVectorNode {
+            node: node.to_string(),
+            target: OnceCell::new(),
+        }
+    }
+
+    /// get the referenced node or an error if the reference is not part of a workflow
+    fn linked(&self) -> Result<&WorkflowNode> {
+        self.target
+            .get()
+            .map(AsRef::as_ref)
+            .ok_or_else(|| Error::UnknownNode(self.node.clone()))
+    }
+}
+
+impl NodeReference for MetaVectorNode {
+    fn node(&self) -> &str {
+        &self.node
+    }
+
+    fn link(&self, node: &Rc<WorkflowNode>) -> Result<()> {
+        node.vector_operator()?;
+        // a reference is only linked once, by the workflow that contains it
+        let _ = self.target.set(node.clone());
+        Ok(())
+    }
+}
+
+impl MetaOperator for MetaVectorNode {
+    fn raster_sources(&self) -> &[Box<dyn MetaRasterOperator>] {
+        &[]
+    }
+
+    fn raster_wants(&self) -> &[RasterWants] {
+        &[]
+    }
+
+    fn node_references(&self) -> Vec<&dyn NodeReference> {
+        vec![self]
+    }
+}
+
+#[typetag::serde]
+impl MetaVectorOperator for MetaVectorNode {
+    fn creates_collection_type(&self) -> Result<VectorCollectionType> {
+        self.linked()?.collection_type()
+    }
+
+    /// the schema of the linked node, which must be valid
+    fn attribute_schema(&self) -> Result<AttributeSchema> {
+        let node = self.linked()?;
+        node.validate()?;
+        node.attribute_schema()
+    }
+
+    fn result_descriptor(&self) -> Result<VectorResultDescriptor> {
+        self.linked()?.vector_descriptor()
+    }
+
+    fn build_vector_op(&self) -> Result<BoxedVectorOperatorInstance> {
+        self.linked()
+            .and_then(WorkflowNode::create_vector_instance)
+            .map_err(|error| error.in_operator(self.typetag_name()))
+    }
+}
+
+crate::register_operator! {
+    MetaVectorNode: Vector
+}
+
+/// A workflow is a graph of named nodes. Operators reference other nodes with a `MetaRasterNode`
+/// or a `MetaVectorNode`, so a node can feed several operators. `output` is the node that produces the result.
+#[derive(Deserialize)]
+#[serde(try_from = "WorkflowDefinition")]
+pub struct Workflow {
+    nodes: BTreeMap<String, Rc<WorkflowNode>>,
+    output: String,
+}
+
+#[derive(Deserialize)]
+struct WorkflowDefinition {
+    nodes: BTreeMap<String, WorkflowOperator>,
+    output: String,
+}
+
+impl TryFrom<WorkflowDefinition> for Workflow {
+    type Error = Error;
+
+    fn try_from(definition: WorkflowDefinition) -> Result<Self> {
+        Workflow::new(definition.nodes, definition.output)
+    }
+}
+
+impl Serialize for Workflow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let nodes: BTreeMap<&str, &WorkflowOperator> = self
+            .nodes
+            .iter()
+            .map(|(id, node)| (id.as_str(), &node.operator))
+            .collect();
+        let mut workflow = serializer.serialize_struct("Workflow", 2)?;
+        workflow.serialize_field("nodes", &nodes)?;
+        workflow.serialize_field("output", &self.output)?;
+        workflow.end()
+    }
+}
+
+impl Workflow {
+    /// links the references between the nodes. Fails if a node is missing, has the wrong kind or the nodes form a cycle.
+    pub fn new(nodes: BTreeMap<String, WorkflowOperator>, output: String) -> Result<Self> {
+        let workflow = Workflow {
+            nodes: nodes
+                .into_iter()
+                .map(|(id, operator)| (id.clone(), Rc::new(WorkflowNode::new(id, operator))))
+                .collect(),
+            output,
+        };
+        workflow.node(&workflow.output)?;
+
+        for node in workflow.nodes.values() {
+            for reference in node.operator.node_references() {
+                workflow
+                    .node(reference.node())
+                    .map_err(|error| error.in_operator(&format!("node {}", node.id)))?;
+            }
+        }
+        // the references of a cycle would keep its nodes alive forever
+        workflow.check_cycles()?;
+
+        for node in workflow.nodes.values() {
+            for reference in node.operator.node_references() {
+                reference
+                    .link(&workflow.nodes[reference.node()])
+                    .map_err(|error| error.in_operator(&format!("node {}", node.id)))?;
+            }
+        }
+        Ok(workflow)
+    }
+
+    /// get a node by its id
+    pub fn node(&self, id: &str) -> Result<&WorkflowNode> {
+        self.nodes
+            .get(id)
+            .map(AsRef::as_ref)
+            .ok_or_else(|| Error::UnknownNode(id.to_string()))
+    }
+
+    /// get the node that produces the result
+    pub fn output(&self) -> &WorkflowNode {
+        &self.nodes[&self.output]
+    }
+
+    /// get the output as a raster operator. Fails if the output is a vector node.
+    pub fn raster_output(&self) -> Result<MetaRasterNode> {
+        let reference = MetaRasterNode::new(&self.output);
+        reference.link(&self.nodes[&self.output])?;
+        Ok(reference)
+    }
+
+    /// get the output as a vector operator. Fails if the output is a raster node.
+    pub fn vector_output(&self) -> Result<MetaVectorNode> {
+        let reference = MetaVectorNode::new(&self.output);
+        reference.link(&self.nodes[&self.output])?;
+        Ok(reference)
+    }
+
+    fn check_cycles(&self) -> Result<()> {
+        let mut finished = HashSet::new();
+        for id in self.nodes.keys() {
+            self.visit(id, &mut Vec::new(), &mut finished)?;
+        }
+        Ok(())
+    }
+
+    /// a depth-first search that fails if it reaches a node on the current `path`
+    fn visit<'a>(
+        &'a self,
+        id: &'a str,
+        path: &mut Vec<&'a str>,
+        finished: &mut HashSet<&'a str>,
+    ) -> Result<()> {
+        if finished.contains(id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|&node| node == id) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|&node| node.into()).collect();
+            cycle.push(id.to_string());
+            return Err(Error::CyclicWorkflow(cycle));
+        }
+
+        path.push(id);
+        for reference in self.nodes[id].operator.node_references() {
+            self.visit(reference.node(), path, finished)?;
+        }
+        path.pop();
+        finished.insert(id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{query, write_test_geotiff},
+        validate_raster_graph, validate_vector_graph, Coordinate2D, GeoTransform, GridShape,
+        Raster, SpatialResolution, TimeInterval,
+    };
+    use std::cell::Cell;
+
+    fn workflow(value: serde_json::Value) -> Result<Workflow, serde_json::Error> {
+        serde_json::from_value(value)
+    }
+
+    #[test]
+    fn computes_shared_results_once() {
+        struct CountingSource(Rc<Cell<usize>>);
+
+        impl Source for CountingSource {
+            type Output = Raster<u8>;
+            fn query(&self, _query: Query) -> Result<Self::Output> {
+                self.0.set(self.0.get() + 1);
+                Ok(Raster::new(
+                    GridShape::new(1, 1),
+                    GeoTransform::new_north_up(
+                        Coordinate2D::new(0., 1.),
+                        SpatialResolution::new_square(1.).unwrap(),
+                    ),
+                    vec![7],
+                    None,
+                ))
+            }
+        }
+
+        let queries = Rc::new(Cell::new(0));
+        let shared = SharedSource::new(CountingSource(queries.clone()));
+        let copy = shared.clone();
+        assert_eq!(shared.query(query()).unwrap().data, vec![7]);
+        assert_eq!(copy.query(query()).unwrap().data, vec![7]);
+        assert_eq!(queries.get(), 1);
+
+        let other_query = Query {
+            time_interval: TimeInterval::new(1, 2).unwrap(),
+            ..query()
+        };
+        copy.query(other_query).unwrap();
+        assert_eq!(queries.get(), 2);
+    }
+
+    #[test]
+    fn reuses_nodes() {
+        let workflow = workflow(serde_json::json!({
+            "nodes": {
+                "input": {
+                    "type": "MetaGdalSource",
+                    "dataset": write_test_geotiff(
+                        "workflow_reuses_nodes.tif",
+                        GridShape::new(2, 2),
+                        vec![1u8, 2, 3, 4],
+                    ),
+                },
+                "plus_one": {
+                    "type": "MetaPlusOneOperator",
+                    "sources": [{"type": "MetaRasterNode", "node": "input"}],
+                },
+                "doubled": {
+                    "type": "MetaAddRasterOperator",
+                    "sources": [
+                        {"type": "MetaRasterNode", "node": "plus_one"},
+                        {"type": "MetaRasterNode", "node": "plus_one"},
+                    ],
+                },
+            },
+            "output": "doubled",
+        }))
+        .unwrap();
+        assert_eq!(workflow.output().id, "doubled");
+
+        let output = workflow.raster_output().unwrap();
+        assert_eq!(
+            validate_raster_graph(&output).unwrap(),
+            crate::RasterType::U8
+        );
+        let raster = output
+            .create_raster_op()
+            .unwrap()
+            .get::<u8>()
+            .unwrap()
+            .raster_query(query())
+            .unwrap();
+        assert_eq!(raster.data, vec![4, 6, 8, 10]);
+
+        assert_eq!(
+            workflow.vector_output().err().unwrap().to_string(),
+            "the workflow node doubled is not a Vector node"
+        );
+    }
+
+    #[test]
+    fn reports_invalid_shared_nodes_once() {
+        let workflow = workflow(serde_json::json!({
+            "nodes": {
+                "empty": {"type": "MetaNoopOperator", "sources": []},
+                "doubled": {
+                    "type": "MetaAddRasterOperator",
+                    "sources": [
+                        {"type": "MetaRasterNode", "node": "empty"},
+                        {"type": "MetaRasterNode", "node": "empty"},
+                    ],
+                },
+            },
+            "output": "doubled",
+        }))
+        .unwrap();
+        let output = workflow.raster_output().unwrap();
+        match validate_raster_graph(&output) {
+            Err(Error::InvalidGraph(errors)) => {
+                let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                assert_eq!(
+                    messages,
+                    vec![
+                        "MetaRasterNode -> node doubled -> MetaAddRasterOperator -> raster source 0 -> \
+                         MetaRasterNode -> node empty -> MetaNoopOperator: the raster source 0 is missing",
+                        "MetaRasterNode -> node doubled -> MetaAddRasterOperator -> raster source 1 -> \
+                         MetaRasterNode: the workflow node empty is invalid",
+                    ]
+                );
+            }
+            result => panic!("expected an invalid graph, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn links_vector_nodes() {
+        let workflow = workflow(serde_json::json!({
+            "nodes": {
+                "points": {"type": "MetaMyVectorSourceOperator"},
+                "sampled": {
+                    "type": "MetaRasterVectorOperator",
+                    "raster_sources": [{
+                        "type": "MetaGdalSource",
+                        "dataset": write_test_geotiff(
+                            "workflow_vector_nodes.tif",
+                            GridShape::new(2, 2),
+                            vec![1u8, 2, 3, 4],
+                        ),
+                    }],
+                    "vector_sources": [{"type": "MetaVectorNode", "node": "points"}],
+                },
+            },
+            "output": "sampled",
+        }))
+        .unwrap();
+        let output = workflow.vector_output().unwrap();
+        validate_vector_graph(&output).unwrap();
+        assert_eq!(
+            output.creates_collection_type().unwrap(),
+            VectorCollectionType::MultiPoint
+        );
+    }
+
+    #[test]
+    fn serializes_nodes() {
+        let definition = serde_json::json!({
+            "nodes": {
+                "a": {"type": "MetaGdalSource", "dataset": "a.tif"},
+                "b": {"type": "MetaNoopOperator", "sources": [{"type": "MetaRasterNode", "node": "a"}]},
+            },
+            "output": "b",
+        });
+        let workflow = workflow(definition.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&workflow).unwrap(), definition);
+    }
+
+    #[test]
+    fn rejects_invalid_references() {
+        let error = |value| workflow(value).err().unwrap().to_string();
+        let noop = |node: &str| {
+            serde_json::json!({
+                "type": "MetaNoopOperator",
+                "sources": [{"type": "MetaRasterNode", "node": node}],
+            })
+        };
+
+        assert_eq!(
+            error(serde_json::json!({"nodes": {"a": noop("b")}, "output": "a"})),
+            "node a: there is no workflow node b"
+        );
+        assert_eq!(
+            error(serde_json::json!({"nodes": {"a": noop("a")}, "output": "c"})),
+            "there is no workflow node c"
+        );
+        assert_eq!(
+            error(serde_json::json!({
+                "nodes": {"a": noop("b"), "b": noop("c"), "c": noop("b")},
+                "output": "a",
+            })),
+            "the workflow nodes form a cycle: b -> c -> b"
+        );
+        assert_eq!(
+            error(serde_json::json!({
+                "nodes": {"a": noop("b"), "b": {"type": "MetaMyVectorSourceOperator"}},
+                "output": "a",
+            })),
+            "node a: the workflow node b is not a Raster node"
+        );
+    }
+}